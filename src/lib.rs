@@ -10,6 +10,7 @@
 
 pub mod vulnerabilities;
 pub mod utils;
+pub mod render;
 
 /// Version of the library
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -82,6 +83,39 @@ pub enum Error {
     #[error("Storage management issue detected")]
     StorageManagement,
     
+    #[error("Clock cannot move backwards from {current} to {target}")]
+    ClockRegression { current: u64, target: u64 },
+    
+    #[error("Account not found")]
+    AccountNotFound,
+    
+    #[error("Insufficient balance: have {have}, need {need}")]
+    InsufficientBalance { have: u64, need: u64 },
+    
+    #[error("Serialization failed: {0}")]
+    Serialization(String),
+    
+    #[error("Account too small: needed {needed} bytes but only {available} are allocated")]
+    AccountTooSmall { needed: usize, available: usize },
+    
+    #[error("Realloc increase of {increase} bytes exceeds the {max} byte limit")]
+    ReallocTooLarge { increase: usize, max: usize },
+    
+    #[error("Too many values: at most {max} may be stored")]
+    TooManyValues { max: usize },
+    
+    #[error("Rate limit exceeded: requested {requested} but only {remaining} remains in the window")]
+    RateLimitExceeded { requested: u64, remaining: u64 },
+    
+    #[error("Contract is paused")]
+    Paused,
+    
+    #[error("Contract is not paused")]
+    NotPaused,
+    
+    #[error("Account is not the program-derived address for the given seeds")]
+    InvalidPda,
+    
     #[error("Generic error: {0}")]
     Generic(String),
 }
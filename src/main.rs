@@ -1,77 +1,93 @@
+use clap::{builder::PossibleValuesParser, Parser, Subcommand};
+use rust_smart_contracts_vulns::render::{render_catalog_markdown, render_vulnerability, render_vulnerability_json};
 use rust_smart_contracts_vulns::vulnerabilities::*;
-use std::env;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+#[derive(Parser)]
+#[command(name = "rust-smart-contracts-vulns", version, about = "Rust Smart Contract Vulnerabilities Guide")]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the writeup for a vulnerability
+    Show {
+        /// Short name of the vulnerability (see `list`)
+        #[arg(value_parser = PossibleValuesParser::new(VULNERABILITY_SLUGS), ignore_case = true)]
+        name: String,
+        
+        /// Print the writeup as a JSON report instead of text
+        #[arg(long)]
+        json: bool,
+    },
     
-    println!("Rust Smart Contract Vulnerabilities Guide");
-    println!("========================================");
+    /// List the available vulnerabilities
+    List {
+        /// Only list vulnerabilities affecting this platform
+        #[arg(long)]
+        platform: Option<String>,
+    },
     
-    if args.len() > 1 {
-        match args[1].to_lowercase().as_str() {
-            "reentrancy" => print_vulnerability(&reentrancy::ReentrancyVulnerability),
-            "overflow" => print_vulnerability(&overflow::OverflowVulnerability),
-            "unchecked" => print_vulnerability(&unchecked_inputs::UncheckedInputsVulnerability),
-            "oracle" => print_vulnerability(&oracle_manipulation::OracleManipulationVulnerability),
-            "access" => print_vulnerability(&access_control::AccessControlVulnerability),
-            "dos" => print_vulnerability(&denial_of_service::DoSVulnerability),
-            "fee" => print_vulnerability(&illicit_fee_collection::IllicitFeeVulnerability),
-            "flash" => print_vulnerability(&flash_loan::FlashLoanVulnerability),
-            "logic" => print_vulnerability(&logic_errors::LogicErrorVulnerability),
-            "random" => print_vulnerability(&random_manipulation::RandomManipulationVulnerability),
-            "list" => list_vulnerabilities(),
-            _ => {
-                println!("Unknown vulnerability type: {}", args[1]);
-                println!("Use 'list' to see all available vulnerabilities");
+    /// Print the writeup for a vulnerability as a JSON report
+    Json {
+        /// Short name of the vulnerability (see `list`)
+        #[arg(value_parser = PossibleValuesParser::new(VULNERABILITY_SLUGS), ignore_case = true)]
+        name: String,
+    },
+    
+    /// Find vulnerabilities whose writeup mentions every word of a query
+    Search {
+        /// Words to look for (case-insensitive)
+        query: String,
+    },
+    
+    /// Print every writeup as one Markdown report, for audit deliverables
+    Report,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    
+    match &cli.command {
+        Commands::Show { name, json: false } => {
+            println!("Rust Smart Contract Vulnerabilities Guide");
+            println!("========================================");
+            print!("{}", render_vulnerability(lookup(name).as_ref()));
+        },
+        Commands::Show { name, json: true } | Commands::Json { name } => {
+            println!("{}", render_vulnerability_json(lookup(name).as_ref()));
+        },
+        Commands::List { platform: Some(platform) } => list_vulnerabilities_for_platform(platform),
+        Commands::List { platform: None } => list_vulnerabilities(),
+        Commands::Search { query } => {
+            let matches = search_vulnerabilities(query);
+            if matches.is_empty() {
+                println!("No vulnerabilities match \"{}\"", query);
             }
-        }
-    } else {
-        print_usage();
+            for vuln in matches {
+                println!("  - {}", vuln.name());
+            }
+        },
+        Commands::Report => print!("{}", render_catalog_markdown()),
     }
 }
 
-fn print_usage() {
-    println!("Usage: rust-smart-contracts-vulns [vulnerability-type]");
-    println!("Example: rust-smart-contracts-vulns reentrancy");
-    println!("Use 'list' to see all available vulnerabilities");
+/// The vulnerability for a name clap has already checked against the registry
+fn lookup(name: &str) -> Box<dyn Vulnerability> {
+    vulnerability_by_slug(name).expect("clap only accepts registered slugs")
 }
 
-fn list_vulnerabilities() {
-    println!("Available vulnerability types:");
-    println!("  - reentrancy: Reentrancy attacks");
-    println!("  - overflow: Integer overflow/underflow");
-    println!("  - unchecked: Unchecked inputs");
-    println!("  - oracle: Oracle manipulation");
-    println!("  - access: Access control issues");
-    println!("  - dos: Denial of service");
-    println!("  - fee: Illicit fee collection");
-    println!("  - flash: Flash loan attacks");
-    println!("  - logic: Logic errors");
-    println!("  - random: Random number manipulation");
+fn list_vulnerabilities_for_platform(platform: &str) {
+    println!("Vulnerabilities affecting {}:", platform);
+    for vuln in vulnerabilities_for_platform(platform) {
+        println!("  - {}", vuln.name());
+    }
 }
 
-fn print_vulnerability(vuln: &dyn Vulnerability) {
-    println!("\n{}", vuln.name());
-    println!("{}", "=".repeat(vuln.name().len()));
-    
-    println!("\nDescription:");
-    println!("{}", vuln.description());
-    
-    println!("\nAffected Platforms:");
-    for platform in vuln.affected_platforms() {
-        println!("  - {}", platform);
-    }
-    
-    println!("\nExample Vulnerability:");
-    println!("{}", vuln.exploit_example());
-    
-    println!("\nDetection Methods:");
-    for method in vuln.detection_methods() {
-        println!("  - {}", method);
-    }
-    
-    println!("\nRemediation Strategies:");
-    for strategy in vuln.remediation() {
-        println!("  - {}", strategy);
+fn list_vulnerabilities() {
+    println!("Available vulnerability types:");
+    for (slug, vuln) in VULNERABILITY_SLUGS.iter().zip(all_vulnerabilities()) {
+        println!("  - {}: {}", slug, vuln.name());
     }
 }
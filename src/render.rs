@@ -0,0 +1,295 @@
+//! Text, JSON and Markdown rendering of vulnerability writeups shared by the command-line tools
+
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::vulnerabilities::{all_vulnerabilities, Category, Exploitability, Severity, SimulationResult, Vulnerability};
+
+/// Everything a writeup contains, in a form that serializes for tooling
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VulnerabilityReport {
+    pub name: String,
+    pub description: String,
+    pub affected_platforms: Vec<String>,
+    pub categories: Vec<Category>,
+    pub exploitability: Exploitability,
+    pub severity: Severity,
+    pub exploit_example: String,
+    pub secure_example: String,
+    pub detection_methods: Vec<String>,
+    pub remediation: Vec<String>,
+    pub simulation: Option<SimulationResult>,
+}
+
+impl VulnerabilityReport {
+    /// Collect the writeup for `vuln`, running its simulation if it has one
+    pub fn new(vuln: &dyn Vulnerability) -> Self {
+        Self {
+            name: vuln.name().to_string(),
+            description: vuln.description().to_string(),
+            affected_platforms: owned(vuln.affected_platforms()),
+            categories: vuln.categories(),
+            exploitability: vuln.exploitability(),
+            severity: vuln.severity(),
+            exploit_example: vuln.exploit_example().to_string(),
+            secure_example: vuln.secure_example().to_string(),
+            detection_methods: owned(vuln.detection_methods()),
+            remediation: owned(vuln.remediation()),
+            simulation: vuln.simulate(),
+        }
+    }
+}
+
+/// Copy borrowed writeup text into the report so it can also be decoded
+fn owned(items: Vec<&'static str>) -> Vec<String> {
+    items.into_iter().map(String::from).collect()
+}
+
+/// Render the writeup for a vulnerability as pretty-printed JSON
+pub fn render_vulnerability_json(vuln: &dyn Vulnerability) -> String {
+    serde_json::to_string_pretty(&VulnerabilityReport::new(vuln)).expect("reports always serialize")
+}
+
+/// Reports for every vulnerability in the catalog, in catalog order
+pub fn catalog() -> Vec<VulnerabilityReport> {
+    all_vulnerabilities().iter().map(|vuln| VulnerabilityReport::new(vuln.as_ref())).collect()
+}
+
+/// Encode the whole catalog as CBOR, a compact binary alternative to JSON
+#[cfg(feature = "cbor")]
+pub fn catalog_cbor() -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::ser::into_writer(&catalog(), &mut out).expect("reports always serialize");
+    out
+}
+
+/// Decode CBOR produced by [`catalog_cbor`] back into the catalog's reports
+#[cfg(feature = "cbor")]
+pub fn decode_catalog_cbor(bytes: &[u8]) -> Result<Vec<VulnerabilityReport>, crate::Error> {
+    ciborium::de::from_reader(bytes).map_err(|e| crate::Error::Serialization(e.to_string()))
+}
+
+/// GitHub's anchor for a heading: lowercase, punctuation dropped, spaces as dashes
+pub fn markdown_anchor(heading: &str) -> String {
+    heading.to_lowercase().chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-' || *c == '_')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// Fence `code` as a Markdown code block tagged with `lang`
+///
+/// Blank lines around the code and its common indentation are removed, and the
+/// fence is made longer than any run of backticks inside the code so the block
+/// can't be closed early.
+pub fn markdown_code_block(code: &str, lang: &str) -> String {
+    let lines: Vec<&str> = code.lines()
+        .skip_while(|line| line.trim().is_empty())
+        .collect();
+    let end = lines.iter().rposition(|line| !line.trim().is_empty()).map_or(0, |i| i + 1);
+    let lines = &lines[..end];
+    
+    let indent = lines.iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    
+    let longest_run = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    
+    let mut out = format!("{}{}\n", fence, lang);
+    for line in lines {
+        out.push_str(line.get(indent..).unwrap_or("").trim_end());
+        out.push('\n');
+    }
+    out.push_str(&fence);
+    out.push('\n');
+    out
+}
+
+/// Render every vulnerability in the catalog as a GitHub-flavored Markdown report
+///
+/// The report opens with a table of contents linking to one `## ` section per
+/// vulnerability, each with its exploit and fix in fenced `rust` blocks.
+pub fn render_catalog_markdown() -> String {
+    let reports = catalog();
+    let mut out = String::new();
+    
+    writeln!(out, "# Rust Smart Contract Vulnerabilities\n").unwrap();
+    writeln!(out, "## Contents\n").unwrap();
+    for report in &reports {
+        writeln!(out, "- [{}](#{})", report.name, markdown_anchor(&report.name)).unwrap();
+    }
+    
+    for report in &reports {
+        writeln!(out, "\n## {}\n", report.name).unwrap();
+        writeln!(out, "**Exploitability:** {}  ", exploitability_label(report.exploitability)).unwrap();
+        writeln!(out, "**Severity:** {:?}  ", report.severity).unwrap();
+        writeln!(out, "**Platforms:** {}\n", report.affected_platforms.join(", ")).unwrap();
+        writeln!(out, "{}", report.description).unwrap();
+        
+        writeln!(out, "\n### Example Vulnerability\n").unwrap();
+        out.push_str(&markdown_code_block(&report.exploit_example, "rust"));
+        
+        writeln!(out, "\n### Secure Implementation\n").unwrap();
+        out.push_str(&markdown_code_block(&report.secure_example, "rust"));
+        
+        writeln!(out, "\n### Detection Methods\n").unwrap();
+        for method in &report.detection_methods {
+            writeln!(out, "- {}", method).unwrap();
+        }
+        
+        writeln!(out, "\n### Remediation Strategies\n").unwrap();
+        for strategy in &report.remediation {
+            writeln!(out, "- {}", strategy).unwrap();
+        }
+    }
+    
+    out
+}
+
+/// Exploitability with an emoji so the level stands out in rendered Markdown
+fn exploitability_label(exploitability: Exploitability) -> &'static str {
+    match exploitability {
+        Exploitability::Trivial => "🔴 Trivial",
+        Exploitability::Moderate => "🟠 Moderate",
+        Exploitability::Advanced => "🟡 Advanced",
+    }
+}
+
+/// Render the full writeup for a vulnerability: description, platforms, example and its fix,
+/// detection methods, remediation and, when available, the attack simulation
+pub fn render_vulnerability(vuln: &dyn Vulnerability) -> String {
+    // Writing to a String cannot fail
+    let mut out = String::new();
+    
+    writeln!(out, "\n{}", vuln.name()).unwrap();
+    writeln!(out, "{}", "=".repeat(vuln.name().len())).unwrap();
+    
+    writeln!(out, "\nDescription:").unwrap();
+    writeln!(out, "{}", vuln.description()).unwrap();
+    
+    writeln!(out, "\nAffected Platforms:").unwrap();
+    for platform in vuln.affected_platforms() {
+        writeln!(out, "  - {}", platform).unwrap();
+    }
+    
+    writeln!(out, "\nExample Vulnerability:").unwrap();
+    writeln!(out, "{}", vuln.exploit_example()).unwrap();
+    
+    writeln!(out, "\nSecure Implementation:").unwrap();
+    writeln!(out, "{}", vuln.secure_example()).unwrap();
+    
+    writeln!(out, "\nDetection Methods:").unwrap();
+    for method in vuln.detection_methods() {
+        writeln!(out, "  - {}", method).unwrap();
+    }
+    
+    writeln!(out, "\nRemediation Strategies:").unwrap();
+    for strategy in vuln.remediation() {
+        writeln!(out, "  - {}", strategy).unwrap();
+    }
+    
+    if let Some(simulation) = vuln.simulate() {
+        writeln!(out, "\nAttack Simulation:").unwrap();
+        for (i, step) in simulation.steps.iter().enumerate() {
+            writeln!(out, "  {}. {}", i + 1, step).unwrap();
+        }
+        let outcome = if simulation.succeeded { "attack succeeded" } else { "attack did not succeed" };
+        writeln!(out, "  Result: {}", outcome).unwrap();
+    }
+    
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulnerabilities::{denial_of_service, reentrancy};
+    
+    #[test]
+    fn test_render_underlines_name_to_its_length() {
+        let rendered = render_vulnerability(&denial_of_service::DoSVulnerability);
+        let mut lines = rendered.lines().skip_while(|line| line.is_empty());
+        
+        let name = lines.next().unwrap();
+        assert_eq!(name, "Denial of Service Vulnerability");
+        assert_eq!(lines.next().unwrap(), "=".repeat(name.len()));
+    }
+    
+    #[test]
+    fn test_render_shows_fix_after_exploit() {
+        let rendered = render_vulnerability(&reentrancy::ReentrancyVulnerability);
+        let exploit = rendered.find("\nExample Vulnerability:\n").unwrap();
+        let fix = rendered.find("\nSecure Implementation:\n").unwrap();
+        
+        assert!(exploit < fix);
+        assert!(rendered[fix..].contains("reentrancy_lock"));
+    }
+    
+    #[test]
+    fn test_render_includes_simulation_when_available() {
+        let rendered = render_vulnerability(&reentrancy::ReentrancyVulnerability);
+        assert!(rendered.contains("\nAttack Simulation:\n"));
+        assert!(rendered.contains("  Result: attack succeeded"));
+        
+        let rendered = render_vulnerability(&denial_of_service::DoSVulnerability);
+        assert!(!rendered.contains("Attack Simulation:"));
+    }
+    
+    #[test]
+    fn test_json_report_mirrors_writeup() {
+        let json = render_vulnerability_json(&reentrancy::ReentrancyVulnerability);
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        
+        assert_eq!(report["name"], "Reentrancy Vulnerability");
+        assert!(!report["remediation"].as_array().unwrap().is_empty());
+        assert_eq!(report["simulation"]["succeeded"], true);
+        
+        let json = render_vulnerability_json(&denial_of_service::DoSVulnerability);
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(report["simulation"].is_null());
+    }
+    
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_catalog_round_trips_to_catalog() {
+        let bytes = catalog_cbor();
+        let decoded = decode_catalog_cbor(&bytes).unwrap();
+        
+        assert_eq!(decoded, catalog());
+        assert_eq!(decoded.len(), all_vulnerabilities().len());
+        assert!(bytes.len() < serde_json::to_vec(&catalog()).unwrap().len());
+        
+        assert!(decode_catalog_cbor(&bytes[..bytes.len() / 2]).is_err());
+    }
+    
+    #[test]
+    fn test_catalog_markdown_has_section_and_fenced_blocks_per_vulnerability() {
+        let markdown = render_catalog_markdown();
+        
+        for vuln in all_vulnerabilities() {
+            assert!(markdown.lines().any(|line| line == format!("## {}", vuln.name())), "no heading for {}", vuln.name());
+            assert!(markdown.contains(&format!("(#{})", markdown_anchor(vuln.name()))));
+            
+            let section = markdown.split(&format!("\n## {}\n", vuln.name())).nth(1).unwrap();
+            assert!(section.contains(&format!("\n**Severity:** {:?}  \n", vuln.severity())), "no severity for {}", vuln.name());
+        }
+        
+        // Every fence that opens a rust block is closed by a bare fence
+        let opening = markdown.lines().filter(|line| *line == "```rust").count();
+        let closing = markdown.lines().filter(|line| *line == "```").count();
+        assert_eq!(opening, all_vulnerabilities().len() * 2);
+        assert_eq!(opening, closing);
+    }
+    
+    #[test]
+    fn test_markdown_code_block_outlasts_backticks_and_dedents() {
+        let block = markdown_code_block("\n        let s = \"```\";\n            s.len()\n    ", "rust");
+        assert_eq!(block, "````rust\nlet s = \"```\";\n    s.len()\n````\n");
+        
+        assert_eq!(markdown_anchor("Front-Running (MEV) Attack"), "front-running-mev-attack");
+    }
+}
@@ -3,7 +3,13 @@
 //! This module contains helper code used across the vulnerability examples,
 //! including mock blockchain environments, account structures, and testing utilities.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::Error;
+
+/// Maximum number of bytes an account may grow by in a single realloc,
+/// mirroring Solana's `MAX_PERMITTED_DATA_INCREASE`
+pub const MAX_PERMITTED_DATA_INCREASE: usize = 10 * 1024;
 
 /// Mock account structure for examples
 #[derive(Debug, Clone)]
@@ -13,6 +19,7 @@ pub struct Account {
     pub owner: Option<[u8; 32]>,
     pub data: Vec<u8>,
     pub executable: bool,
+    pub size: usize, // Allocated data size; writes beyond it are rejected
 }
 
 impl Account {
@@ -23,6 +30,7 @@ impl Account {
             owner: None,
             data: Vec::new(),
             executable: false,
+            size: 0,
         }
     }
     
@@ -32,10 +40,31 @@ impl Account {
     }
 }
 
+/// Snapshot of the chain's time state, like Solana's `Clock` sysvar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clock {
+    pub slot: u64,
+    pub unix_timestamp: u64,
+}
+
+/// Account state captured by `MockBlockchain::snapshot`
+#[derive(Debug, Clone)]
+pub struct BlockchainSnapshot {
+    accounts: BTreeMap<[u8; 32], Account>,
+    clock: Clock,
+}
+
+impl BlockchainSnapshot {
+    /// Slot and timestamp at which the snapshot was taken
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+}
+
 /// Mock blockchain environment for examples
 #[derive(Debug, Default)]
 pub struct MockBlockchain {
-    accounts: HashMap<[u8; 32], Account>,
+    accounts: BTreeMap<[u8; 32], Account>,
     current_block: u64,
     timestamp: u64,
 }
@@ -43,7 +72,7 @@ pub struct MockBlockchain {
 impl MockBlockchain {
     pub fn new() -> Self {
         Self {
-            accounts: HashMap::new(),
+            accounts: BTreeMap::new(),
             current_block: 1,
             timestamp: 1621500000, // Example timestamp
         }
@@ -61,9 +90,206 @@ impl MockBlockchain {
         self.accounts.get_mut(address)
     }
     
+    /// Create an account owned by `owner` with `size` bytes of allocated data
+    pub fn create_account(&mut self, key: [u8; 32], owner: [u8; 32], size: usize) {
+        let mut account = Account::new(key);
+        account.owner = Some(owner);
+        account.size = size;
+        self.add_account(account);
+    }
+    
+    /// Resize an account's data allocation
+    ///
+    /// Growth is capped at `MAX_PERMITTED_DATA_INCREASE` per call; shrinking
+    /// truncates any data beyond the new size.
+    pub fn realloc(&mut self, key: [u8; 32], new_size: usize) -> Result<(), Error> {
+        let account = self.accounts.get_mut(&key).ok_or(Error::AccountNotFound)?;
+        
+        let increase = new_size.saturating_sub(account.size);
+        if increase > MAX_PERMITTED_DATA_INCREASE {
+            return Err(Error::ReallocTooLarge {
+                increase,
+                max: MAX_PERMITTED_DATA_INCREASE,
+            });
+        }
+        
+        account.data.truncate(new_size);
+        account.size = new_size;
+        Ok(())
+    }
+    
+    /// Replace an account's data, rejecting writes larger than its allocation
+    pub fn write_data(&mut self, key: [u8; 32], data: &[u8]) -> Result<(), Error> {
+        let account = self.accounts.get_mut(&key).ok_or(Error::AccountNotFound)?;
+        
+        if data.len() > account.size {
+            return Err(Error::AccountTooSmall {
+                needed: data.len(),
+                available: account.size,
+            });
+        }
+        
+        account.data = data.to_vec();
+        Ok(())
+    }
+    
     pub fn advance_block(&mut self) {
-        self.current_block += 1;
-        self.timestamp += 1; // Simplified: 1 second per block
+        self.advance_slots(1);
+    }
+    
+    /// Current slot (block height)
+    pub fn current_slot(&self) -> u64 {
+        self.current_block
+    }
+    
+    /// Current unix timestamp in seconds
+    pub fn unix_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+    
+    /// Read the current slot and timestamp together
+    pub fn clock(&self) -> Clock {
+        Clock {
+            slot: self.current_block,
+            unix_timestamp: self.timestamp,
+        }
+    }
+    
+    /// Advance `n` slots, moving the timestamp forward with them
+    pub fn advance_slots(&mut self, n: u64) {
+        self.current_block = self.current_block.saturating_add(n);
+        self.timestamp = self.timestamp.saturating_add(n); // Simplified: 1 second per block
+    }
+    
+    /// Capture every account so a failed transaction can be rolled back
+    pub fn snapshot(&self) -> BlockchainSnapshot {
+        BlockchainSnapshot {
+            accounts: self.accounts.clone(),
+            clock: self.clock(),
+        }
+    }
+    
+    /// Roll all accounts back to `snapshot`, as a reverted transaction would
+    ///
+    /// Accounts created since the snapshot are removed. The clock is not
+    /// rewound: like a real chain, time keeps moving when a transaction fails.
+    /// The chain records no events of its own, so callers that emit into an
+    /// `EventLog` should clone it alongside the snapshot and restore both.
+    pub fn restore(&mut self, snapshot: BlockchainSnapshot) {
+        self.accounts = snapshot.accounts;
+    }
+    
+    /// Apply `f` to at most `limit` accounts, starting at index `start`
+    ///
+    /// Accounts are kept in address order, so pages are read straight off the
+    /// map without sorting and the boundaries are stable between calls as long
+    /// as no accounts are added or removed. Returns `(processed, has_more)`;
+    /// the next page starts at `start + processed`. Work over a large
+    /// collection should be split like this across transactions rather than
+    /// done in one unbounded loop that can exceed the compute budget.
+    pub fn process_accounts_paged<F>(&mut self, start: usize, limit: usize, mut f: F) -> (usize, bool)
+    where
+        F: FnMut(&mut Account),
+    {
+        let mut processed = 0;
+        for account in self.accounts.values_mut().skip(start).take(limit) {
+            f(account);
+            processed += 1;
+        }
+        
+        // Even an empty page reports whether accounts remain past it
+        let has_more = start.saturating_add(processed) < self.accounts.len();
+        (processed, has_more)
+    }
+    
+    /// Jump the timestamp forward to `ts` without producing blocks
+    pub fn warp_to(&mut self, ts: u64) -> Result<(), Error> {
+        if ts < self.timestamp {
+            return Err(Error::ClockRegression {
+                current: self.timestamp,
+                target: ts,
+            });
+        }
+        self.timestamp = ts;
+        Ok(())
+    }
+    
+    /// Bring a contract's local `clock` up to the chain's timestamp
+    ///
+    /// A clock already ahead of the chain is left unchanged and the sync is
+    /// rejected, so a contract never sees time run backwards.
+    pub fn sync_clock(&self, clock: &mut u64) -> Result<(), Error> {
+        if self.timestamp < *clock {
+            return Err(Error::ClockRegression {
+                current: *clock,
+                target: self.timestamp,
+            });
+        }
+        *clock = self.timestamp;
+        Ok(())
+    }
+}
+
+/// Who signed a transaction and when it executes
+///
+/// Secure examples take authorization and timestamps from this one place
+/// instead of separate `caller` and `timestamp` arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxContext {
+    pub signer: [u8; 32],       // Fee payer and primary signer
+    pub signers: Vec<[u8; 32]>, // Every account that signed, including `signer`
+    pub timestamp: u64,
+    pub block: u64,
+    pub signature_valid: bool, // In a real runtime, the result of verifying every signature
+}
+
+impl TxContext {
+    /// A validly signed transaction from a single signer at time zero
+    pub fn new(signer: [u8; 32]) -> Self {
+        Self {
+            signer,
+            signers: vec![signer],
+            timestamp: 0,
+            block: 0,
+            signature_valid: true,
+        }
+    }
+    
+    /// A single-signer transaction stamped with the chain's current clock
+    pub fn from_chain(signer: [u8; 32], chain: &MockBlockchain) -> Self {
+        let clock = chain.clock();
+        Self {
+            timestamp: clock.unix_timestamp,
+            block: clock.slot,
+            ..Self::new(signer)
+        }
+    }
+    
+    /// Set the time the transaction executes at
+    pub fn at(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+    
+    /// Add a co-signer, e.g. an account owner whose transaction a relayer submits
+    pub fn with_signer(mut self, signer: [u8; 32]) -> Self {
+        if !self.signers.contains(&signer) {
+            self.signers.push(signer);
+        }
+        self
+    }
+    
+    /// Whether `account` validly signed this transaction
+    pub fn is_signed_by(&self, account: &[u8; 32]) -> bool {
+        self.is_signed_by_any(|signer| signer == account)
+    }
+    
+    /// Whether any valid signer satisfies `authorized`
+    ///
+    /// Always false when `signature_valid` is false, so a forged transaction
+    /// never authorizes anything.
+    pub fn is_signed_by_any(&self, authorized: impl Fn(&[u8; 32]) -> bool) -> bool {
+        self.signature_valid && (authorized(&self.signer) || self.signers.iter().any(authorized))
     }
 }
 
@@ -84,6 +310,483 @@ pub fn has_privilege(address: &[u8; 32], privilege: &str) -> bool {
     address.iter().any(|&b| b == first_byte)
 }
 
+/// Hash `seeds || [bump] || program_id` into a candidate program address
+///
+/// Like Solana's `create_program_address`, the candidate is only usable if it is
+/// not a valid ed25519 point, so no private key can sign for it.
+fn create_program_address(program_id: &[u8; 32], seeds: &[&[u8]], bump: u8) -> Option<[u8; 32]> {
+    use sha2::{Digest, Sha256};
+    
+    let mut hasher = Sha256::new();
+    for seed in seeds {
+        hasher.update(seed);
+    }
+    hasher.update([bump]);
+    hasher.update(program_id);
+    hasher.update(b"ProgramDerivedAddress");
+    let candidate: [u8; 32] = hasher.finalize().into();
+    
+    if ed25519_dalek::PublicKey::from_bytes(&candidate).is_ok() {
+        return None;
+    }
+    Some(candidate)
+}
+
+/// Find the program-derived address for `seeds` and its canonical bump
+///
+/// Mirrors Solana's `find_program_address`: bumps are tried from 255 down and the
+/// first candidate that is off the ed25519 curve wins.
+pub fn derive_pda(program_id: &[u8; 32], seeds: &[&[u8]]) -> ([u8; 32], u8) {
+    (0..=u8::MAX).rev()
+        .find_map(|bump| create_program_address(program_id, seeds, bump).map(|address| (address, bump)))
+        .expect("no viable bump seed for program-derived address")
+}
+
+/// Check that `expected` is the canonical PDA for `seeds` under `program_id`
+///
+/// Only the canonical bump is accepted, so callers can't smuggle in one of the
+/// other valid addresses for the same seeds.
+pub fn assert_pda(expected: &[u8; 32], program_id: &[u8; 32], seeds: &[&[u8]], bump: u8) -> Result<(), Error> {
+    let (address, canonical_bump) = derive_pda(program_id, seeds);
+    if bump != canonical_bump || address != *expected {
+        return Err(Error::InvalidPda);
+    }
+    Ok(())
+}
+
+/// A structured event emitted by a program for off-chain observers
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Event {
+    pub kind: &'static str,
+    pub fields: Vec<(String, String)>,
+    pub timestamp: u64,
+}
+
+impl Event {
+    /// Value of the named field, if present
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Append-only event sink shared by the secure examples
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    pub events: Vec<Event>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Record an event of `kind` with the given key/value fields
+    pub fn emit(&mut self, kind: &'static str, fields: &[(&str, String)], timestamp: u64) {
+        self.events.push(Event {
+            kind,
+            fields: fields.iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+            timestamp,
+        });
+    }
+    
+    /// All events of `kind`, oldest first
+    pub fn by_kind(&self, kind: &str) -> Vec<&Event> {
+        self.events.iter().filter(|event| event.kind == kind).collect()
+    }
+    
+    /// Full history as a JSON array in emission order
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.events).expect("events always serialize")
+    }
+}
+
+/// Sliding-window cap on the total amount moved within `window_secs`
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    pub window_secs: u64,
+    pub max_amount: u64,
+    consumed: VecDeque<(u64, u64)>, // (timestamp, amount)
+}
+
+impl RateLimiter {
+    pub fn new(window_secs: u64, max_amount: u64) -> Self {
+        Self {
+            window_secs,
+            max_amount,
+            consumed: VecDeque::new(),
+        }
+    }
+    
+    /// Amount still available in the window ending at `now`
+    ///
+    /// `now` must come from a clock the caller can't set: a timestamp jumped
+    /// forward rolls every recorded amount out of the window.
+    pub fn remaining(&mut self, now: u64) -> u64 {
+        // An amount consumed at `t` counts against the window until `t + window_secs`
+        while let Some(&(timestamp, _)) = self.consumed.front() {
+            if timestamp.saturating_add(self.window_secs) <= now {
+                self.consumed.pop_front();
+            } else {
+                break;
+            }
+        }
+        
+        let used: u64 = self.consumed.iter().map(|&(_, amount)| amount).sum();
+        self.max_amount.saturating_sub(used)
+    }
+    
+    /// Record `amount` at `now` if it fits in the current window
+    pub fn try_consume(&mut self, amount: u64, now: u64) -> Result<(), Error> {
+        // Recording out of order would let an amount leave the window early
+        if let Some(&(last, _)) = self.consumed.back()
+            && now < last
+        {
+            return Err(Error::ClockRegression { current: last, target: now });
+        }
+        
+        let remaining = self.remaining(now);
+        if amount > remaining {
+            return Err(Error::RateLimitExceeded {
+                requested: amount,
+                remaining,
+            });
+        }
+        
+        self.consumed.push_back((now, amount));
+        Ok(())
+    }
+}
+
+/// A token amount whose arithmetic reports overflow instead of wrapping or panicking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub fn checked_add(self, rhs: impl Into<Amount>) -> Result<Amount, Error> {
+        self.0.checked_add(rhs.into().0).map(Amount).ok_or(Error::Overflow)
+    }
+    
+    /// Fails with `Error::Overflow` when the result would go below zero
+    pub fn checked_sub(self, rhs: impl Into<Amount>) -> Result<Amount, Error> {
+        self.0.checked_sub(rhs.into().0).map(Amount).ok_or(Error::Overflow)
+    }
+    
+    pub fn checked_mul(self, rhs: impl Into<Amount>) -> Result<Amount, Error> {
+        self.0.checked_mul(rhs.into().0).map(Amount).ok_or(Error::Overflow)
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(value: u64) -> Self {
+        Amount(value)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> Self {
+        amount.0
+    }
+}
+
+/// Emergency stop switch for incident response
+///
+/// Who may flip the switch is up to the embedding program.
+#[derive(Debug, Clone, Default)]
+pub struct Pausable {
+    paused: bool,
+}
+
+impl Pausable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    
+    pub fn pause(&mut self) -> Result<(), Error> {
+        if self.paused {
+            return Err(Error::Paused);
+        }
+        self.paused = true;
+        Ok(())
+    }
+    
+    pub fn unpause(&mut self) -> Result<(), Error> {
+        if !self.paused {
+            return Err(Error::NotPaused);
+        }
+        self.paused = false;
+        Ok(())
+    }
+    
+    /// Guard for functions that must not run while paused
+    pub fn when_not_paused(&self) -> Result<(), Error> {
+        if self.paused {
+            return Err(Error::Paused);
+        }
+        Ok(())
+    }
+}
+
+/// An m-of-n approval gate for privileged operations
+///
+/// Owners `propose` an operation (which counts as their approval), other
+/// owners `approve` it, and it can be `execute`d once `threshold` distinct
+/// owners have approved. The multisig only tracks approvals; the embedding
+/// program stores what each proposal does and performs it on execution.
+#[derive(Debug, Clone)]
+pub struct MultiSig {
+    owners: Vec<[u8; 32]>,
+    threshold: usize,
+    proposals: HashMap<u64, HashSet<[u8; 32]>>, // proposal id -> approving owners
+    next_proposal_id: u64,
+}
+
+impl MultiSig {
+    /// Create a multisig requiring `threshold` of `owners`; duplicate owners count once
+    pub fn new(owners: Vec<[u8; 32]>, threshold: usize) -> Result<Self, &'static str> {
+        let owners = Self::validated_owners(owners, threshold)?;
+        Ok(Self {
+            owners,
+            threshold,
+            proposals: HashMap::new(),
+            next_proposal_id: 1,
+        })
+    }
+    
+    fn validated_owners(mut owners: Vec<[u8; 32]>, threshold: usize) -> Result<Vec<[u8; 32]>, &'static str> {
+        let mut seen = HashSet::new();
+        owners.retain(|owner| seen.insert(*owner));
+        if threshold == 0 || threshold > owners.len() {
+            return Err("Threshold must be between 1 and the number of owners");
+        }
+        Ok(owners)
+    }
+    
+    pub fn owners(&self) -> &[[u8; 32]] {
+        &self.owners
+    }
+    
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+    
+    pub fn is_owner(&self, account: &[u8; 32]) -> bool {
+        self.owners.contains(account)
+    }
+    
+    /// Open a proposal approved by `proposer`, returning its id
+    pub fn propose(&mut self, proposer: [u8; 32]) -> Result<u64, &'static str> {
+        if !self.is_owner(&proposer) {
+            return Err("Not a multisig owner");
+        }
+        let id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+        self.proposals.insert(id, HashSet::from([proposer]));
+        Ok(id)
+    }
+    
+    /// Record `owner`'s approval and return the number of distinct approvals
+    ///
+    /// Approving twice is harmless and does not count twice.
+    pub fn approve(&mut self, proposal_id: u64, owner: [u8; 32]) -> Result<usize, &'static str> {
+        if !self.is_owner(&owner) {
+            return Err("Not a multisig owner");
+        }
+        let approvals = self.proposals.get_mut(&proposal_id).ok_or("Proposal not found")?;
+        approvals.insert(owner);
+        Ok(approvals.len())
+    }
+    
+    pub fn approvals(&self, proposal_id: u64) -> Option<usize> {
+        self.proposals.get(&proposal_id).map(HashSet::len)
+    }
+    
+    /// Consume a proposal that has reached the threshold
+    ///
+    /// A proposal short of the threshold stays open for more approvals.
+    pub fn execute(&mut self, proposal_id: u64) -> Result<(), &'static str> {
+        let approvals = self.approvals(proposal_id).ok_or("Proposal not found")?;
+        if approvals < self.threshold {
+            return Err("Not enough approvals");
+        }
+        self.proposals.remove(&proposal_id);
+        Ok(())
+    }
+    
+    /// Replace the owner set and threshold
+    ///
+    /// Approvals given under the old owner set are discarded, so open
+    /// proposals must be approved again by the new owners.
+    pub fn set_owners(&mut self, owners: Vec<[u8; 32]>, threshold: usize) -> Result<(), &'static str> {
+        self.owners = Self::validated_owners(owners, threshold)?;
+        self.threshold = threshold;
+        for approvals in self.proposals.values_mut() {
+            approvals.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Queue of items that only take effect `delay` seconds after being queued
+///
+/// Gives users time to react to privileged changes (fee updates, admin
+/// transfers) before they apply.
+#[derive(Debug, Clone)]
+pub struct Timelock<T> {
+    pending: Vec<(T, u64)>, // (item, effective time), in queue order
+    delay: u64,
+}
+
+impl<T> Timelock<T> {
+    pub fn new(delay: u64) -> Self {
+        Self {
+            pending: Vec::new(),
+            delay,
+        }
+    }
+    
+    pub fn delay(&self) -> u64 {
+        self.delay
+    }
+    
+    /// Queue `item` and return the time it becomes ready
+    pub fn queue(&mut self, item: T, now: u64) -> u64 {
+        let effective_time = now.saturating_add(self.delay);
+        self.pending.push((item, effective_time));
+        effective_time
+    }
+    
+    /// Remove and return every item whose effective time has been reached
+    ///
+    /// Items are released in queue order, and all items sharing an effective
+    /// time are released together.
+    pub fn ready(&mut self, now: u64) -> Vec<T> {
+        let (ready, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(_, effective_time)| *effective_time <= now);
+        self.pending = pending;
+        ready.into_iter().map(|(item, _)| item).collect()
+    }
+    
+    /// Remove and return the pending items matching `matches`
+    pub fn cancel(&mut self, mut matches: impl FnMut(&T) -> bool) -> Vec<T> {
+        let (cancelled, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(item, _)| matches(item));
+        self.pending = pending;
+        cancelled.into_iter().map(|(item, _)| item).collect()
+    }
+    
+    /// Items still waiting, with their effective times
+    pub fn pending(&self) -> &[(T, u64)] {
+        &self.pending
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Median of a set of oracle prices, `None` if there are none
+///
+/// With an even number of prices the two middle values are averaged
+/// (rounding down).
+pub fn median_price(prices: &[u64]) -> Option<u64> {
+    if prices.is_empty() {
+        return None;
+    }
+    
+    let mut sorted = prices.to_vec();
+    sorted.sort_unstable();
+    
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        Some(sorted[mid])
+    } else {
+        // u128 so two large prices can't overflow when summed
+        Some(((sorted[mid - 1] as u128 + sorted[mid] as u128) / 2) as u64)
+    }
+}
+
+/// Time-weighted average price over `[window_start, now]`, `None` if there are no samples
+///
+/// `samples` are `(timestamp, price)` pairs in timestamp order. Each price is weighted by how
+/// long it was in effect: until the next sample, or until `now` for the last one. Spans are
+/// clipped to the window, so a price set before the window counts only from `window_start`
+/// and samples after `now` are ignored. If no time has passed in the window (e.g. a single
+/// sample taken at `now`), the latest price is returned.
+pub fn time_weighted_average(samples: impl IntoIterator<Item = (u64, u64)>, window_start: u64, now: u64) -> Option<u64> {
+    // u128 so long spans of large prices can't overflow
+    let mut weighted_sum: u128 = 0;
+    let mut total_time: u128 = 0;
+    let mut in_effect: Option<(u64, u64)> = None;
+    
+    let mut accumulate = |(since, price): (u64, u64), until: u64| {
+        let since = since.max(window_start);
+        if until > since {
+            weighted_sum += (until - since) as u128 * price as u128;
+            total_time += (until - since) as u128;
+        }
+    };
+    
+    for (timestamp, price) in samples {
+        if timestamp > now {
+            break;
+        }
+        if let Some(previous) = in_effect {
+            accumulate(previous, timestamp);
+        }
+        in_effect = Some((timestamp, price));
+    }
+    
+    let (last_timestamp, last_price) = in_effect?;
+    accumulate((last_timestamp, last_price), now);
+    
+    if total_time == 0 {
+        return Some(last_price);
+    }
+    Some((weighted_sum / total_time) as u64)
+}
+
+/// Deterministic xorshift64 generator for tests
+///
+/// TEST ONLY: the whole sequence follows from the seed, so this must never be
+/// used as a source of production entropy. It gives tests reproducible values
+/// that are plainly fixtures rather than something derived from block data.
+#[derive(Debug, Clone)]
+pub struct TestRng(u64);
+
+impl TestRng {
+    /// Seed the generator. Xorshift gets stuck at zero, so a zero seed is
+    /// replaced with a fixed non-zero one.
+    pub fn new(seed: u64) -> Self {
+        Self(if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed })
+    }
+    
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+    
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
 /// Utilities for formatting/printing
 pub mod display {
     /// Format an address as a hexadecimal string
@@ -97,6 +800,194 @@ pub mod display {
     }
 }
 
+/// Hash-based commitments for commit-reveal schemes
+pub mod commit_reveal {
+    use sha2::{Digest, Sha256};
+    
+    /// Compute the commitment `sha256(value || salt)` for a value
+    pub fn commitment(value: &[u8], salt: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(value);
+        hasher.update(salt);
+        hasher.finalize().into()
+    }
+    
+    /// Check that a revealed value and salt open the given commitment
+    pub fn verify(commitment: &[u8; 32], value: &[u8], salt: &[u8; 32]) -> bool {
+        self::commitment(value, salt) == *commitment
+    }
+}
+
+/// Cryptographic primitives backing the secure examples
+pub mod crypto {
+    use ed25519_dalek::{PublicKey, Signature, Verifier};
+    use sha2::{Digest, Sha256};
+    
+    /// Order `L` of the ed25519 base point, little-endian
+    pub const ED25519_ORDER: [u8; 32] = [
+        0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10,
+    ];
+    
+    /// Whether the signature's `S` scalar is fully reduced (`S < L`)
+    ///
+    /// `S` and `S + L` verify identically under a lenient verifier, so every
+    /// valid signature has a second, byte-different twin. A program that
+    /// dedups processed transfers by signature bytes would accept that twin
+    /// as a new transfer. Requiring the reduced form leaves one encoding per
+    /// signature.
+    pub fn is_canonical_signature(signature: &[u8; 64]) -> bool {
+        // Compare as little-endian integers, most significant byte first
+        signature[32..].iter().rev().cmp(ED25519_ORDER.iter().rev()) == std::cmp::Ordering::Less
+    }
+    
+    /// Verify an ed25519 signature over `message` by `pubkey`
+    pub fn verify_ed25519(pubkey: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+        let public_key = match PublicKey::from_bytes(pubkey) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        let signature = match Signature::from_bytes(signature) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+        public_key.verify(message, &signature).is_ok()
+    }
+    
+    fn hmac_sha256(key: &[u8; 32], message: &[u8]) -> [u8; 32] {
+        let mut inner_pad = [0x36u8; 64];
+        let mut outer_pad = [0x5cu8; 64];
+        for (i, byte) in key.iter().enumerate() {
+            inner_pad[i] ^= byte;
+            outer_pad[i] ^= byte;
+        }
+        
+        let inner: [u8; 32] = Sha256::new().chain_update(inner_pad).chain_update(message).finalize().into();
+        Sha256::new().chain_update(outer_pad).chain_update(inner).finalize().into()
+    }
+    
+    /// Mock VRF evaluation: the `(output, proof)` an oracle holding `public_key` returns for `seed`
+    ///
+    /// The proof is `gamma || hmac(public_key, gamma || seed)` with `gamma = sha256(public_key || seed)`,
+    /// and the output is `sha256(gamma)`. This has the shape of a real VRF (the output is bound to
+    /// key and seed, and the proof checks that binding) but, being keyed on the public key, it
+    /// offers no unforgeability. It stands in for an ECVRF in the examples.
+    pub fn vrf_prove(public_key: &[u8; 32], seed: &[u8]) -> ([u8; 32], [u8; 64]) {
+        let gamma: [u8; 32] = Sha256::new().chain_update(public_key).chain_update(seed).finalize().into();
+        let tag = hmac_sha256(public_key, &[&gamma[..], seed].concat());
+        
+        let mut proof = [0u8; 64];
+        proof[..32].copy_from_slice(&gamma);
+        proof[32..].copy_from_slice(&tag);
+        (Sha256::digest(gamma).into(), proof)
+    }
+    
+    /// Check that `output` and `proof` are the VRF evaluation of `seed` under `public_key`
+    pub fn verify_vrf(public_key: &[u8; 32], seed: &[u8], output: &[u8; 32], proof: &[u8; 64]) -> bool {
+        let (expected_output, expected_proof) = vrf_prove(public_key, seed);
+        expected_output == *output && expected_proof == *proof
+    }
+}
+
+/// Role-based access control
+pub mod rbac {
+    use std::collections::{HashMap, HashSet};
+    
+    /// A permission that can be granted to accounts
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub enum Role {
+        Admin,
+        FeeManager,
+        Pauser,
+        Custom(String),
+    }
+    
+    /// Which accounts hold which roles
+    ///
+    /// The registry only tracks membership; callers decide who may grant or
+    /// revoke. It does enforce that the last `Admin` can never be removed, and
+    /// admins leave in two steps (`renounce_admin` then `confirm_renounce_admin`)
+    /// so a single mistaken call can't lock everyone out.
+    #[derive(Debug, Clone, Default)]
+    pub struct RoleRegistry {
+        members: HashMap<Role, HashSet<[u8; 32]>>,
+        pending_renouncements: HashSet<[u8; 32]>,
+    }
+    
+    impl RoleRegistry {
+        pub fn new() -> Self {
+            Self::default()
+        }
+        
+        /// Create a registry with `admin` as its first `Admin`
+        pub fn with_admin(admin: [u8; 32]) -> Self {
+            let mut registry = Self::new();
+            registry.grant_role(admin, Role::Admin);
+            registry
+        }
+        
+        pub fn has_role(&self, account: &[u8; 32], role: &Role) -> bool {
+            self.members.get(role).is_some_and(|accounts| accounts.contains(account))
+        }
+        
+        /// Every role `account` currently holds, in no particular order
+        pub fn roles_of(&self, account: &[u8; 32]) -> Vec<Role> {
+            self.members
+                .iter()
+                .filter(|(_, accounts)| accounts.contains(account))
+                .map(|(role, _)| role.clone())
+                .collect()
+        }
+        
+        pub fn grant_role(&mut self, account: [u8; 32], role: Role) {
+            self.members.entry(role).or_default().insert(account);
+        }
+        
+        pub fn revoke_role(&mut self, account: [u8; 32], role: &Role) -> Result<(), &'static str> {
+            if !self.has_role(&account, role) {
+                return Err("Account does not have role");
+            }
+            
+            if *role == Role::Admin {
+                self.ensure_not_last_admin()?;
+                self.pending_renouncements.remove(&account);
+            }
+            
+            self.members.get_mut(role).unwrap().remove(&account);
+            Ok(())
+        }
+        
+        /// First step of an admin stepping down
+        pub fn renounce_admin(&mut self, account: [u8; 32]) -> Result<(), &'static str> {
+            if !self.has_role(&account, &Role::Admin) {
+                return Err("Account does not have role");
+            }
+            self.ensure_not_last_admin()?;
+            
+            self.pending_renouncements.insert(account);
+            Ok(())
+        }
+        
+        /// Second step of an admin stepping down
+        pub fn confirm_renounce_admin(&mut self, account: [u8; 32]) -> Result<(), &'static str> {
+            if !self.pending_renouncements.contains(&account) {
+                return Err("No pending renouncement");
+            }
+            
+            // Other admins may have left since the renouncement was started
+            self.revoke_role(account, &Role::Admin)
+        }
+        
+        fn ensure_not_last_admin(&self) -> Result<(), &'static str> {
+            let admins = self.members.get(&Role::Admin).map_or(0, |accounts| accounts.len());
+            if admins <= 1 {
+                return Err("Cannot remove the last admin");
+            }
+            Ok(())
+        }
+    }
+}
+
 #[cfg(feature = "mock-runtime")]
 pub mod runtime {
     use super::*;
@@ -121,3 +1012,470 @@ pub mod runtime {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_clock_is_monotonic() {
+        let mut chain = MockBlockchain::new();
+        let start = chain.clock();
+        
+        chain.advance_block();
+        chain.advance_slots(10);
+        let after = chain.clock();
+        assert_eq!(after.slot, start.slot + 11);
+        assert!(after.unix_timestamp > start.unix_timestamp);
+        
+        // Warping forward moves time but not the slot
+        chain.warp_to(after.unix_timestamp + 3600).unwrap();
+        assert_eq!(chain.current_slot(), after.slot);
+        assert_eq!(chain.unix_timestamp(), after.unix_timestamp + 3600);
+        
+        // Warping to the current time is a no-op
+        assert!(chain.warp_to(chain.unix_timestamp()).is_ok());
+    }
+    
+    #[test]
+    fn test_write_data_respects_account_size() {
+        let mut chain = MockBlockchain::new();
+        let key = [1u8; 32];
+        chain.create_account(key, [2u8; 32], 16);
+        
+        // Writes up to the allocated size succeed
+        assert!(chain.write_data(key, &[7u8; 16]).is_ok());
+        
+        // Oversized writes are rejected and leave the data untouched
+        let result = chain.write_data(key, &[9u8; 17]);
+        assert!(matches!(result, Err(Error::AccountTooSmall { needed: 17, available: 16 })));
+        assert_eq!(chain.get_account(&key).unwrap().data, vec![7u8; 16]);
+        
+        // Unknown accounts can't be written
+        assert!(matches!(chain.write_data([3u8; 32], &[0u8; 1]), Err(Error::AccountNotFound)));
+    }
+    
+    #[test]
+    fn test_realloc_then_write() {
+        let mut chain = MockBlockchain::new();
+        let key = [1u8; 32];
+        chain.create_account(key, [2u8; 32], 8);
+        
+        assert!(chain.write_data(key, &[1u8; 32]).is_err());
+        chain.realloc(key, 32).unwrap();
+        assert!(chain.write_data(key, &[1u8; 32]).is_ok());
+        assert_eq!(chain.get_account(&key).unwrap().size, 32);
+        
+        // Growth beyond the per-call limit is rejected
+        let result = chain.realloc(key, 32 + MAX_PERMITTED_DATA_INCREASE + 1);
+        assert!(matches!(result, Err(Error::ReallocTooLarge { .. })));
+        
+        // Shrinking truncates existing data
+        chain.realloc(key, 4).unwrap();
+        assert_eq!(chain.get_account(&key).unwrap().data, vec![1u8; 4]);
+    }
+    
+    #[test]
+    fn test_restore_reverts_failed_multi_step_transfer() {
+        let mut chain = MockBlockchain::new();
+        let (alice, bob, carol) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+        chain.add_account(Account::new(alice).with_balance(100));
+        chain.add_account(Account::new(bob).with_balance(50));
+        
+        let snapshot = chain.snapshot();
+        let mut events = EventLog::new();
+        let saved_events = events.clone();
+        
+        // Step one succeeds, step two fails because carol doesn't exist yet
+        let mut transfer = |chain: &mut MockBlockchain| -> Result<(), Error> {
+            chain.get_account_mut(&alice).ok_or(Error::AccountNotFound)?.balance -= 60;
+            chain.get_account_mut(&bob).ok_or(Error::AccountNotFound)?.balance += 60;
+            events.emit("Transfer", &[("amount", "60".to_string())], chain.unix_timestamp());
+            chain.create_account([4u8; 32], alice, 0);
+            chain.advance_block();
+            chain.get_account_mut(&carol).ok_or(Error::AccountNotFound)?.balance += 1;
+            Ok(())
+        };
+        assert!(matches!(transfer(&mut chain), Err(Error::AccountNotFound)));
+        
+        chain.restore(snapshot.clone());
+        events = saved_events;
+        assert_eq!(chain.get_account(&alice).unwrap().balance, 100);
+        assert_eq!(chain.get_account(&bob).unwrap().balance, 50);
+        assert!(chain.get_account(&[4u8; 32]).is_none());
+        assert!(events.by_kind("Transfer").is_empty());
+        
+        // Time spent on the failed transaction is not rewound
+        assert_eq!(chain.current_slot(), snapshot.clock().slot + 1);
+    }
+    
+    #[test]
+    fn test_tx_context_signers() {
+        let (relayer, owner, stranger) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+        let ctx = TxContext::new(relayer).with_signer(owner).with_signer(owner);
+        assert_eq!(ctx.signers, vec![relayer, owner]);
+        assert!(ctx.is_signed_by(&relayer));
+        assert!(ctx.is_signed_by(&owner));
+        assert!(!ctx.is_signed_by(&stranger));
+        
+        // An invalid signature authorizes no one, not even the primary signer
+        let forged = TxContext { signature_valid: false, ..ctx };
+        assert!(!forged.is_signed_by(&relayer));
+        assert!(!forged.is_signed_by_any(|_| true));
+        
+        let mut chain = MockBlockchain::new();
+        chain.advance_slots(5);
+        let ctx = TxContext::from_chain(owner, &chain);
+        assert_eq!((ctx.block, ctx.timestamp), (chain.current_slot(), chain.unix_timestamp()));
+    }
+    
+    #[test]
+    fn test_multisig_executes_only_at_threshold() {
+        let (a, b, c, outsider) = ([1u8; 32], [2u8; 32], [3u8; 32], [4u8; 32]);
+        let mut multisig = MultiSig::new(vec![a, b, c, a], 2).unwrap();
+        assert_eq!(multisig.owners(), &[a, b, c]);
+        assert!(MultiSig::new(vec![a, b], 3).is_err());
+        assert!(MultiSig::new(vec![a], 0).is_err());
+        
+        assert_eq!(multisig.propose(outsider), Err("Not a multisig owner"));
+        let id = multisig.propose(a).unwrap();
+        
+        // The proposer approving again still leaves one approval
+        assert_eq!(multisig.approve(id, a), Ok(1));
+        assert_eq!(multisig.approve(id, outsider), Err("Not a multisig owner"));
+        assert_eq!(multisig.execute(id), Err("Not enough approvals"));
+        
+        assert_eq!(multisig.approve(id, b), Ok(2));
+        assert_eq!(multisig.execute(id), Ok(()));
+        assert_eq!(multisig.execute(id), Err("Proposal not found"));
+    }
+    
+    #[test]
+    fn test_multisig_owner_change_invalidates_approvals() {
+        let (a, b, c, d) = ([1u8; 32], [2u8; 32], [3u8; 32], [4u8; 32]);
+        let mut multisig = MultiSig::new(vec![a, b, c], 2).unwrap();
+        let id = multisig.propose(a).unwrap();
+        multisig.approve(id, b).unwrap();
+        
+        multisig.set_owners(vec![a, c, d], 2).unwrap();
+        assert_eq!(multisig.approvals(id), Some(0));
+        assert_eq!(multisig.execute(id), Err("Not enough approvals"));
+        assert_eq!(multisig.approve(id, b), Err("Not a multisig owner"));
+        
+        multisig.approve(id, c).unwrap();
+        multisig.approve(id, d).unwrap();
+        assert_eq!(multisig.execute(id), Ok(()));
+    }
+    
+    #[test]
+    fn test_timelock_releases_items_after_delay() {
+        let mut timelock = Timelock::new(100);
+        assert_eq!(timelock.queue("a", 0), 100);
+        assert_eq!(timelock.queue("b", 50), 150);
+        assert_eq!(timelock.queue("c", 0), 100);
+        
+        assert!(timelock.ready(99).is_empty());
+        
+        // Items sharing an effective time release together, in queue order
+        assert_eq!(timelock.ready(100), vec!["a", "c"]);
+        assert_eq!(timelock.pending(), &[("b", 150)]);
+        assert_eq!(timelock.ready(1_000), vec!["b"]);
+        assert!(timelock.is_empty());
+        
+        // Queueing near the end of time saturates instead of overflowing
+        assert_eq!(timelock.queue("late", u64::MAX - 1), u64::MAX);
+    }
+    
+    #[test]
+    fn test_timelock_cancel_removes_only_matching_items() {
+        let mut timelock = Timelock::new(10);
+        timelock.queue((1, 40), 0);
+        timelock.queue((2, 50), 0);
+        timelock.queue((1, 60), 5);
+        
+        assert_eq!(timelock.cancel(|&(pool, fee)| pool == 1 && fee == 40), vec![(1, 40)]);
+        assert!(timelock.cancel(|&(pool, _)| pool == 3).is_empty());
+        assert_eq!(timelock.ready(15), vec![(2, 50), (1, 60)]);
+    }
+    
+    #[test]
+    fn test_warp_into_past_errors() {
+        let mut chain = MockBlockchain::new();
+        let now = chain.unix_timestamp();
+        
+        let result = chain.warp_to(now - 1);
+        assert!(matches!(
+            result,
+            Err(Error::ClockRegression { current, target }) if current == now && target == now - 1
+        ));
+        assert_eq!(chain.unix_timestamp(), now);
+    }
+    
+    #[test]
+    fn test_median_price() {
+        assert_eq!(median_price(&[]), None);
+        assert_eq!(median_price(&[7]), Some(7));
+        assert_eq!(median_price(&[100, 1, 102]), Some(100));
+        
+        // Even counts average the two middle values
+        assert_eq!(median_price(&[100, 1, 103, 10_000]), Some(101));
+        assert_eq!(median_price(&[u64::MAX, u64::MAX]), Some(u64::MAX));
+    }
+    
+    #[test]
+    fn test_time_weighted_average() {
+        // 100 for 90s, then 200 for 10s: the naive mean of the samples would be 150
+        let samples = [(0, 100), (90, 200)];
+        assert_eq!(time_weighted_average(samples, 0, 100), Some(110));
+        
+        // A price set before the window only counts from the window's start
+        assert_eq!(time_weighted_average(samples, 80, 100), Some(150));
+        assert_eq!(time_weighted_average(samples, 90, 100), Some(200));
+        
+        // A single sample is its own average, even with no time elapsed
+        assert_eq!(time_weighted_average([(50, 7)], 0, 100), Some(7));
+        assert_eq!(time_weighted_average([(100, 7)], 0, 100), Some(7));
+        
+        // Samples after `now` are ignored; no samples means no price
+        assert_eq!(time_weighted_average([(0, 100), (200, 1)], 0, 100), Some(100));
+        assert_eq!(time_weighted_average([], 0, 100), None);
+    }
+    
+    #[test]
+    fn test_canonical_signature_scalar_bound() {
+        let mut signature = [0u8; 64];
+        assert!(crypto::is_canonical_signature(&signature));
+        
+        // S = L is the first non-canonical value, L - 1 the last canonical one
+        signature[32..].copy_from_slice(&crypto::ED25519_ORDER);
+        assert!(!crypto::is_canonical_signature(&signature));
+        signature[32] -= 1;
+        assert!(crypto::is_canonical_signature(&signature));
+        
+        signature[63] = 0xff;
+        assert!(!crypto::is_canonical_signature(&signature));
+    }
+    
+    #[test]
+    fn test_event_log_queries_by_kind() {
+        let mut log = EventLog::new();
+        log.emit("FeeChanged", &[("old_fee", "10".to_string()), ("new_fee", "20".to_string())], 5);
+        log.emit("AdminTransferred", &[("new_admin", "0xab".to_string())], 6);
+        log.emit("FeeChanged", &[("old_fee", "20".to_string()), ("new_fee", "30".to_string())], 7);
+        
+        let fee_changes = log.by_kind("FeeChanged");
+        assert_eq!(fee_changes.len(), 2);
+        assert_eq!(fee_changes[1].field("new_fee"), Some("30"));
+        assert_eq!(fee_changes[1].timestamp, 7);
+        assert_eq!(fee_changes[0].field("missing"), None);
+        assert!(log.by_kind("Unknown").is_empty());
+    }
+    
+    #[test]
+    fn test_rate_limiter_window_rollover() {
+        let mut limiter = RateLimiter::new(100, 1_000);
+        
+        limiter.try_consume(600, 0).unwrap();
+        limiter.try_consume(400, 50).unwrap();
+        assert!(matches!(
+            limiter.try_consume(1, 99),
+            Err(Error::RateLimitExceeded { requested: 1, remaining: 0 })
+        ));
+        
+        // At t=100 the first 600 rolls out of the window; the 400 is still counted
+        assert_eq!(limiter.remaining(100), 600);
+        limiter.try_consume(600, 100).unwrap();
+        
+        // At t=150 the 400 rolls out too
+        assert_eq!(limiter.remaining(150), 400);
+        
+        // Amounts can't be recorded before the latest one
+        assert!(matches!(
+            limiter.try_consume(1, 99),
+            Err(Error::ClockRegression { current: 100, target: 99 })
+        ));
+    }
+    
+    #[test]
+    fn test_rate_limiter_rejects_oversized_burst() {
+        let mut limiter = RateLimiter::new(100, 1_000);
+        
+        // A single request larger than the whole window is never allowed
+        assert!(matches!(
+            limiter.try_consume(1_001, 0),
+            Err(Error::RateLimitExceeded { requested: 1_001, remaining: 1_000 })
+        ));
+        
+        // Rejected requests don't consume capacity
+        limiter.try_consume(1_000, 0).unwrap();
+    }
+    
+    #[test]
+    fn test_role_registry_grant_and_revoke() {
+        use rbac::{Role, RoleRegistry};
+        
+        let admin = [1u8; 32];
+        let manager = [2u8; 32];
+        let mut roles = RoleRegistry::with_admin(admin);
+        
+        roles.grant_role(manager, Role::FeeManager);
+        roles.grant_role(manager, Role::Custom("oracle-updater".to_string()));
+        assert!(roles.has_role(&manager, &Role::FeeManager));
+        assert!(roles.has_role(&manager, &Role::Custom("oracle-updater".to_string())));
+        assert!(!roles.has_role(&manager, &Role::Admin));
+        
+        roles.revoke_role(manager, &Role::FeeManager).unwrap();
+        assert!(!roles.has_role(&manager, &Role::FeeManager));
+        assert_eq!(roles.revoke_role(manager, &Role::FeeManager), Err("Account does not have role"));
+    }
+    
+    #[test]
+    fn test_role_registry_keeps_last_admin() {
+        use rbac::{Role, RoleRegistry};
+        
+        let admin = [1u8; 32];
+        let second = [2u8; 32];
+        let mut roles = RoleRegistry::with_admin(admin);
+        
+        assert_eq!(roles.revoke_role(admin, &Role::Admin), Err("Cannot remove the last admin"));
+        assert_eq!(roles.renounce_admin(admin), Err("Cannot remove the last admin"));
+        
+        // With a second admin, renouncing takes two steps
+        roles.grant_role(second, Role::Admin);
+        roles.renounce_admin(admin).unwrap();
+        assert!(roles.has_role(&admin, &Role::Admin));
+        assert_eq!(roles.confirm_renounce_admin(second), Err("No pending renouncement"));
+        
+        // If the other admin leaves first, confirming would lock everyone out
+        roles.revoke_role(second, &Role::Admin).unwrap();
+        assert_eq!(roles.confirm_renounce_admin(admin), Err("Cannot remove the last admin"));
+        
+        roles.grant_role(second, Role::Admin);
+        roles.confirm_renounce_admin(admin).unwrap();
+        assert!(!roles.has_role(&admin, &Role::Admin));
+        assert!(roles.has_role(&second, &Role::Admin));
+    }
+    
+    #[test]
+    fn test_pausable_transitions() {
+        let mut switch = Pausable::new();
+        assert!(switch.when_not_paused().is_ok());
+        
+        // Unpausing a running contract is an error, not a no-op
+        assert!(matches!(switch.unpause(), Err(Error::NotPaused)));
+        
+        switch.pause().unwrap();
+        assert!(switch.is_paused());
+        assert!(matches!(switch.when_not_paused(), Err(Error::Paused)));
+        assert!(matches!(switch.pause(), Err(Error::Paused)));
+        
+        switch.unpause().unwrap();
+        assert!(switch.when_not_paused().is_ok());
+    }
+    
+    #[test]
+    fn test_amount_checked_arithmetic() {
+        let amount = Amount::from(1_000);
+        
+        // Normal arithmetic matches plain u64 results
+        assert_eq!(u64::from(amount.checked_add(24).unwrap()), 1_024);
+        assert_eq!(u64::from(amount.checked_sub(Amount::from(1_000)).unwrap()), 0);
+        assert_eq!(u64::from(amount.checked_mul(3).unwrap()), 3_000);
+        
+        // Each operation reports overflow instead of wrapping
+        assert!(matches!(Amount::from(u64::MAX).checked_add(1), Err(Error::Overflow)));
+        assert!(matches!(amount.checked_sub(1_001), Err(Error::Overflow)));
+        assert!(matches!(Amount::from(u64::MAX / 2 + 1).checked_mul(2), Err(Error::Overflow)));
+    }
+    
+    #[test]
+    fn test_derive_pda_is_deterministic_and_checked() {
+        let program_id = [7u8; 32];
+        let (pda, bump) = derive_pda(&program_id, &[b"vault"]);
+        
+        assert_eq!(derive_pda(&program_id, &[b"vault"]), (pda, bump));
+        assert!(assert_pda(&pda, &program_id, &[b"vault"], bump).is_ok());
+        
+        // Different seeds or programs derive different addresses
+        assert_ne!(derive_pda(&program_id, &[b"other"]).0, pda);
+        assert_ne!(derive_pda(&[8u8; 32], &[b"vault"]).0, pda);
+        
+        assert!(matches!(assert_pda(&[1u8; 32], &program_id, &[b"vault"], bump), Err(Error::InvalidPda)));
+        assert!(matches!(assert_pda(&pda, &program_id, &[b"vault"], bump.wrapping_sub(1)), Err(Error::InvalidPda)));
+    }
+    
+    #[test]
+    fn test_test_rng_is_reproducible() {
+        let mut a = TestRng::new(42);
+        let mut b = TestRng::new(42);
+        let mut c = TestRng::new(43);
+        
+        let first: Vec<u64> = (0..16).map(|_| a.next_u64()).collect();
+        let second: Vec<u64> = (0..16).map(|_| b.next_u64()).collect();
+        let other: Vec<u64> = (0..16).map(|_| c.next_u64()).collect();
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        
+        // Byte fills follow the same sequence, including a partial final word
+        let mut bytes_a = [0u8; 13];
+        let mut bytes_b = [0u8; 13];
+        a.fill_bytes(&mut bytes_a);
+        b.fill_bytes(&mut bytes_b);
+        assert_eq!(bytes_a, bytes_b);
+        
+        // A zero seed still produces a non-degenerate sequence
+        assert_ne!(TestRng::new(0).next_u64(), 0);
+    }
+    
+    #[test]
+    fn test_event_log_json_keeps_emission_order() {
+        let mut log = EventLog::new();
+        log.emit("FeeChanged", &[("new_fee", "20".to_string())], 5);
+        log.emit("AdminTransferred", &[], 6);
+        
+        assert_eq!(
+            log.to_json(),
+            r#"[{"kind":"FeeChanged","fields":[["new_fee","20"]],"timestamp":5},{"kind":"AdminTransferred","fields":[],"timestamp":6}]"#
+        );
+    }
+    
+    #[test]
+    fn test_paged_processing_covers_every_account_once() {
+        let mut chain = MockBlockchain::new();
+        for i in 0..1000u32 {
+            let mut key = [0u8; 32];
+            key[..4].copy_from_slice(&i.to_be_bytes());
+            chain.add_account(Account::new(key));
+        }
+        
+        let mut start = 0;
+        let mut pages = 0;
+        loop {
+            let (processed, has_more) = chain.process_accounts_paged(start, 100, |account| account.balance += 1);
+            assert_eq!(processed, 100);
+            start += processed;
+            pages += 1;
+            assert_eq!(has_more, start < 1000);
+            if !has_more {
+                break;
+            }
+        }
+        
+        assert_eq!(pages, 10);
+        assert!(chain.accounts.values().all(|account| account.balance == 1));
+    }
+    
+    #[test]
+    fn test_empty_page_still_reports_remaining_accounts() {
+        let mut chain = MockBlockchain::new();
+        for i in 0..3u8 {
+            chain.add_account(Account::new([i; 32]));
+        }
+        
+        // A zero-sized page processes nothing but must not read as "done"
+        assert_eq!(chain.process_accounts_paged(0, 0, |_| unreachable!()), (0, true));
+        assert_eq!(chain.process_accounts_paged(2, 0, |_| unreachable!()), (0, true));
+        assert_eq!(chain.process_accounts_paged(3, 0, |_| unreachable!()), (0, false));
+        assert_eq!(chain.process_accounts_paged(2, 5, |_| {}), (1, false));
+    }
+}
@@ -8,7 +8,7 @@
 //! These vulnerabilities can be especially subtle in Rust smart contracts where
 //! ownership patterns and account validation may differ from other platforms.
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 
 /// Represents an access control vulnerability example
 pub struct AccessControlVulnerability;
@@ -28,6 +28,18 @@ impl Vulnerability for AccessControlVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "All Rust-based contracts"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::AccessControl]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Trivial
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable function with missing access control
@@ -58,6 +70,30 @@ impl Vulnerability for AccessControlVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure fee update restricted to a signed transaction from the fee manager
+        pub fn set_fee_percentage(&mut self, ctx: &TxContext, new_fee: u64) -> Result<(), &'static str> {
+            // FIXED: Verify the transaction is valid (signature check)
+            if !ctx.signature_valid {
+                return Err("Invalid transaction");
+            }
+            
+            // FIXED: Check that a signer holds the fee manager role
+            if !ctx.is_signed_by_any(|signer| self.roles.has_role(signer, &Role::FeeManager)) {
+                return Err("Only fee manager can change fee percentage");
+            }
+            
+            if new_fee > 10000 {
+                return Err("Fee percentage too high");
+            }
+            
+            self.fee_percentage = new_fee;
+            Ok(())
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Identify privileged functions and verify appropriate access controls",
@@ -176,14 +212,19 @@ pub mod vulnerable {
 
 /// Example of secure code with proper access control
 pub mod secure {
+    use crate::utils::{EventLog, Timelock, TxContext};
+    use crate::utils::display::format_address;
+    use crate::utils::rbac::{Role, RoleRegistry};
+    
     #[derive(Debug, Clone)]
     pub struct Protocol {
         pub admin: [u8; 32],
-        pub pending_admin: Option<[u8; 32]>,
-        pub admin_change_time: Option<u64>,
+        pub roles: RoleRegistry,
+        pub admin_transfer: Timelock<[u8; 32]>, // Pending new admin
         pub fee_percentage: u64,
         pub accounts: std::collections::HashMap<[u8; 32], UserAccount>,
-        pub current_time: u64,
+        pub events: EventLog,
+        pub current_time: u64, // Protocol clock; the admin timelock never trusts a caller's timestamp
     }
     
     #[derive(Debug, Clone)]
@@ -200,43 +241,54 @@ pub mod secure {
         pub withdraw_limit: u64,
     }
     
-    #[derive(Debug, Clone)]
-    pub struct Transaction {
-        pub caller: [u8; 32],
-        pub signature: [u8; 64],
-        pub valid: bool, // In a real implementation, would verify the signature
-    }
-    
     impl Protocol {
         pub fn new(admin: [u8; 32]) -> Self {
+            // The deployer starts with every role and can hand them out
+            let mut roles = RoleRegistry::with_admin(admin);
+            roles.grant_role(admin, Role::FeeManager);
+            roles.grant_role(admin, Role::Pauser);
+            
             Self {
                 admin,
-                pending_admin: None,
-                admin_change_time: None,
+                roles,
+                admin_transfer: Timelock::new(86400), // 24-hour timelock
                 fee_percentage: 10, // 0.1%
                 accounts: std::collections::HashMap::new(),
+                events: EventLog::new(),
                 current_time: 0,
             }
         }
         
+        /// Helper to advance the protocol clock (for testing)
+        pub fn advance_time(&mut self, seconds: u64) {
+            self.current_time = self.current_time.saturating_add(seconds);
+        }
+        
         /// Secure function with proper access control
-        pub fn set_fee_percentage(&mut self, tx: &Transaction, new_fee: u64) -> Result<(), &'static str> {
+        pub fn set_fee_percentage(&mut self, ctx: &TxContext, new_fee: u64) -> Result<(), &'static str> {
             // FIXED: Proper access control check
             
             // Verify the transaction is valid (signature check)
-            if !tx.valid {
+            if !ctx.signature_valid {
                 return Err("Invalid transaction");
             }
             
-            // Check that the caller is the admin
-            if tx.caller != self.admin {
-                return Err("Only admin can change fee percentage");
+            // Check that a signer holds the fee manager role
+            if !ctx.is_signed_by_any(|signer| self.roles.has_role(signer, &Role::FeeManager)) {
+                return Err("Only fee manager can change fee percentage");
             }
             
             if new_fee > 10000 {
                 return Err("Fee percentage too high");
             }
             
+            // FIXED: Critical parameter changes are observable off-chain
+            self.events.emit("FeeChanged", &[
+                ("old_fee", self.fee_percentage.to_string()),
+                ("new_fee", new_fee.to_string()),
+                ("caller", format_address(&ctx.signer)),
+            ], ctx.timestamp);
+            
             self.fee_percentage = new_fee;
             
             Ok(())
@@ -245,12 +297,12 @@ pub mod secure {
         /// Secure function with proper validation
         pub fn update_user_settings(
             &mut self,
-            tx: &Transaction,
+            ctx: &TxContext,
             account_id: [u8; 32],
             new_settings: UserSettings,
         ) -> Result<(), &'static str> {
             // FIXED: Verify transaction validity first
-            if !tx.valid {
+            if !ctx.signature_valid {
                 return Err("Invalid transaction");
             }
             
@@ -259,9 +311,9 @@ pub mod secure {
                 None => return Err("Account not found"),
             };
             
-            // FIXED: Check that the caller is authorized
-            let is_owner = account.owner == tx.caller;
-            let is_authorized = account.authorized_signers.contains(&tx.caller);
+            // FIXED: Check that the owner or one of its delegates signed
+            let is_owner = ctx.is_signed_by(&account.owner);
+            let is_authorized = ctx.is_signed_by_any(|signer| account.authorized_signers.contains(signer));
             
             if !is_owner && !is_authorized {
                 return Err("Not authorized to update settings");
@@ -277,52 +329,99 @@ pub mod secure {
             Ok(())
         }
         
+        /// Grant `role` to `account`; only admins may grant roles
+        pub fn grant_role(&mut self, ctx: &TxContext, account: [u8; 32], role: Role) -> Result<(), &'static str> {
+            if !ctx.signature_valid {
+                return Err("Invalid transaction");
+            }
+            
+            if !ctx.is_signed_by_any(|signer| self.roles.has_role(signer, &Role::Admin)) {
+                return Err("Only admin can manage roles");
+            }
+            
+            self.roles.grant_role(account, role);
+            Ok(())
+        }
+        
+        /// Revoke `role` from `account`; only admins may revoke roles
+        pub fn revoke_role(&mut self, ctx: &TxContext, account: [u8; 32], role: &Role) -> Result<(), &'static str> {
+            if !ctx.signature_valid {
+                return Err("Invalid transaction");
+            }
+            
+            if !ctx.is_signed_by_any(|signer| self.roles.has_role(signer, &Role::Admin)) {
+                return Err("Only admin can manage roles");
+            }
+            
+            self.roles.revoke_role(account, role)
+        }
+        
         /// Secure version of admin transfer with timelock
-        pub fn initiate_admin_transfer(&mut self, tx: &Transaction, new_admin: [u8; 32]) -> Result<(), &'static str> {
+        pub fn initiate_admin_transfer(&mut self, ctx: &TxContext, new_admin: [u8; 32]) -> Result<(), &'static str> {
             // Verify transaction and admin status
-            if !tx.valid {
+            if !ctx.signature_valid {
                 return Err("Invalid transaction");
             }
             
-            if tx.caller != self.admin {
+            if !ctx.is_signed_by(&self.admin) {
                 return Err("Only current admin can initiate transfer");
             }
             
-            // Set pending admin with timelock
-            self.pending_admin = Some(new_admin);
-            self.admin_change_time = Some(self.current_time + 86400); // 24-hour timelock
+            if new_admin == self.admin {
+                return Err("New admin is already the admin");
+            }
+            
+            // Set pending admin with timelock, replacing any earlier nominee
+            self.admin_transfer.cancel(|_| true);
+            let effective_time = self.admin_transfer.queue(new_admin, self.current_time);
+            
+            self.events.emit("AdminTransferInitiated", &[
+                ("current_admin", format_address(&self.admin)),
+                ("pending_admin", format_address(&new_admin)),
+                ("effective_time", effective_time.to_string()),
+            ], self.current_time);
             
             Ok(())
         }
         
         /// Second step of admin transfer with timelock
-        pub fn complete_admin_transfer(&mut self, tx: &Transaction) -> Result<(), &'static str> {
+        pub fn complete_admin_transfer(&mut self, ctx: &TxContext) -> Result<(), &'static str> {
             // Verify transaction validity
-            if !tx.valid {
+            if !ctx.signature_valid {
                 return Err("Invalid transaction");
             }
             
-            // Check pending admin exists and caller is the pending admin
-            match self.pending_admin {
-                Some(pending_admin) if pending_admin == tx.caller => {
-                    // Check if timelock has elapsed
-                    match self.admin_change_time {
-                        Some(change_time) if self.current_time >= change_time => {
-                            self.admin = pending_admin;
-                            self.pending_admin = None;
-                            self.admin_change_time = None;
-                            Ok(())
-                        },
-                        _ => Err("Timelock has not expired yet"),
-                    }
-                },
-                _ => Err("Not the pending admin or no admin transfer in progress"),
+            // Check pending admin exists and signed the transaction
+            let (pending_admin, effective_time) = match self.admin_transfer.pending().first() {
+                Some(&(pending_admin, effective_time)) if ctx.is_signed_by(&pending_admin) => (pending_admin, effective_time),
+                _ => return Err("Not the pending admin or no admin transfer in progress"),
+            };
+            
+            // FIXED: Check the timelock against the protocol clock; a caller
+            // could otherwise sign a far-future timestamp and skip the delay
+            if self.current_time < effective_time {
+                return Err("Timelock has not expired yet");
             }
-        }
-        
-        /// Helper to advance time (for testing)
-        pub fn advance_time(&mut self, seconds: u64) {
-            self.current_time += seconds;
+            
+            // FIXED: Hand over every role the admin key holds, not just `Admin`,
+            // so the old key keeps no fee or pause powers. The new admin is
+            // granted first, so the old one is never the last admin.
+            let roles = self.roles.roles_of(&self.admin);
+            for role in &roles {
+                self.roles.grant_role(pending_admin, role.clone());
+            }
+            for role in &roles {
+                self.roles.revoke_role(self.admin, role)?;
+            }
+            
+            // Only drain the timelock and announce once the handover has succeeded
+            self.admin_transfer.ready(self.current_time);
+            self.events.emit("AdminTransferred", &[
+                ("old_admin", format_address(&self.admin)),
+                ("new_admin", format_address(&pending_admin)),
+            ], self.current_time);
+            self.admin = pending_admin;
+            Ok(())
         }
     }
 }
@@ -330,6 +429,7 @@ pub mod secure {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::TxContext;
     
     #[test]
     fn test_vulnerable_access_control() {
@@ -373,22 +473,13 @@ mod tests {
         let attacker = [3u8; 32];
         
         // Create valid transactions (in reality, these would have valid signatures)
-        let admin_tx = secure::Transaction {
-            caller: [1u8; 32],
-            signature: [0u8; 64],
-            valid: true,
-        };
-        
-        let attacker_tx = secure::Transaction {
-            caller: attacker,
-            signature: [0u8; 64],
-            valid: true,
-        };
+        let admin_tx = TxContext::new([1u8; 32]);
+        let attacker_tx = TxContext::new(attacker);
         
         // Attacker cannot change the protocol fee
         let result = protocol.set_fee_percentage(&attacker_tx, 5000);
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Only admin can change fee percentage");
+        assert_eq!(result.unwrap_err(), "Only fee manager can change fee percentage");
         
         // Admin can change the fee
         let result = protocol.set_fee_percentage(&admin_tx, 20); // 0.2%
@@ -397,11 +488,7 @@ mod tests {
         
         // Test admin transfer with timelock
         let new_admin = [4u8; 32];
-        let new_admin_tx = secure::Transaction {
-            caller: new_admin,
-            signature: [0u8; 64],
-            valid: true,
-        };
+        let new_admin_tx = TxContext::new(new_admin);
         
         // Initiate transfer
         let result = protocol.initiate_admin_transfer(&admin_tx, new_admin);
@@ -412,12 +499,128 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "Timelock has not expired yet");
         
-        // Advance time
-        protocol.advance_time(86401); // Just over 24 hours
+        // A far-future timestamp on the transaction doesn't skip the timelock
+        let result = protocol.complete_admin_transfer(&new_admin_tx.clone().at(u64::MAX));
+        assert_eq!(result.unwrap_err(), "Timelock has not expired yet");
         
-        // Now can complete the transfer
+        // Just over 24 hours later, the transfer can complete
+        protocol.advance_time(86401);
         let result = protocol.complete_admin_transfer(&new_admin_tx);
         assert!(result.is_ok());
         assert_eq!(protocol.admin, new_admin);
     }
+    
+    #[test]
+    fn test_secure_admin_transfer_moves_every_role() {
+        use crate::utils::rbac::Role;
+        
+        let admin = [1u8; 32];
+        let new_admin = [4u8; 32];
+        let mut protocol = secure::Protocol::new(admin);
+        let tx = TxContext::new;
+        
+        // Nominating the current admin is refused up front, leaving nothing queued
+        assert_eq!(protocol.initiate_admin_transfer(&tx(admin), admin), Err("New admin is already the admin"));
+        assert!(protocol.admin_transfer.is_empty());
+        assert!(protocol.events.by_kind("AdminTransferInitiated").is_empty());
+        
+        protocol.initiate_admin_transfer(&tx(admin), new_admin).unwrap();
+        protocol.advance_time(86400);
+        protocol.complete_admin_transfer(&tx(new_admin)).unwrap();
+        
+        for role in [Role::Admin, Role::FeeManager, Role::Pauser] {
+            assert!(protocol.roles.has_role(&new_admin, &role));
+            assert!(!protocol.roles.has_role(&admin, &role));
+        }
+        
+        // The old key has lost its fee powers along with the admin role
+        assert_eq!(protocol.set_fee_percentage(&tx(admin), 9999), Err("Only fee manager can change fee percentage"));
+        protocol.set_fee_percentage(&tx(new_admin), 20).unwrap();
+    }
+    
+    #[test]
+    fn test_secure_emits_admin_events() {
+        let admin = [1u8; 32];
+        let new_admin = [4u8; 32];
+        let mut protocol = secure::Protocol::new(admin);
+        let tx = TxContext::new;
+        
+        protocol.set_fee_percentage(&tx(admin), 20).unwrap();
+        // Rejected calls emit nothing
+        let _ = protocol.set_fee_percentage(&tx(new_admin), 5000);
+        
+        let fee_changes = protocol.events.by_kind("FeeChanged");
+        assert_eq!(fee_changes.len(), 1);
+        assert_eq!(fee_changes[0].field("old_fee"), Some("10"));
+        assert_eq!(fee_changes[0].field("new_fee"), Some("20"));
+        
+        protocol.initiate_admin_transfer(&tx(admin), new_admin).unwrap();
+        protocol.advance_time(86400);
+        protocol.complete_admin_transfer(&tx(new_admin)).unwrap();
+        
+        assert_eq!(protocol.events.by_kind("AdminTransferInitiated").len(), 1);
+        let transfers = protocol.events.by_kind("AdminTransferred");
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].timestamp, 86400);
+        assert_eq!(transfers[0].field("new_admin"), Some(crate::utils::display::format_address(&new_admin).as_str()));
+    }
+    
+    #[test]
+    fn test_secure_fee_changes_require_fee_manager_role() {
+        use crate::utils::rbac::Role;
+        
+        let admin = [1u8; 32];
+        let manager = [2u8; 32];
+        let mut protocol = secure::Protocol::new(admin);
+        let tx = TxContext::new;
+        
+        assert_eq!(protocol.set_fee_percentage(&tx(manager), 30), Err("Only fee manager can change fee percentage"));
+        
+        // Only admins hand out roles
+        assert_eq!(protocol.grant_role(&tx(manager), manager, Role::FeeManager), Err("Only admin can manage roles"));
+        protocol.grant_role(&tx(admin), manager, Role::FeeManager).unwrap();
+        protocol.set_fee_percentage(&tx(manager), 30).unwrap();
+        assert_eq!(protocol.fee_percentage, 30);
+        
+        // Admin alone doesn't imply fee management once the role is revoked
+        protocol.revoke_role(&tx(admin), admin, &Role::FeeManager).unwrap();
+        assert_eq!(protocol.set_fee_percentage(&tx(admin), 40), Err("Only fee manager can change fee percentage"));
+        assert_eq!(protocol.revoke_role(&tx(admin), admin, &Role::Admin), Err("Cannot remove the last admin"));
+    }
+    
+    #[test]
+    fn test_secure_settings_accept_owner_as_co_signer() {
+        let (owner, relayer, delegate) = ([2u8; 32], [5u8; 32], [6u8; 32]);
+        let mut protocol = secure::Protocol::new([1u8; 32]);
+        protocol.accounts.insert(owner, secure::UserAccount {
+            owner,
+            balance: 1000,
+            settings: secure::UserSettings { auto_compound: false, withdraw_limit: 100 },
+            authorized_signers: vec![delegate],
+        });
+        let settings = || secure::UserSettings { auto_compound: true, withdraw_limit: 500 };
+        
+        // A relayer alone can't touch the account, but can submit the owner's
+        // or a delegate's co-signed transaction
+        assert_eq!(protocol.update_user_settings(&TxContext::new(relayer), owner, settings()), Err("Not authorized to update settings"));
+        assert!(protocol.update_user_settings(&TxContext::new(relayer).with_signer(owner), owner, settings()).is_ok());
+        assert!(protocol.update_user_settings(&TxContext::new(relayer).with_signer(delegate), owner, settings()).is_ok());
+        
+        // Role checks consider every signer too
+        assert!(protocol.set_fee_percentage(&TxContext::new(relayer).with_signer([1u8; 32]), 25).is_ok());
+    }
+    
+    #[test]
+    fn test_secure_rejects_invalid_signatures() {
+        let admin = [1u8; 32];
+        let mut protocol = secure::Protocol::new(admin);
+        let forged = TxContext { signature_valid: false, ..TxContext::new(admin) };
+        
+        assert_eq!(protocol.set_fee_percentage(&forged, 20), Err("Invalid transaction"));
+        assert_eq!(protocol.grant_role(&forged, [2u8; 32], crate::utils::rbac::Role::Pauser), Err("Invalid transaction"));
+        assert_eq!(protocol.initiate_admin_transfer(&forged, [2u8; 32]), Err("Invalid transaction"));
+        assert!(protocol.update_user_settings(&forged, admin, secure::UserSettings { auto_compound: true, withdraw_limit: 1 }).is_err());
+        assert_eq!(protocol.fee_percentage, 10);
+        assert!(protocol.admin_transfer.is_empty());
+    }
 }
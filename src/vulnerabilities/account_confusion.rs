@@ -10,9 +10,17 @@
 //! - Not verifying Program Derived Addresses (PDAs)
 //! - Cross-instance attacks where one instance's data is used in another instance
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 use crate::utils::{Account, MockBlockchain};
 
+/// Kind of data an account holds, stored alongside it like an Anchor discriminator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountType {
+    Vault,
+    UserToken,
+    Mint,
+}
+
 /// Represents an account confusion vulnerability example
 pub struct AccountConfusionVulnerability;
 
@@ -31,6 +39,18 @@ impl Vulnerability for AccountConfusionVulnerability {
         vec!["Solana", "NEAR"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::AccessControl, Category::InputValidation]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Moderate
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable account validation in a Solana program
@@ -73,6 +93,35 @@ impl Vulnerability for AccountConfusionVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure withdrawal from the vault the program recorded, never one the caller supplies
+        pub fn withdraw(&mut self, user_address: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            // SECURE: Using the known vault address stored in the program state
+            let vault_account = self.token_accounts.get_mut(&self.vault_address)
+                .ok_or("Vault account not found")?;
+            
+            // SECURE: Validate that the vault is owned by the program
+            if vault_account.owner != self.program_id {
+                return Err("Vault account has invalid ownership");
+            }
+            
+            // SECURE: A program-owned account of another kind must not be debited as the vault
+            if vault_account.account_type != AccountType::Vault {
+                return Err("wrong account type");
+            }
+            
+            if vault_account.balance < amount {
+                return Err("Insufficient funds in vault");
+            }
+            
+            vault_account.balance -= amount;
+            // ...credit the user's token account...
+            Ok(())
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Verify that all account ownership checks are implemented correctly",
@@ -97,6 +146,7 @@ impl Vulnerability for AccountConfusionVulnerability {
 /// Module containing a vulnerable implementation
 pub mod vulnerable {
     use std::collections::HashMap;
+    use super::AccountType;
     
     /// A simplified Solana-style program with vulnerable account validation
     pub struct VaultProgram {
@@ -109,6 +159,7 @@ pub mod vulnerable {
     pub struct TokenAccount {
         pub owner: [u8; 32],
         pub balance: u64,
+        pub account_type: AccountType,
     }
     
     impl VaultProgram {
@@ -120,6 +171,7 @@ pub mod vulnerable {
             token_accounts.insert(vault_address, TokenAccount {
                 owner: program_id,
                 balance: 1000000,
+                account_type: AccountType::Vault,
             });
             
             Self {
@@ -134,9 +186,21 @@ pub mod vulnerable {
                         user_address: [u8; 32], 
                         vault_address: [u8; 32], // VULNERABILITY: This should be validated
                         amount: u64) -> Result<(), &'static str> {
+            self.withdraw_from(user_address, vault_address, amount)
+        }
+        
+        /// Withdraw from whichever vault account the caller names
+        ///
+        /// VULNERABILITY: Accounts from every program instance share one key space, so a user of
+        /// this instance can name another instance's vault and drain it (a cross-instance attack).
+        pub fn withdraw_from(&mut self,
+                             user_address: [u8; 32],
+                             vault_address: [u8; 32],
+                             amount: u64) -> Result<(), &'static str> {
             
             // VULNERABILITY: No validation that vault_address is the correct vault
-            // An attacker could pass in any account they control
+            // An attacker could pass in any account they control, even one that
+            // is not a vault at all (e.g. a mint)
             
             // Get the vault account
             let vault_account = self.token_accounts.get_mut(&vault_address)
@@ -152,11 +216,12 @@ pub mod vulnerable {
                 self.token_accounts.insert(user_address, TokenAccount {
                     owner: user_address,
                     balance: 0,
+                    account_type: AccountType::UserToken,
                 });
             }
             
             // Process transfer
-            vault_account.balance -= amount;
+            self.token_accounts.get_mut(&vault_address).unwrap().balance -= amount;
             self.token_accounts.get_mut(&user_address).unwrap().balance += amount;
             
             Ok(())
@@ -167,10 +232,16 @@ pub mod vulnerable {
 /// Module containing a secure implementation
 pub mod secure {
     use std::collections::HashMap;
+    use super::AccountType;
+    use crate::utils::{assert_pda, derive_pda};
+    
+    /// Seed the vault PDA is derived from
+    pub const VAULT_SEED: &[u8] = b"vault";
     
     /// A Solana-style program with secure account validation
     pub struct VaultProgram {
-        pub vault_address: [u8; 32], // The intended vault address
+        pub vault_address: [u8; 32], // PDA derived from VAULT_SEED and the program id
+        pub vault_bump: u8,
         pub token_accounts: HashMap<[u8; 32], TokenAccount>,
         pub program_id: [u8; 32],
     }
@@ -179,26 +250,43 @@ pub mod secure {
     pub struct TokenAccount {
         pub owner: [u8; 32],
         pub balance: u64,
+        pub account_type: AccountType,
     }
     
     impl VaultProgram {
-        /// Create a new vault program
-        pub fn new(program_id: [u8; 32], vault_address: [u8; 32]) -> Self {
+        /// Create a new vault program whose vault lives at the program's PDA
+        pub fn new(program_id: [u8; 32]) -> Self {
+            let (vault_address, vault_bump) = derive_pda(&program_id, &[VAULT_SEED]);
             let mut token_accounts = HashMap::new();
             
             // Create the vault account with some initial tokens
             token_accounts.insert(vault_address, TokenAccount {
                 owner: program_id,
                 balance: 1000000,
+                account_type: AccountType::Vault,
             });
             
             Self {
                 vault_address,
+                vault_bump,
                 token_accounts,
                 program_id,
             }
         }
         
+        /// Withdraw from a vault account supplied by the caller, as a Solana instruction would
+        ///
+        /// SECURE: The supplied account must be the program's vault PDA; any substitute is rejected.
+        pub fn withdraw_with_vault(&mut self,
+                                   user_address: [u8; 32],
+                                   vault_address: [u8; 32],
+                                   amount: u64) -> Result<(), &'static str> {
+            assert_pda(&vault_address, &self.program_id, &[VAULT_SEED], self.vault_bump)
+                .map_err(|_| "Vault account is not the program's PDA")?;
+            
+            self.withdraw(user_address, amount)
+        }
+        
         /// Withdraw tokens with secure account validation
         pub fn withdraw(&mut self, 
                         user_address: [u8; 32], 
@@ -217,6 +305,12 @@ pub mod secure {
                 return Err("Vault account has invalid ownership");
             }
             
+            // SECURE: A program-owned account of another kind (e.g. its mint)
+            // must not be debited as if it were the vault
+            if vault_account.account_type != AccountType::Vault {
+                return Err("wrong account type");
+            }
+            
             // Check balance
             if vault_account.balance < amount {
                 return Err("Insufficient funds in vault");
@@ -227,14 +321,128 @@ pub mod secure {
                 self.token_accounts.insert(user_address, TokenAccount {
                     owner: user_address,
                     balance: 0,
+                    account_type: AccountType::UserToken,
                 });
             }
             
             // Process transfer
-            vault_account.balance -= amount;
+            self.token_accounts.get_mut(&vault_address).unwrap().balance -= amount;
             self.token_accounts.get_mut(&user_address).unwrap().balance += amount;
             
             Ok(())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Two vault instances whose accounts live in one shared key space, as on Solana
+    ///
+    /// Returns the first instance with the second instance's vault visible in its accounts,
+    /// plus that vault's address.
+    fn vulnerable_instances_sharing_accounts() -> (vulnerable::VaultProgram, [u8; 32]) {
+        let mut instance_a = vulnerable::VaultProgram::new([1u8; 32], [0xa0; 32]);
+        let mut instance_b = vulnerable::VaultProgram::new([2u8; 32], [0xb0; 32]);
+        
+        let vault_b = instance_b.vault_address;
+        let account = instance_b.token_accounts.remove(&vault_b).unwrap();
+        instance_a.token_accounts.insert(vault_b, account);
+        (instance_a, vault_b)
+    }
+    
+    #[test]
+    fn test_cross_instance_vault_substitution() {
+        let user = [9u8; 32];
+        
+        // Vulnerable: instance A pays its user out of instance B's vault
+        let (mut instance_a, vault_b) = vulnerable_instances_sharing_accounts();
+        let vault_a = instance_a.vault_address;
+        instance_a.withdraw_from(user, vault_b, 1_000_000).unwrap();
+        assert_eq!(instance_a.token_accounts[&vault_b].balance, 0);
+        assert_eq!(instance_a.token_accounts[&vault_a].balance, 1_000_000);
+        assert_eq!(instance_a.token_accounts[&user].balance, 1_000_000);
+        
+        // Secure: the same setup only ever touches instance A's own vault
+        let mut secure_a = secure::VaultProgram::new([1u8; 32]);
+        let secure_b = secure::VaultProgram::new([2u8; 32]);
+        let secure_vault_b = secure_b.vault_address;
+        secure_a.token_accounts.insert(secure_vault_b, secure::TokenAccount {
+            owner: secure_b.program_id,
+            balance: 1_000_000,
+            account_type: AccountType::Vault,
+        });
+        
+        assert_eq!(secure_a.withdraw_with_vault(user, secure_vault_b, 1_000_000), Err("Vault account is not the program's PDA"));
+        secure_a.withdraw(user, 1_000).unwrap();
+        assert_eq!(secure_a.token_accounts[&secure_vault_b].balance, 1_000_000);
+        assert_eq!(secure_a.token_accounts[&secure_a.vault_address].balance, 1_000_000 - 1_000);
+    }
+    
+    #[test]
+    fn test_secure_vault_rejects_non_pda_account() {
+        let program_id = [1u8; 32];
+        let user = [2u8; 32];
+        let attacker_vault = [3u8; 32];
+        
+        let mut program = secure::VaultProgram::new(program_id);
+        
+        // The attacker fakes a program-owned account at an address of their choosing
+        program.token_accounts.insert(attacker_vault, secure::TokenAccount {
+            owner: program_id,
+            balance: 1_000_000,
+            account_type: AccountType::Vault,
+        });
+        
+        let result = program.withdraw_with_vault(user, attacker_vault, 500);
+        assert_eq!(result.unwrap_err(), "Vault account is not the program's PDA");
+        assert!(!program.token_accounts.contains_key(&user));
+        
+        // The canonical PDA is accepted
+        let vault = program.vault_address;
+        program.withdraw_with_vault(user, vault, 500).unwrap();
+        assert_eq!(program.token_accounts[&user].balance, 500);
+        assert_eq!(program.token_accounts[&vault].balance, 1_000_000 - 500);
+    }
+    
+    #[test]
+    fn test_mint_substituted_for_vault() {
+        let program_id = [1u8; 32];
+        let user = [2u8; 32];
+        let mint = [4u8; 32];
+        
+        // Vulnerable: a program-owned mint is debited as though it were the vault
+        let mut program = vulnerable::VaultProgram::new(program_id, [0xa0; 32]);
+        program.token_accounts.insert(mint, vulnerable::TokenAccount {
+            owner: program_id,
+            balance: 50_000,
+            account_type: AccountType::Mint,
+        });
+        program.withdraw(user, mint, 50_000).unwrap();
+        assert_eq!(program.token_accounts[&mint].balance, 0);
+        assert_eq!(program.token_accounts[&user].balance, 50_000);
+        
+        // Secure: a separate program-owned mint passed as the vault fails the PDA
+        // check before its type is even looked at
+        let mut program = secure::VaultProgram::new(program_id);
+        let vault = program.vault_address;
+        program.token_accounts.insert(mint, secure::TokenAccount {
+            owner: program_id,
+            balance: 50_000,
+            account_type: AccountType::Mint,
+        });
+        assert_eq!(program.withdraw_with_vault(user, mint, 500), Err("Vault account is not the program's PDA"));
+        assert_eq!(program.token_accounts[&mint].balance, 50_000);
+        
+        // The same substitution at the vault address is rejected by type
+        program.token_accounts.get_mut(&vault).unwrap().account_type = AccountType::Mint;
+        assert_eq!(program.withdraw(user, 500), Err("wrong account type"));
+        assert_eq!(program.withdraw_with_vault(user, vault, 500), Err("wrong account type"));
+        assert!(!program.token_accounts.contains_key(&user));
+        
+        program.token_accounts.get_mut(&vault).unwrap().account_type = AccountType::Vault;
+        program.withdraw(user, 500).unwrap();
+        assert_eq!(program.token_accounts[&user].account_type, AccountType::UserToken);
+    }
+}
@@ -8,7 +8,7 @@
 //! In Rust smart contracts, DoS can manifest in various ways, from loops with
 //! unbounded iterations to storage exhaustion attacks.
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 
 /// Represents a denial of service vulnerability example
 pub struct DoSVulnerability;
@@ -29,6 +29,18 @@ impl Vulnerability for DoSVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "All Rust-based contracts"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::Availability]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Moderate
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable function with unbounded iteration
@@ -65,6 +77,47 @@ impl Vulnerability for DoSVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure refunds: each bidder pulls their own, and any push is batched
+        pub fn claim_refund(&mut self, bidder: [u8; 32]) -> Result<(), &'static str> {
+            if !self.ended {
+                return Err("Auction not ended yet");
+            }
+            if Some(bidder) == self.highest_bidder {
+                return Err("Highest bidder cannot claim refund");
+            }
+            
+            // FIXED: One bidder's failed refund can't block anyone else's
+            self.bidder_amounts.remove(&bidder).ok_or("No bid found for this bidder")?;
+            Ok(())
+        }
+        
+        pub fn process_refund_batch(&mut self, max_refunds: usize) -> Result<usize, &'static str> {
+            if !self.ended {
+                return Err("Auction not ended yet");
+            }
+            let Some(highest_bidder) = self.highest_bidder else {
+                return Ok(0);
+            };
+            
+            // FIXED: Process refunds in bounded batches
+            let batch: Vec<[u8; 32]> = self.bidder_amounts.keys()
+                .filter(|&&bidder| bidder != highest_bidder)
+                .cloned()
+                .take(max_refunds)
+                .collect();
+            
+            for bidder in &batch {
+                // A failed transfer is skipped rather than aborting the batch
+                let _ = self.process_single_refund(*bidder);
+                self.bidder_amounts.remove(bidder);
+            }
+            Ok(batch.len())
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Look for loops that iterate over user-controlled collections",
@@ -173,10 +226,17 @@ pub mod vulnerable {
 
 /// Example of secure code that prevents DoS
 pub mod secure {
+    use std::collections::BTreeMap;
+    use std::ops::Bound;
+    use crate::utils::Timelock;
+    
+    /// Bidders refunded by one `refund_page` call, and the cursor for the next page
+    pub type RefundPage = (Vec<[u8; 32]>, Option<[u8; 32]>);
+    
     pub struct Auction {
         pub highest_bidder: Option<[u8; 32]>,
         pub highest_bid: u64,
-        pub bidder_amounts: std::collections::HashMap<[u8; 32], u64>,
+        pub bidder_amounts: BTreeMap<[u8; 32], u64>, // Ordered by key so refunds can be paged with a cursor
         pub ended: bool,
         pub max_bidders: usize,
     }
@@ -186,7 +246,7 @@ pub mod secure {
             Self {
                 highest_bidder: None,
                 highest_bid: 0,
-                bidder_amounts: std::collections::HashMap::new(),
+                bidder_amounts: BTreeMap::new(),
                 ended: false,
                 max_bidders,
             }
@@ -211,7 +271,7 @@ pub mod secure {
             self.highest_bidder = Some(bidder);
             self.highest_bid = amount;
             
-            // FIXED: Efficiently track bids with a HashMap
+            // FIXED: Efficiently track bids in a map
             // Only stores one entry per bidder, preventing storage explosion
             self.bidder_amounts.insert(bidder, amount);
             
@@ -264,13 +324,18 @@ pub mod secure {
                 return Err("Auction not ended yet");
             }
             
+            // No bids means nothing to refund
+            let Some(highest_bidder) = self.highest_bidder else {
+                return Ok(0);
+            };
+            
             // FIXED: Process refunds in bounded batches
             let mut refunded_count = 0;
-            let highest_bidder_key = self.highest_bidder.unwrap_or([0; 32]);
             
-            // Get bidders to refund (excluding highest bidder)
+            // Get bidders to refund (excluding highest bidder). Comparing the real key rather
+            // than a sentinel keeps a bidder whose key is all zeroes eligible for a refund.
             let bidders_to_refund: Vec<[u8; 32]> = self.bidder_amounts.keys()
-                .filter(|&&k| k != highest_bidder_key)
+                .filter(|&&k| k != highest_bidder)
                 .cloned()
                 .take(max_refunds)
                 .collect();
@@ -289,6 +354,44 @@ pub mod secure {
             Ok(refunded_count)
         }
         
+        /// Refund up to `limit` bidders in ascending key order, starting after the `start_after` cursor
+        ///
+        /// Returns the refunded keys and the cursor for the next page (`None` once every losing
+        /// bidder has been refunded). Ordering by key keeps pages stable while refunded entries are
+        /// removed, so no bidder is refunded twice or skipped. Does nothing before the auction ends.
+        /// A zero `limit` is rejected, since its empty page could not carry a cursor forward.
+        pub fn refund_page(&mut self, start_after: Option<[u8; 32]>, limit: usize) -> Result<RefundPage, &'static str> {
+            if limit == 0 {
+                return Err("Page limit must be greater than zero");
+            }
+            if !self.ended {
+                return Ok((Vec::new(), None));
+            }
+            
+            // Walk the ordered keys from the cursor instead of sorting every bidder on each page
+            let from = start_after.map_or(Bound::Unbounded, Bound::Excluded);
+            let highest_bidder = self.highest_bidder;
+            let mut remaining = self.bidder_amounts.range((from, Bound::Unbounded))
+                .map(|(&bidder, _)| bidder)
+                .filter(|&bidder| Some(bidder) != highest_bidder);
+            let page: Vec<[u8; 32]> = remaining.by_ref().take(limit).collect();
+            let more = remaining.next().is_some();
+            
+            for bidder in &page {
+                // Failed transfers are skipped without reverting the page
+                let _ = self.process_single_refund(*bidder);
+                self.bidder_amounts.remove(bidder);
+            }
+            
+            let next_cursor = if more {
+                page.last().cloned()
+            } else {
+                None
+            };
+            
+            Ok((page, next_cursor))
+        }
+        
         /// Helper function that doesn't revert the entire batch on failure
         fn process_single_refund(&self, bidder: [u8; 32]) -> Result<(), &'static str> {
             // In a real contract, this would transfer funds
@@ -303,6 +406,80 @@ pub mod secure {
             Ok(())
         }
     }
+    
+    /// Vault whose withdrawals are requested first and claimed after a cooldown
+    ///
+    /// A bank run can't drain the vault in one block: every withdrawal waits
+    /// `cooldown` seconds, giving operators time to react, and each account
+    /// may only have `max_pending` requests outstanding so the queue stays bounded.
+    pub struct WithdrawalQueue {
+        pub balances: std::collections::HashMap<[u8; 32], u64>,
+        pub requests: std::collections::HashMap<[u8; 32], Timelock<u64>>, // account -> requested amounts
+        pub cooldown: u64,
+        pub max_pending: usize,
+    }
+    
+    impl WithdrawalQueue {
+        pub fn new(cooldown: u64, max_pending: usize) -> Self {
+            Self {
+                balances: std::collections::HashMap::new(),
+                requests: std::collections::HashMap::new(),
+                cooldown,
+                max_pending,
+            }
+        }
+        
+        pub fn deposit(&mut self, account: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            let balance = self.balances.entry(account).or_insert(0);
+            *balance = balance.checked_add(amount).ok_or("Balance overflow")?;
+            Ok(())
+        }
+        
+        /// Lock `amount` for withdrawal, returning when it becomes claimable
+        ///
+        /// The amount leaves the account's balance immediately, so it can't be
+        /// requested twice. Several requests may be pending at once.
+        pub fn request_withdrawal(&mut self, account: [u8; 32], amount: u64, now: u64) -> Result<u64, &'static str> {
+            if amount == 0 {
+                return Err("Amount must be greater than zero");
+            }
+            
+            let balance = self.balances.get(&account).copied().unwrap_or(0);
+            let remaining = balance.checked_sub(amount).ok_or("Insufficient balance")?;
+            // FIXED: Bounded per-account queue, so requests can't pile up without limit
+            if self.requests.get(&account).is_some_and(|pending| pending.pending().len() >= self.max_pending) {
+                return Err("Too many pending withdrawals");
+            }
+            
+            // Only create the queue entry once the request is known to succeed
+            let cooldown = self.cooldown;
+            let pending = self.requests.entry(account).or_insert_with(|| Timelock::new(cooldown));
+            self.balances.insert(account, remaining);
+            Ok(pending.queue(amount, now))
+        }
+        
+        /// Pay out every request whose cooldown has passed, returning the total
+        pub fn claim_withdrawal(&mut self, account: [u8; 32], now: u64) -> Result<u64, &'static str> {
+            let pending = self.requests.get_mut(&account).ok_or("No withdrawal requested")?;
+            if pending.is_empty() {
+                return Err("No withdrawal requested");
+            }
+            
+            let ready = pending.ready(now);
+            if ready.is_empty() {
+                return Err("Withdrawal still cooling down");
+            }
+            if pending.is_empty() {
+                self.requests.remove(&account);
+            }
+            
+            // In a real contract, this would transfer the funds out
+            ready
+                .iter()
+                .try_fold(0u64, |total, &amount| total.checked_add(amount))
+                .ok_or("Withdrawal total overflow")
+        }
+    }
 }
 
 #[cfg(test)]
@@ -360,4 +537,123 @@ mod tests {
         // Even with a malicious bidder, other refunds can still be processed
         let _ = auction.claim_refund([5; 32]);
     }
+    
+    #[test]
+    fn test_refund_batch_excludes_only_the_real_highest_bidder() {
+        // A losing bidder keyed [0; 32] must still be refunded
+        let mut auction = secure::Auction::new(10);
+        auction.place_bid([0; 32], 100).unwrap();
+        auction.place_bid([1; 32], 200).unwrap();
+        auction.end_auction().unwrap();
+        
+        assert_eq!(auction.process_refund_batch(10), Ok(1));
+        assert!(!auction.bidder_amounts.contains_key(&[0; 32]));
+        assert!(auction.bidder_amounts.contains_key(&[1; 32]));
+        
+        // A winning bidder keyed [0; 32] must not be refunded
+        let mut auction = secure::Auction::new(10);
+        auction.place_bid([1; 32], 100).unwrap();
+        auction.place_bid([0; 32], 200).unwrap();
+        auction.end_auction().unwrap();
+        
+        assert_eq!(auction.process_refund_batch(10), Ok(1));
+        assert!(auction.bidder_amounts.contains_key(&[0; 32]));
+        assert!(!auction.bidder_amounts.contains_key(&[1; 32]));
+    }
+    
+    #[test]
+    fn test_refund_batch_with_no_bids() {
+        let mut auction = secure::Auction::new(10);
+        auction.end_auction().unwrap();
+        
+        assert_eq!(auction.process_refund_batch(10), Ok(0));
+    }
+    
+    #[test]
+    fn test_refund_pages_refund_every_bidder_exactly_once() {
+        let mut auction = secure::Auction::new(100);
+        for i in 1..=25u8 {
+            auction.place_bid([i; 32], i as u64 * 10).unwrap();
+        }
+        auction.end_auction().unwrap();
+        
+        let mut refunded = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, next) = auction.refund_page(cursor, 4).unwrap();
+            assert!(page.len() <= 4);
+            refunded.extend(page);
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        
+        // Every losing bidder was refunded once; the winner was left alone
+        let mut unique = refunded.clone();
+        unique.dedup();
+        assert_eq!(unique.len(), refunded.len());
+        assert_eq!(refunded.len(), 24);
+        assert!(!refunded.contains(&[25; 32]));
+        assert_eq!(auction.bidder_amounts.len(), 1);
+    }
+    
+    #[test]
+    fn test_refund_page_rejects_zero_limit() {
+        let mut auction = secure::Auction::new(100);
+        auction.place_bid([1; 32], 10).unwrap();
+        auction.place_bid([2; 32], 20).unwrap();
+        auction.end_auction().unwrap();
+        
+        // An empty page would hand back a `None` cursor that reads as "done"
+        assert_eq!(auction.refund_page(None, 0), Err("Page limit must be greater than zero"));
+        assert_eq!(auction.bidder_amounts.len(), 2);
+        
+        assert_eq!(auction.refund_page(None, 1), Ok((vec![[1; 32]], None)));
+    }
+    
+    #[test]
+    fn test_withdrawals_pay_out_only_after_cooldown() {
+        let account = [1u8; 32];
+        let mut queue = secure::WithdrawalQueue::new(3600, 3);
+        queue.deposit(account, 1_000).unwrap();
+        
+        assert_eq!(queue.claim_withdrawal(account, 0), Err("No withdrawal requested"));
+        assert_eq!(queue.request_withdrawal(account, 300, 0), Ok(3600));
+        assert_eq!(queue.request_withdrawal(account, 200, 600), Ok(4200));
+        assert_eq!(queue.balances[&account], 500);
+        
+        assert_eq!(queue.claim_withdrawal(account, 3599), Err("Withdrawal still cooling down"));
+        assert_eq!(queue.claim_withdrawal(account, 3600), Ok(300));
+        assert_eq!(queue.claim_withdrawal(account, 3601), Err("Withdrawal still cooling down"));
+        
+        // Matured requests accumulate into a single claim
+        queue.request_withdrawal(account, 100, 700).unwrap();
+        assert_eq!(queue.claim_withdrawal(account, 10_000), Ok(300));
+        assert_eq!(queue.claim_withdrawal(account, 10_000), Err("No withdrawal requested"));
+        assert_eq!(queue.balances[&account], 400);
+    }
+    
+    #[test]
+    fn test_withdrawal_queue_is_bounded_per_account() {
+        let (account, other) = ([1u8; 32], [2u8; 32]);
+        let mut queue = secure::WithdrawalQueue::new(60, 2);
+        queue.deposit(account, 1_000).unwrap();
+        queue.deposit(other, 1_000).unwrap();
+        
+        queue.request_withdrawal(account, 1, 0).unwrap();
+        queue.request_withdrawal(account, 1, 0).unwrap();
+        assert_eq!(queue.request_withdrawal(account, 1, 0), Err("Too many pending withdrawals"));
+        assert_eq!(queue.request_withdrawal(other, 1_001, 0), Err("Insufficient balance"));
+        queue.request_withdrawal(other, 1, 0).unwrap();
+        
+        // A rejected request from an unknown account leaves no queue entry behind
+        assert_eq!(queue.request_withdrawal([3u8; 32], 1, 0), Err("Insufficient balance"));
+        assert!(!queue.requests.contains_key(&[3u8; 32]));
+        assert!(!queue.balances.contains_key(&[3u8; 32]));
+        
+        // Claiming frees the slots again
+        queue.claim_withdrawal(account, 60).unwrap();
+        queue.request_withdrawal(account, 1, 60).unwrap();
+    }
 }
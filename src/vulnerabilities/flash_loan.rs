@@ -8,7 +8,7 @@
 //! In Rust-based smart contracts, these vulnerabilities can manifest in various ways,
 //! particularly in DeFi applications across different blockchain platforms.
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 
 /// Represents a flash loan vulnerability example
 pub struct FlashLoanVulnerability;
@@ -29,6 +29,18 @@ impl Vulnerability for FlashLoanVulnerability {
         vec!["Solana", "NEAR", "All DeFi platforms"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::DeFi, Category::Economic]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Advanced
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable price calculation that can be manipulated by flash loans
@@ -66,6 +78,34 @@ impl Vulnerability for FlashLoanVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure liquidation priced with a TWAP that a flash loan can't move
+        pub fn liquidate_position(&mut self, position_id: u64, liquidator: [u8; 32]) -> Result<(), &'static str> {
+            self.pausable.when_not_paused().map_err(|_| "Protocol is paused")?;
+            
+            let position = self.positions.get(&position_id).cloned().ok_or("Position not found")?;
+            
+            // FIXED: Use TWAP from oracle instead of spot price
+            let collateral_value = self.collateral_value(&position)?;
+            let debt_value = self.debt_value(&position)?;
+            
+            let min_collateral_value = debt_value * self.liquidation_threshold as u128 / 100;
+            if collateral_value as u128 >= min_collateral_value {
+                return Err("Position is not liquidatable");
+            }
+            
+            self.positions.remove(&position_id);
+            
+            // FIXED: Any shortfall is recorded as bad debt instead of vanishing
+            let shortfall = debt_value.saturating_sub(collateral_value as u128);
+            self.bad_debt = self.bad_debt.saturating_add(u64::try_from(shortfall).unwrap_or(u64::MAX));
+            
+            Ok(())
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Examine price oracle implementations for manipulation vulnerabilities",
@@ -87,8 +127,40 @@ impl Vulnerability for FlashLoanVulnerability {
     }
 }
 
+/// `numerator * 1_000_000 / denominator`, saturating instead of overflowing
+fn scaled_ratio(numerator: u64, denominator: u64) -> u64 {
+    if denominator == 0 {
+        return 0;
+    }
+    let ratio = numerator as u128 * 1_000_000 / denominator as u128;
+    u64::try_from(ratio).unwrap_or(u64::MAX)
+}
+
+/// Constant-product swap of `amount_in` into `reserve_in`, returning the amount paid out of `reserve_out`
+///
+/// `k` is computed in u128. Returns 0 without touching the reserves if the
+/// input reserve would no longer fit in a u64. The output reserve is rounded
+/// up so `k` never decreases and the pool can't be fully drained.
+fn constant_product_swap(reserve_in: &mut u64, reserve_out: &mut u64, amount_in: u64) -> u64 {
+    let k = *reserve_in as u128 * *reserve_out as u128;
+    
+    let new_in_reserves = match reserve_in.checked_add(amount_in) {
+        Some(reserves) => reserves,
+        None => return 0,
+    };
+    // k / new_in_reserves <= reserve_out, so the cast cannot truncate
+    let new_out_reserves = k.div_ceil(new_in_reserves as u128) as u64;
+    let amount_out = *reserve_out - new_out_reserves;
+    
+    *reserve_in = new_in_reserves;
+    *reserve_out = new_out_reserves;
+    
+    amount_out
+}
+
 /// Example of vulnerable code susceptible to flash loan attacks
 pub mod vulnerable {
+    use super::{constant_product_swap, scaled_ratio};
     use std::collections::HashMap;
     
     #[derive(Debug, Clone)]
@@ -100,8 +172,7 @@ pub mod vulnerable {
     #[derive(Debug, Clone)]
     pub struct UserPosition {
         pub owner: [u8; 32],
-        pub collateral_token: String,
-        pub collateral_amount: u64,
+        pub collateral: HashMap<String, u64>, // token -> amount
         pub borrowed_token: String,
         pub borrowed_amount: u64,
     }
@@ -120,36 +191,23 @@ pub mod vulnerable {
     }
     
     impl DexPool {
+        /// Price of `base_token` in `quote_token`, scaled by 1_000_000.
+        /// Computed with a u128 intermediate and saturated to `u64::MAX`.
         pub fn get_price(&self, base_token: &str, quote_token: &str) -> u64 {
             if base_token == self.token_a && quote_token == self.token_b {
-                return self.token_b_reserves * 1_000_000 / self.token_a_reserves;
+                return scaled_ratio(self.token_b_reserves, self.token_a_reserves);
             } else if base_token == self.token_b && quote_token == self.token_a {
-                return self.token_a_reserves * 1_000_000 / self.token_b_reserves;
+                return scaled_ratio(self.token_a_reserves, self.token_b_reserves);
             }
             0
         }
         
+        /// Constant-product swap of `amount_in` of `token_in`; see `constant_product_swap`
         pub fn swap(&mut self, token_in: &str, amount_in: u64) -> u64 {
-            let k = self.token_a_reserves * self.token_b_reserves;
-            
             if token_in == self.token_a {
-                let new_a_reserves = self.token_a_reserves + amount_in;
-                let new_b_reserves = k / new_a_reserves;
-                let amount_out = self.token_b_reserves - new_b_reserves;
-                
-                self.token_a_reserves = new_a_reserves;
-                self.token_b_reserves = new_b_reserves;
-                
-                return amount_out;
+                return constant_product_swap(&mut self.token_a_reserves, &mut self.token_b_reserves, amount_in);
             } else if token_in == self.token_b {
-                let new_b_reserves = self.token_b_reserves + amount_in;
-                let new_a_reserves = k / new_b_reserves;
-                let amount_out = self.token_a_reserves - new_a_reserves;
-                
-                self.token_a_reserves = new_a_reserves;
-                self.token_b_reserves = new_b_reserves;
-                
-                return amount_out;
+                return constant_product_swap(&mut self.token_b_reserves, &mut self.token_a_reserves, amount_in);
             }
             
             0
@@ -223,12 +281,13 @@ pub mod vulnerable {
                 None => return Err("Position not found"),
             };
             
-            // VULNERABILITY: Price from a single DEX pool, vulnerable to manipulation
-            let collateral_price = self.get_token_price(&position.collateral_token);
+            // VULNERABILITY: Prices from a single DEX pool, vulnerable to manipulation
             let borrowed_price = self.get_token_price(&position.borrowed_token);
             
             // Calculate position health
-            let collateral_value = position.collateral_amount * collateral_price / 1_000_000;
+            let collateral_value: u64 = position.collateral.iter()
+                .map(|(token, amount)| amount * self.get_token_price(token) / 1_000_000)
+                .sum();
             let debt_value = position.borrowed_amount * borrowed_price / 1_000_000;
             
             // Check if position is undercollateralized
@@ -239,8 +298,6 @@ pub mod vulnerable {
             }
             
             // Calculate liquidation amount (for simplicity, we liquidate the whole position)
-            let liquidation_bonus_amount = position.collateral_amount * self.liquidation_bonus / 100;
-            let liquidator_collateral = position.collateral_amount - liquidation_bonus_amount;
             
             // Perform liquidation (simplified)
             // In a real contract, the liquidator would need to repay the debt
@@ -268,12 +325,17 @@ pub mod vulnerable {
             1_000_000 // Default price of 1.0 if not found
         }
         
-        /// Create a position (for testing)
+        /// Create a single-collateral position (for testing)
         pub fn create_position(&mut self, owner: [u8; 32], collateral_token: &str, collateral_amount: u64, borrowed_token: &str, borrowed_amount: u64) -> u64 {
+            let collateral = HashMap::from([(collateral_token.to_string(), collateral_amount)]);
+            self.create_multi_collateral_position(owner, collateral, borrowed_token, borrowed_amount)
+        }
+        
+        /// Create a position backed by several collateral tokens (for testing)
+        pub fn create_multi_collateral_position(&mut self, owner: [u8; 32], collateral: HashMap<String, u64>, borrowed_token: &str, borrowed_amount: u64) -> u64 {
             let position = UserPosition {
                 owner,
-                collateral_token: collateral_token.to_string(),
-                collateral_amount,
+                collateral,
                 borrowed_token: borrowed_token.to_string(),
                 borrowed_amount,
             };
@@ -290,23 +352,83 @@ pub mod vulnerable {
 
 /// Example of secure code that prevents flash loan attacks
 pub mod secure {
+    use super::{constant_product_swap, scaled_ratio};
     use std::collections::HashMap;
     use std::collections::VecDeque;
+    use crate::utils::{time_weighted_average, EventLog, MockBlockchain, Pausable};
+    use crate::utils::rbac::{Role, RoleRegistry};
+    
+    /// Flash loan fee tiers as (max share of reserves, fee), both in basis points
+    ///
+    /// A loan pays the rate of the first tier whose share it does not exceed,
+    /// so draining a large part of a pool costs proportionally more.
+    pub const FLASH_FEE_TIERS: [(u64, u64); 3] = [
+        (1_000, 5),    // up to 10% of reserves: 0.05%
+        (5_000, 30),   // up to 50% of reserves: 0.3%
+        (10_000, 100), // anything larger: 1%
+    ];
+    
+    /// How long a liquidation auction runs before it expires, in seconds
+    pub const LIQUIDATION_AUCTION_DURATION: u64 = 3600;
+    
+    /// Auction start and end prices as percentages of the collateral's TWAP value
+    pub const AUCTION_START_PERCENT: u64 = 120;
+    pub const AUCTION_END_PERCENT: u64 = 80;
+    
+    /// A Dutch auction selling a position's collateral for repayment of its debt
+    ///
+    /// The asking price falls linearly from `start_price` to `end_price` over
+    /// `LIQUIDATION_AUCTION_DURATION`, so collateral goes to the first liquidator
+    /// willing to pay the market rate instead of at a fixed discount.
+    #[derive(Debug, Clone)]
+    pub struct LiquidationAuction {
+        pub start_time: u64,
+        pub start_price: u64,
+        pub end_price: u64,
+    }
+    
+    impl LiquidationAuction {
+        /// Asking price at `now`, or `None` once the auction has expired
+        pub fn price_at(&self, now: u64) -> Option<u64> {
+            let elapsed = now.saturating_sub(self.start_time);
+            if elapsed >= LIQUIDATION_AUCTION_DURATION {
+                return None;
+            }
+            let decay = (self.start_price - self.end_price) as u128 * elapsed as u128
+                / LIQUIDATION_AUCTION_DURATION as u128;
+            Some(self.start_price - decay as u64)
+        }
+    }
+    
+    /// Fee owed on a flash loan of `amount` from a pool holding `reserves`
+    pub fn compute_flash_fee(amount: u64, reserves: u64) -> u64 {
+        if amount == 0 {
+            return 0;
+        }
+        
+        let share = amount as u128 * 10_000;
+        let fee_bps = FLASH_FEE_TIERS.iter()
+            .find(|&&(max_share_bps, _)| share <= reserves as u128 * max_share_bps as u128)
+            .map_or(FLASH_FEE_TIERS[FLASH_FEE_TIERS.len() - 1].1, |&(_, fee_bps)| fee_bps);
+        
+        (amount as u128 * fee_bps as u128 / 10_000) as u64
+    }
     
     #[derive(Debug, Clone)]
     pub struct LendingPool {
         pub token_reserves: u64,
         pub name: String,
-        pub flash_loan_fee: u64, // in basis points (e.g., 30 = 0.3%)
+        pub in_flash_loan: bool, // Set while a flash loan from this pool is outstanding
+        pub borrow_rate_bps_per_sec: u64, // Simple interest charged on debt, in basis points per second
     }
     
     #[derive(Debug, Clone)]
     pub struct UserPosition {
         pub owner: [u8; 32],
-        pub collateral_token: String,
-        pub collateral_amount: u64,
+        pub collateral: HashMap<String, u64>, // token -> amount
         pub borrowed_token: String,
-        pub borrowed_amount: u64,
+        pub borrowed_amount: u64, // Debt as of `last_accrual_time`
+        pub last_accrual_time: u64,
     }
     
     #[derive(Debug, Clone)]
@@ -319,6 +441,10 @@ pub mod secure {
     pub struct PriceData {
         pub current_price: u64,
         pub price_history: VecDeque<(u64, u64)>, // (timestamp, price)
+        /// Sum of price × seconds since the token's first update (Uniswap V2 style)
+        pub price_cumulative: u128,
+        /// When `price_cumulative` was last brought up to date
+        pub last_cumulative_ts: u64,
     }
     
     impl PriceOracle {
@@ -329,12 +455,25 @@ pub mod secure {
             }
         }
         
-        pub fn update_price(&mut self, token: &str, price: u64) {
+        /// Record a new price for `token` at the oracle's current time
+        ///
+        /// Rejected if the clock is behind the token's last update, since the
+        /// cumulative price can only accrue forwards.
+        pub fn update_price(&mut self, token: &str, price: u64) -> Result<(), &'static str> {
             let price_data = self.token_prices.entry(token.to_string()).or_insert(PriceData {
                 current_price: price,
                 price_history: VecDeque::new(),
+                price_cumulative: 0,
+                last_cumulative_ts: self.current_time,
             });
             
+            // Accrue the outgoing price for as long as it was in effect
+            let elapsed = self.current_time.checked_sub(price_data.last_cumulative_ts)
+                .ok_or("Oracle time moved backwards")?;
+            price_data.price_cumulative = price_data.price_cumulative
+                .wrapping_add(price_data.current_price as u128 * elapsed as u128);
+            price_data.last_cumulative_ts = self.current_time;
+            
             // Update current price
             price_data.current_price = price;
             
@@ -350,29 +489,45 @@ pub mod secure {
                     break;
                 }
             }
+            
+            Ok(())
         }
         
         pub fn get_spot_price(&self, token: &str) -> Option<u64> {
             self.token_prices.get(token).map(|data| data.current_price)
         }
         
-        pub fn get_twap(&self, token: &str, period: u64) -> Option<u64> {
+        /// Snapshot of `token`'s `(price_cumulative, timestamp)` as of now
+        ///
+        /// Includes the current price's accrual since the last update without writing
+        /// it, so two snapshots taken any time apart give a TWAP via `twap_between`.
+        /// `None` if the token is unknown or the clock is behind its last update.
+        pub fn cumulative_price(&self, token: &str) -> Option<(u128, u64)> {
             let price_data = self.token_prices.get(token)?;
-            
-            let min_time = self.current_time.saturating_sub(period);
-            
-            let relevant_prices: Vec<u64> = price_data.price_history
-                .iter()
-                .filter(|(timestamp, _)| *timestamp >= min_time)
-                .map(|(_, price)| *price)
-                .collect();
-            
-            if relevant_prices.is_empty() {
-                return None;
+            let elapsed = self.current_time.checked_sub(price_data.last_cumulative_ts)?;
+            let cumulative = price_data.price_cumulative
+                .wrapping_add(price_data.current_price as u128 * elapsed as u128);
+            Some((cumulative, self.current_time))
+        }
+        
+        /// Average price between two cumulative-price snapshots, 0 if no time separates them
+        ///
+        /// The accumulator may wrap; as in Uniswap V2 only the difference between
+        /// snapshots matters, so it is taken with wrapping arithmetic.
+        pub fn twap_between(&self, start_cumulative: u128, start_ts: u64, now_cumulative: u128, now_ts: u64) -> u64 {
+            let elapsed = now_ts.saturating_sub(start_ts);
+            if elapsed == 0 {
+                return 0;
             }
-            
-            let sum: u64 = relevant_prices.iter().sum();
-            Some(sum / relevant_prices.len() as u64)
+            let average = now_cumulative.wrapping_sub(start_cumulative) / elapsed as u128;
+            u64::try_from(average).unwrap_or(u64::MAX)
+        }
+        
+        /// Time-weighted average price of `token` over the last `period` seconds
+        pub fn get_twap(&self, token: &str, period: u64) -> Option<u64> {
+            let price_data = self.token_prices.get(token)?;
+            let window_start = self.current_time.saturating_sub(period);
+            time_weighted_average(price_data.price_history.iter().copied(), window_start, self.current_time)
         }
         
         pub fn advance_time(&mut self, seconds: u64) {
@@ -389,36 +544,23 @@ pub mod secure {
     }
     
     impl DexPool {
+        /// Price of `base_token` in `quote_token`, scaled by 1_000_000.
+        /// Computed with a u128 intermediate and saturated to `u64::MAX`.
         pub fn get_price(&self, base_token: &str, quote_token: &str) -> u64 {
             if base_token == self.token_a && quote_token == self.token_b {
-                return self.token_b_reserves * 1_000_000 / self.token_a_reserves;
+                return scaled_ratio(self.token_b_reserves, self.token_a_reserves);
             } else if base_token == self.token_b && quote_token == self.token_a {
-                return self.token_a_reserves * 1_000_000 / self.token_b_reserves;
+                return scaled_ratio(self.token_a_reserves, self.token_b_reserves);
             }
             0
         }
         
+        /// Constant-product swap of `amount_in` of `token_in`; see `constant_product_swap`
         pub fn swap(&mut self, token_in: &str, amount_in: u64) -> u64 {
-            let k = self.token_a_reserves * self.token_b_reserves;
-            
             if token_in == self.token_a {
-                let new_a_reserves = self.token_a_reserves + amount_in;
-                let new_b_reserves = k / new_a_reserves;
-                let amount_out = self.token_b_reserves - new_b_reserves;
-                
-                self.token_a_reserves = new_a_reserves;
-                self.token_b_reserves = new_b_reserves;
-                
-                return amount_out;
+                return constant_product_swap(&mut self.token_a_reserves, &mut self.token_b_reserves, amount_in);
             } else if token_in == self.token_b {
-                let new_b_reserves = self.token_b_reserves + amount_in;
-                let new_a_reserves = k / new_b_reserves;
-                let amount_out = self.token_a_reserves - new_a_reserves;
-                
-                self.token_a_reserves = new_a_reserves;
-                self.token_b_reserves = new_b_reserves;
-                
-                return amount_out;
+                return constant_product_swap(&mut self.token_b_reserves, &mut self.token_a_reserves, amount_in);
             }
             
             0
@@ -434,6 +576,12 @@ pub mod secure {
         pub liquidation_bonus: u64,     // e.g., 5 means 5% bonus for liquidators
         pub price_oracle: PriceOracle,
         pub max_flash_loan_amount: u64, // As percentage of pool size (e.g., 50 = 50%)
+        pub events: EventLog,
+        pub pausable: Pausable,
+        pub roles: RoleRegistry, // Accounts with `Role::Pauser` may pause
+        pub bad_debt: u64, // Debt value left uncovered by liquidated collateral
+        pub liquidation_auctions: HashMap<u64, LiquidationAuction>, // position id -> running auction
+        pub collateral_factors: HashMap<String, u64>, // token -> percentage of value counted as collateral
     }
     
     impl LendingProtocol {
@@ -447,125 +595,569 @@ pub mod secure {
                 liquidation_bonus: 5,      // 5% bonus for liquidators
                 price_oracle: PriceOracle::new(),
                 max_flash_loan_amount: 50, // 50% of pool can be borrowed in a flash loan
+                events: EventLog::new(),
+                pausable: Pausable::new(),
+                roles: RoleRegistry::new(),
+                bad_debt: 0,
+                liquidation_auctions: HashMap::new(),
+                collateral_factors: HashMap::new(),
+            }
+        }
+        
+        /// Set how much of `token`'s value counts toward position health
+        ///
+        /// A stablecoin might count at 90%, a volatile token at 50%. Tokens
+        /// without a factor cannot back a position.
+        pub fn set_collateral_factor(&mut self, token: &str, factor_percent: u64) -> Result<(), &'static str> {
+            if factor_percent > 100 {
+                return Err("Collateral factor cannot exceed 100%");
+            }
+            self.collateral_factors.insert(token.to_string(), factor_percent);
+            Ok(())
+        }
+        
+        /// Total debt value written off because liquidated collateral could not cover it
+        pub fn total_bad_debt(&self) -> u64 {
+            self.bad_debt
+        }
+        
+        /// Halt new flash loans and liquidations
+        pub fn pause(&mut self, caller: [u8; 32]) -> Result<(), &'static str> {
+            if !self.roles.has_role(&caller, &Role::Pauser) {
+                return Err("Only pauser can pause");
             }
+            self.pausable.pause().map_err(|_| "Protocol already paused")
+        }
+        
+        /// Resume normal operation
+        pub fn unpause(&mut self, caller: [u8; 32]) -> Result<(), &'static str> {
+            if !self.roles.has_role(&caller, &Role::Pauser) {
+                return Err("Only pauser can unpause");
+            }
+            self.pausable.unpause().map_err(|_| "Protocol is not paused")
         }
         
         /// Secure flash loan function with fees and limits
+        ///
+        /// Pausing blocks new loans only. A loan already in flight when the
+        /// protocol is paused (e.g. from inside the callback) still runs its
+        /// repayment check, so lent funds are never stranded.
+        ///
+        /// The callback may borrow from other pools, but a nested loan from a
+        /// pool that is already lending is rejected.
         pub fn flash_loan(&mut self, token: &str, amount: u64, callback: impl FnOnce(&mut Self) -> Result<(), &'static str>) -> Result<(), &'static str> {
-            let pool = match self.lending_pools.get_mut(token) {
-                Some(pool) => pool,
-                None => return Err("Pool not found"),
-            };
-            
-            // FIXED: Limit flash loan amount
-            let max_loan = pool.token_reserves * self.max_flash_loan_amount / 100;
-            if amount > max_loan {
-                return Err("Flash loan exceeds maximum allowed amount");
+            self.flash_loan_multi(&[(token.to_string(), amount)], callback)
+        }
+        
+        /// Borrow several tokens atomically and repay them in one callback
+        ///
+        /// Every pool is checked and debited before the callback runs, and each
+        /// must be repaid with its own fee afterwards. If the callback fails,
+        /// any single repayment is short or a pool disappears, every remaining
+        /// pool's reserves are restored and its flash loan flag cleared.
+        pub fn flash_loan_multi(&mut self, loans: &[(String, u64)], callback: impl FnOnce(&mut Self) -> Result<(), &'static str>) -> Result<(), &'static str> {
+            self.pausable.when_not_paused().map_err(|_| "Protocol is paused")?;
+            
+            if loans.is_empty() {
+                return Err("No flash loans requested");
             }
             
-            // FIXED: Calculate fee
-            let fee_amount = amount * pool.flash_loan_fee / 10000;
-            let repay_amount = amount + fee_amount;
-            
-            // Record initial state for validation
-            let initial_reserves = pool.token_reserves;
+            // Validate the whole batch before lending anything
+            let mut batch: Vec<(&str, u64, u64, u64)> = Vec::new(); // (token, amount, fee, initial reserves)
+            for (token, amount) in loans {
+                // A repeated token would have its repayment counted twice
+                if batch.iter().any(|&(seen, ..)| seen == token.as_str()) {
+                    return Err("Duplicate token in flash loan batch");
+                }
+                
+                let pool = self.lending_pools.get(token).ok_or("Pool not found")?;
+                if pool.in_flash_loan {
+                    return Err("nested flash loan");
+                }
+                if *amount > pool.token_reserves * self.max_flash_loan_amount / 100 {
+                    return Err("Flash loan exceeds maximum allowed amount");
+                }
+                
+                let fee_amount = compute_flash_fee(*amount, pool.token_reserves);
+                batch.push((token.as_str(), *amount, fee_amount, pool.token_reserves));
+            }
             
             // Lend the tokens
-            pool.token_reserves -= amount;
+            for &(token, amount, _, _) in &batch {
+                let pool = self.lending_pools.get_mut(token).unwrap();
+                pool.in_flash_loan = true;
+                pool.token_reserves -= amount;
+            }
             
-            // Execute the callback
             let result = callback(self);
             
-            // Check if the pool has been repaid
-            let pool = match self.lending_pools.get_mut(token) {
-                Some(pool) => pool,
-                None => return Err("Pool disappeared during flash loan"),
-            };
+            // FIXED: Every leg is settled before any error is returned, so no
+            // pool is left flagged as lending or short of its reserves
+            let mut repaid = result.is_ok();
+            let mut disappeared = false;
+            for &(token, _, fee_amount, initial_reserves) in &batch {
+                match self.lending_pools.get_mut(token) {
+                    Some(pool) => {
+                        pool.in_flash_loan = false;
+                        if pool.token_reserves < initial_reserves + fee_amount {
+                            repaid = false;
+                        }
+                    },
+                    None => disappeared = true,
+                }
+            }
             
-            // Verify full repayment with fee
-            if result.is_ok() {
-                if pool.token_reserves < initial_reserves + fee_amount {
-                    return Err("Flash loan not repaid with fee");
+            // Revert the entire batch if any leg fails
+            if !repaid || disappeared {
+                for &(token, _, _, initial_reserves) in &batch {
+                    if let Some(pool) = self.lending_pools.get_mut(token) {
+                        pool.token_reserves = initial_reserves;
+                    }
                 }
-            } else {
-                // If callback failed, we need to rollback
-                pool.token_reserves = initial_reserves;
-                return result;
+                if disappeared {
+                    return Err("Pool disappeared during flash loan");
+                }
+                return result.and(Err("Flash loan not repaid with fee"));
+            }
+            
+            for &(token, amount, fee_amount, _) in &batch {
+                self.events.emit("FlashLoanRepaid", &[
+                    ("token", token.to_string()),
+                    ("amount", amount.to_string()),
+                    ("fee", fee_amount.to_string()),
+                ], self.price_oracle.current_time);
             }
             
             Ok(())
         }
         
+        /// Debt owed by a position once interest since its last accrual is added
+        ///
+        /// Interest is simple within an accrual period and compounds each time
+        /// the position is touched. Debt that would overflow saturates at
+        /// `u64::MAX` instead of panicking, which leaves the position liquidatable.
+        fn accrued_debt(&self, position: &UserPosition) -> u64 {
+            let elapsed = self.price_oracle.current_time.saturating_sub(position.last_accrual_time);
+            let rate = self.lending_pools.get(&position.borrowed_token)
+                .map_or(0, |pool| pool.borrow_rate_bps_per_sec);
+            
+            let debt = position.borrowed_amount as u128;
+            debt.checked_mul(rate as u128)
+                .and_then(|scaled| scaled.checked_mul(elapsed as u128))
+                .map(|scaled| scaled / 10_000)
+                .and_then(|interest| debt.checked_add(interest))
+                .and_then(|total| u64::try_from(total).ok())
+                .unwrap_or(u64::MAX)
+        }
+        
+        /// Add interest accrued since the last touch to a position's debt
+        ///
+        /// Returns the updated debt. Every function that reads or changes a
+        /// position's debt calls this first.
+        pub fn accrue_interest(&mut self, position_id: u64) -> Result<u64, &'static str> {
+            let position = self.positions.get(&position_id).ok_or("Position not found")?;
+            let debt = self.accrued_debt(position);
+            
+            let now = self.price_oracle.current_time;
+            let position = self.positions.get_mut(&position_id).unwrap();
+            position.borrowed_amount = debt;
+            position.last_accrual_time = now;
+            Ok(debt)
+        }
+        
+        /// Open a debt-free position backed by `collateral`
+        pub fn open_position(&mut self, owner: [u8; 32], collateral: HashMap<String, u64>) -> u64 {
+            self.create_multi_collateral_position(owner, collateral, "", 0)
+        }
+        
+        /// Borrow `amount` of `token` from its lending pool against a position
+        ///
+        /// A position borrows a single token at a time. The borrow is rejected
+        /// if it would leave the factor-weighted collateral below the
+        /// liquidation threshold, so a fresh loan is never instantly liquidatable.
+        pub fn borrow(&mut self, position_id: u64, token: &str, amount: u64) -> Result<(), &'static str> {
+            self.pausable.when_not_paused().map_err(|_| "Protocol is paused")?;
+            
+            if amount == 0 {
+                return Err("Borrow amount must be positive");
+            }
+            
+            self.accrue_interest(position_id)?;
+            let mut position = self.positions.get(&position_id).ok_or("Position not found")?.clone();
+            if position.borrowed_amount > 0 && position.borrowed_token != token {
+                return Err("Position already borrows a different token");
+            }
+            
+            let pool = self.lending_pools.get(token).ok_or("Pool not found")?;
+            if pool.token_reserves < amount {
+                return Err("Insufficient liquidity in pool");
+            }
+            
+            position.borrowed_token = token.to_string();
+            position.borrowed_amount = position.borrowed_amount.checked_add(amount).ok_or("Debt overflow")?;
+            self.ensure_healthy(&position)?;
+            
+            self.lending_pools.get_mut(token).unwrap().token_reserves -= amount;
+            self.positions.insert(position_id, position);
+            Ok(())
+        }
+        
+        /// Repay `amount` of a position's debt into the lending pool
+        ///
+        /// Returns the debt still outstanding. Repaying more than is owed is
+        /// rejected rather than silently keeping the excess.
+        pub fn repay(&mut self, position_id: u64, token: &str, amount: u64) -> Result<u64, &'static str> {
+            self.accrue_interest(position_id)?;
+            let position = self.positions.get(&position_id).ok_or("Position not found")?;
+            if position.borrowed_amount == 0 || position.borrowed_token != token {
+                return Err("Position has no debt in this token");
+            }
+            if amount > position.borrowed_amount {
+                return Err("Repay amount exceeds outstanding debt");
+            }
+            
+            let pool = self.lending_pools.get_mut(token).ok_or("Pool not found")?;
+            pool.token_reserves = pool.token_reserves.checked_add(amount).ok_or("Pool reserves overflow")?;
+            
+            let position = self.positions.get_mut(&position_id).unwrap();
+            position.borrowed_amount -= amount;
+            Ok(position.borrowed_amount)
+        }
+        
+        /// Withdraw collateral from a position, as long as it stays healthy
+        pub fn withdraw_collateral(&mut self, position_id: u64, token: &str, amount: u64) -> Result<(), &'static str> {
+            self.accrue_interest(position_id)?;
+            let mut position = self.positions.get(&position_id).ok_or("Position not found")?.clone();
+            let deposited = position.collateral.get_mut(token).ok_or("No such collateral in position")?;
+            *deposited = deposited.checked_sub(amount).ok_or("Insufficient collateral")?;
+            
+            if position.borrowed_amount > 0 {
+                self.ensure_healthy(&position)?;
+            }
+            
+            self.positions.insert(position_id, position);
+            Ok(())
+        }
+        
+        /// Reject a position whose weighted collateral is below the liquidation threshold
+        fn ensure_healthy(&self, position: &UserPosition) -> Result<(), &'static str> {
+            let debt_value = self.debt_value(position)?;
+            let collateral_value = self.risk_adjusted_collateral_value(position)?;
+            if (collateral_value as u128) < debt_value * self.liquidation_threshold as u128 / 100 {
+                return Err("Insufficient collateral for debt");
+            }
+            Ok(())
+        }
+        
         /// Secure liquidation function resistant to flash loan attacks
+        ///
+        /// The whole position is closed. If the seized collateral is worth less
+        /// than the debt being repaid, the shortfall is added to `bad_debt`.
         pub fn liquidate_position(&mut self, position_id: u64, liquidator: [u8; 32]) -> Result<(), &'static str> {
+            self.pausable.when_not_paused().map_err(|_| "Protocol is paused")?;
+            
+            self.accrue_interest(position_id)?;
             let position = match self.positions.get(&position_id) {
                 Some(position) => position.clone(),
                 None => return Err("Position not found"),
             };
             
             // FIXED: Use TWAP from oracle instead of spot price
-            let collateral_token = &position.collateral_token;
-            let borrowed_token = &position.borrowed_token;
+            // Calculate position health against the value of all collateral
+            let collateral_value = self.collateral_value(&position)?;
+            let debt_value = self.debt_value(&position)?;
             
-            // Get TWAP prices over 1 hour
-            let collateral_price = match self.price_oracle.get_twap(collateral_token, 3600) {
-                Some(price) => price,
-                None => return Err("Insufficient price data for collateral token"),
+            // Check if position is undercollateralized
+            let min_collateral_value = debt_value * self.liquidation_threshold as u128 / 100;
+            
+            if self.risk_adjusted_collateral_value(&position)? as u128 >= min_collateral_value {
+                return Err("Position is not liquidatable");
+            }
+            
+            // Calculate liquidation amount (for simplicity, we liquidate the whole position)
+            
+            // Perform liquidation (simplified)
+            // In a real contract, the liquidator would need to repay the debt
+            
+            // Remove the position
+            self.positions.remove(&position_id);
+            
+            // FIXED: A position that fell underwater faster than it could be
+            // liquidated leaves a loss the protocol has to account for
+            let shortfall = debt_value.saturating_sub(collateral_value as u128);
+            if shortfall > 0 {
+                let shortfall = u64::try_from(shortfall).unwrap_or(u64::MAX);
+                self.bad_debt = self.bad_debt.saturating_add(shortfall);
+                self.events.emit("BadDebtRecorded", &[
+                    ("position_id", position_id.to_string()),
+                    ("amount", shortfall.to_string()),
+                ], self.price_oracle.current_time);
+            }
+            
+            // Protocol keeps the bonus
+            
+            Ok(())
+        }
+        
+        /// Liquidate only part of an unhealthy position
+        ///
+        /// The liquidator repays `repay_amount` of the debt and receives
+        /// collateral worth that much plus the liquidation bonus, taken from
+        /// the position's collateral tokens in name order. Returns the amount
+        /// of collateral seized. The position stays open with its remaining
+        /// collateral and debt, and `repay_amount` must be enough to make it
+        /// healthy again.
+        pub fn liquidate_partial(&mut self, position_id: u64, repay_amount: u64) -> Result<u64, &'static str> {
+            self.pausable.when_not_paused().map_err(|_| "Protocol is paused")?;
+            
+            self.accrue_interest(position_id)?;
+            let position = match self.positions.get(&position_id) {
+                Some(position) => position.clone(),
+                None => return Err("Position not found"),
             };
             
-            let borrowed_price = match self.price_oracle.get_twap(borrowed_token, 3600) {
+            if repay_amount == 0 {
+                return Err("Repay amount must be positive");
+            }
+            
+            // FIXED: Repaying more than is owed would leave negative debt
+            if repay_amount > position.borrowed_amount {
+                return Err("Repay amount exceeds outstanding debt");
+            }
+            
+            let borrowed_price = match self.price_oracle.get_twap(&position.borrowed_token, 3600) {
                 Some(price) => price,
                 None => return Err("Insufficient price data for borrowed token"),
             };
             
-            // Calculate position health
-            let collateral_value = position.collateral_amount * collateral_price / 1_000_000;
-            let debt_value = position.borrowed_amount * borrowed_price / 1_000_000;
+            // Only unhealthy positions can be liquidated, even partially
+            let collateral_value = self.risk_adjusted_collateral_value(&position)?;
+            let debt_value = self.debt_value(&position)?;
+            if collateral_value as u128 >= debt_value * self.liquidation_threshold as u128 / 100 {
+                return Err("Position is not liquidatable");
+            }
             
-            // FIXED: Additional check for price deviation
-            // Get spot prices to check for manipulation
-            let spot_collateral_price = match self.price_oracle.get_spot_price(collateral_token) {
-                Some(price) => price,
-                None => return Err("No spot price for collateral token"),
-            };
+            // Value owed to the liquidator: the repaid debt plus the bonus
+            let mut remaining_value = repay_amount as u128 * borrowed_price as u128 / 1_000_000
+                * (100 + self.liquidation_bonus as u128) / 100;
             
-            // Check for significant deviation between TWAP and spot
-            const MAX_DEVIATION_PERCENT: u64 = 10; // 10%
-            let max_deviation = collateral_price * MAX_DEVIATION_PERCENT / 100;
+            let mut tokens: Vec<&String> = position.collateral.keys().collect();
+            tokens.sort();
             
-            if spot_collateral_price > collateral_price && spot_collateral_price - collateral_price > max_deviation {
-                return Err("Suspicious price movement detected, liquidation blocked");
+            let mut seizures = Vec::new();
+            for token in tokens {
+                if remaining_value == 0 {
+                    break;
+                }
+                
+                let amount = position.collateral[token];
+                if amount == 0 {
+                    continue;
+                }
+                
+                // collateral_value() already checked every funded token has a TWAP
+                let price = self.price_oracle.get_twap(token, 3600).unwrap_or(0) as u128;
+                if price == 0 {
+                    continue;
+                }
+                
+                let token_value = amount as u128 * price / 1_000_000;
+                let seized = if remaining_value >= token_value {
+                    remaining_value -= token_value;
+                    amount
+                } else {
+                    // Round up so the liquidator is never short-changed
+                    let seized = (remaining_value * 1_000_000).div_ceil(price).min(amount as u128) as u64;
+                    remaining_value = 0;
+                    seized
+                };
+                seizures.push((token.clone(), seized));
             }
             
-            if collateral_price > spot_collateral_price && collateral_price - spot_collateral_price > max_deviation {
-                return Err("Suspicious price movement detected, liquidation blocked");
+            if remaining_value > 0 {
+                return Err("Insufficient collateral to cover liquidation");
             }
             
-            // Check if position is undercollateralized
-            let min_collateral_value = debt_value * self.liquidation_threshold / 100;
+            let mut liquidated = position;
+            let mut total_seized: u64 = 0;
+            for (token, seized) in seizures {
+                *liquidated.collateral.get_mut(&token).unwrap() -= seized;
+                total_seized = total_seized.saturating_add(seized);
+            }
+            liquidated.borrowed_amount -= repay_amount;
             
-            if collateral_value >= min_collateral_value {
+            // FIXED: A partial liquidation must leave the position healthy, not merely smaller
+            self.ensure_healthy(&liquidated).map_err(|_| "Repay amount does not restore position health")?;
+            self.positions.insert(position_id, liquidated);
+            
+            Ok(total_seized)
+        }
+        
+        /// Put an unhealthy position's collateral up for Dutch auction
+        ///
+        /// Returns the starting price. An expired auction may be restarted at
+        /// current prices; a running one may not.
+        pub fn start_liquidation_auction(&mut self, position_id: u64) -> Result<u64, &'static str> {
+            self.pausable.when_not_paused().map_err(|_| "Protocol is paused")?;
+            
+            self.accrue_interest(position_id)?;
+            let position = self.positions.get(&position_id).ok_or("Position not found")?;
+            let now = self.price_oracle.current_time;
+            if self.liquidation_auctions.get(&position_id).is_some_and(|auction| auction.price_at(now).is_some()) {
+                return Err("Auction already running");
+            }
+            
+            let collateral_value = self.collateral_value(position)?;
+            let debt_value = self.debt_value(position)?;
+            if self.risk_adjusted_collateral_value(position)? as u128 >= debt_value * self.liquidation_threshold as u128 / 100 {
                 return Err("Position is not liquidatable");
             }
             
-            // Calculate liquidation amount (for simplicity, we liquidate the whole position)
-            let liquidation_bonus_amount = position.collateral_amount * self.liquidation_bonus / 100;
-            let liquidator_collateral = position.collateral_amount - liquidation_bonus_amount;
+            // Percentages of the value are taken in u128 so a large position can't overflow
+            let start_price = u64::try_from(collateral_value as u128 * AUCTION_START_PERCENT as u128 / 100)
+                .map_err(|_| "Auction price overflow")?;
+            let end_price = (collateral_value as u128 * AUCTION_END_PERCENT as u128 / 100) as u64;
+            self.liquidation_auctions.insert(position_id, LiquidationAuction {
+                start_time: now,
+                start_price,
+                end_price,
+            });
+            Ok(start_price)
+        }
+        
+        /// Buy a position's auctioned collateral at the current asking price
+        ///
+        /// `bid` is the most the liquidator will pay; the sale clears at the
+        /// asking price, which is returned. Debt the clearing price does not
+        /// cover is recorded as bad debt.
+        pub fn bid_liquidation(&mut self, position_id: u64, bid: u64) -> Result<u64, &'static str> {
+            self.pausable.when_not_paused().map_err(|_| "Protocol is paused")?;
+            
+            // FIXED: Price the auction on the protocol's clock; a bidder-supplied
+            // time would let them skip straight to the floor price
+            let now = self.price_oracle.current_time;
+            let auction = self.liquidation_auctions.get(&position_id).ok_or("No auction for position")?;
+            let price = auction.price_at(now).ok_or("Auction expired")?;
+            if bid < price {
+                return Err("Bid below current auction price");
+            }
             
-            // Perform liquidation (simplified)
-            // In a real contract, the liquidator would need to repay the debt
+            let position = self.positions.get(&position_id).ok_or("Position not found")?;
+            let debt_value = self.debt_value(position)?;
             
-            // Remove the position
+            self.liquidation_auctions.remove(&position_id);
             self.positions.remove(&position_id);
             
-            // Protocol keeps the bonus
+            let shortfall = debt_value.saturating_sub(price as u128);
+            if shortfall > 0 {
+                let shortfall = u64::try_from(shortfall).unwrap_or(u64::MAX);
+                self.bad_debt = self.bad_debt.saturating_add(shortfall);
+                self.events.emit("BadDebtRecorded", &[
+                    ("position_id", position_id.to_string()),
+                    ("amount", shortfall.to_string()),
+                ], now);
+            }
             
-            Ok(())
+            Ok(price)
+        }
+        
+        /// Risk-adjusted collateral value as a share of debt value, in basis points
+        ///
+        /// Uses the same TWAP pricing and collateral factors as liquidation
+        /// without its side effects.
+        /// A position becomes liquidatable below `liquidation_threshold * 100`
+        /// (11_000 by default). A position with no debt returns `u64::MAX`.
+        pub fn health_factor(&self, position_id: u64) -> Result<u64, &'static str> {
+            let position = self.positions.get(&position_id).ok_or("Position not found")?;
+            if position.borrowed_amount == 0 {
+                return Ok(u64::MAX);
+            }
+            
+            let collateral_value = self.risk_adjusted_collateral_value(position)?;
+            let debt_value = self.debt_value(position)?;
+            if debt_value == 0 {
+                return Ok(u64::MAX);
+            }
+            
+            let ratio = collateral_value as u128 * 10_000 / debt_value;
+            Ok(u64::try_from(ratio).unwrap_or(u64::MAX))
+        }
+        
+        /// TWAP value of a position's debt, including unaccrued interest
+        fn debt_value(&self, position: &UserPosition) -> Result<u128, &'static str> {
+            // Get TWAP prices over 1 hour
+            let borrowed_price = match self.price_oracle.get_twap(&position.borrowed_token, 3600) {
+                Some(price) => price,
+                None => return Err("Insufficient price data for borrowed token"),
+            };
+            Ok(self.accrued_debt(position) as u128 * borrowed_price as u128 / 1_000_000)
+        }
+        
+        /// Total TWAP value of a position's collateral
+        ///
+        /// Zero-balance entries contribute nothing. Every other collateral token
+        /// must have TWAP and spot prices that agree, otherwise the whole
+        /// valuation fails rather than silently skipping that asset.
+        fn collateral_value(&self, position: &UserPosition) -> Result<u64, &'static str> {
+            self.weighted_collateral_value(position, |_| Ok(100))
+        }
+        
+        /// Collateral value with each token discounted by its collateral factor
+        ///
+        /// This is what position health is measured against. A funded token
+        /// with no configured factor is rejected rather than counted as zero,
+        /// so a listing mistake surfaces instead of quietly liquidating users.
+        fn risk_adjusted_collateral_value(&self, position: &UserPosition) -> Result<u64, &'static str> {
+            self.weighted_collateral_value(position, |token| {
+                self.collateral_factors.get(token).copied().ok_or("No collateral factor for collateral token")
+            })
+        }
+        
+        fn weighted_collateral_value(
+            &self,
+            position: &UserPosition,
+            factor_percent: impl Fn(&str) -> Result<u64, &'static str>,
+        ) -> Result<u64, &'static str> {
+            // Maximum deviation between TWAP and spot before we suspect manipulation
+            const MAX_DEVIATION_PERCENT: u64 = 10; // 10%
+            
+            let mut total: u64 = 0;
+            
+            for (token, &amount) in &position.collateral {
+                if amount == 0 {
+                    continue;
+                }
+                let factor_percent = factor_percent(token)?;
+                
+                let twap_price = match self.price_oracle.get_twap(token, 3600) {
+                    Some(price) => price,
+                    None => return Err("Insufficient price data for collateral token"),
+                };
+                
+                // FIXED: Additional check for price deviation
+                // Get spot prices to check for manipulation
+                let spot_price = match self.price_oracle.get_spot_price(token) {
+                    Some(price) => price,
+                    None => return Err("No spot price for collateral token"),
+                };
+                
+                let max_deviation = twap_price * MAX_DEVIATION_PERCENT / 100;
+                if spot_price.abs_diff(twap_price) > max_deviation {
+                    return Err("Suspicious price movement detected, liquidation blocked");
+                }
+                
+                let value = amount as u128 * twap_price as u128 / 1_000_000 * factor_percent as u128 / 100;
+                total = u64::try_from(value).ok()
+                    .and_then(|value| total.checked_add(value))
+                    .ok_or("Collateral value overflow")?;
+            }
+            
+            Ok(total)
         }
         
         /// Helper to update oracle prices (for testing)
-        pub fn update_oracle_price(&mut self, token: &str, price: u64) {
-            self.price_oracle.update_price(token, price);
+        pub fn update_oracle_price(&mut self, token: &str, price: u64) -> Result<(), &'static str> {
+            self.price_oracle.update_price(token, price)
         }
         
         /// Helper to advance time in the oracle (for testing)
@@ -573,14 +1165,26 @@ pub mod secure {
             self.price_oracle.advance_time(seconds);
         }
         
-        /// Create a position (for testing)
+        /// Take the current time from a shared `MockBlockchain` instead of
+        /// tracking it locally, refusing to move the clock backwards
+        pub fn sync_clock(&mut self, chain: &MockBlockchain) -> Result<(), crate::Error> {
+            chain.sync_clock(&mut self.price_oracle.current_time)
+        }
+        
+        /// Create a single-collateral position (for testing)
         pub fn create_position(&mut self, owner: [u8; 32], collateral_token: &str, collateral_amount: u64, borrowed_token: &str, borrowed_amount: u64) -> u64 {
+            let collateral = HashMap::from([(collateral_token.to_string(), collateral_amount)]);
+            self.create_multi_collateral_position(owner, collateral, borrowed_token, borrowed_amount)
+        }
+        
+        /// Create a position backed by several collateral tokens (for testing)
+        pub fn create_multi_collateral_position(&mut self, owner: [u8; 32], collateral: HashMap<String, u64>, borrowed_token: &str, borrowed_amount: u64) -> u64 {
             let position = UserPosition {
                 owner,
-                collateral_token: collateral_token.to_string(),
-                collateral_amount,
+                collateral,
                 borrowed_token: borrowed_token.to_string(),
                 borrowed_amount,
+                last_accrual_time: self.price_oracle.current_time,
             };
             
             let position_id = self.next_position_id;
@@ -596,6 +1200,7 @@ pub mod secure {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
     
     #[test]
     fn test_vulnerable_flash_loan_attack() {
@@ -628,16 +1233,14 @@ mod tests {
         // Simulate a flash loan attack
         let result = protocol.flash_loan("USDC", 900_000, |protocol| {
             // Use the flash-loaned USDC to manipulate the TOKEN price in the DEX
-            let dex = protocol.dex_pools.get_mut("TOKEN_USDC").unwrap();
-            
             // Manipulate price by doing a large swap
-            dex.swap("USDC", 900_000);
+            protocol.dex_pools.get_mut("TOKEN_USDC").unwrap().swap("USDC", 900_000);
             
             // Now try to liquidate the position when price is manipulated
             let _ = protocol.liquidate_position(position_id, attacker);
             
             // Swap back to repay the flash loan
-            dex.swap("TOKEN", 900_000);
+            protocol.dex_pools.get_mut("TOKEN_USDC").unwrap().swap("TOKEN", 900_000);
             
             Ok(())
         });
@@ -649,14 +1252,8 @@ mod tests {
     
     #[test]
     fn test_secure_flash_loan_protection() {
-        let mut protocol = secure::LendingProtocol::new();
-        
-        // Set up lending pools with fees
-        protocol.lending_pools.insert("USDC".to_string(), secure::LendingPool {
-            token_reserves: 1_000_000,
-            name: "USDC Pool".to_string(),
-            flash_loan_fee: 30, // 0.3% fee
-        });
+        // Set up a lending pool
+        let mut protocol = secure_protocol_with_pools(&["USDC"]);
         
         // Set up a DEX pool for price discovery
         protocol.dex_pools.insert("TOKEN_USDC".to_string(), secure::DexPool {
@@ -667,13 +1264,14 @@ mod tests {
         });
         
         // Seed the oracle with historical prices
-        for i in 0..24 {
-            protocol.update_oracle_price("TOKEN", 1_000_000); // Price of 1.0
-            protocol.update_oracle_price("USDC", 1_000_000);  // Price of 1.0
+        for _ in 0..24 {
+            protocol.update_oracle_price("TOKEN", 1_000_000).unwrap(); // Price of 1.0
+            protocol.update_oracle_price("USDC", 1_000_000).unwrap();  // Price of 1.0
             protocol.advance_oracle_time(3600); // Advance 1 hour
         }
         
         // Create a position that's well-collateralized at current prices
+        protocol.set_collateral_factor("TOKEN", 100).unwrap();
         let user = [1u8; 32];
         let position_id = protocol.create_position(user, "TOKEN", 100_000, "USDC", 80_000);
         
@@ -682,16 +1280,15 @@ mod tests {
         let result = protocol.liquidate_position(position_id, attacker);
         assert!(result.is_err());
         
-        // Simulate a flash loan attack attempt
-        let result = protocol.flash_loan("USDC", 900_000, |protocol| {
+        // Simulate a flash loan attack attempt with the largest loan the pool allows
+        let loan = 500_000;
+        let result = protocol.flash_loan("USDC", loan, |protocol| {
             // Use the flash-loaned USDC to manipulate the TOKEN price in the DEX
-            let dex = protocol.dex_pools.get_mut("TOKEN_USDC").unwrap();
-            
             // Manipulate price by doing a large swap
-            dex.swap("USDC", 900_000);
+            let token_bought = protocol.dex_pools.get_mut("TOKEN_USDC").unwrap().swap("USDC", loan);
             
             // Update the oracle's spot price
-            protocol.update_oracle_price("TOKEN", 500_000); // Price drops to 0.5
+            protocol.update_oracle_price("TOKEN", 500_000).unwrap(); // Price drops to 0.5
             
             // Try to liquidate the position when price is manipulated
             let liquidation_result = protocol.liquidate_position(position_id, attacker);
@@ -699,9 +1296,11 @@ mod tests {
             // This will fail because TWAP is still at 1.0
             assert!(liquidation_result.is_err());
             
-            // Swap back to repay the flash loan with fee
-            let usdc_needed = 900_000 + (900_000 * 30 / 10000);
-            dex.swap("TOKEN", usdc_needed);
+            // Swap back and repay the flash loan; the fee and any rounding
+            // lost on the round trip come out of the attacker's own funds
+            protocol.dex_pools.get_mut("TOKEN_USDC").unwrap().swap("TOKEN", token_bought);
+            let usdc_needed = loan + secure::compute_flash_fee(loan, 1_000_000);
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += usdc_needed;
             
             Ok(())
         });
@@ -710,4 +1309,654 @@ mod tests {
         assert!(result.is_ok());
         assert!(protocol.positions.contains_key(&position_id)); // Position still exists
     }
+    
+    #[test]
+    fn test_cumulative_price_snapshots_give_time_weighted_mean() {
+        let mut oracle = secure::PriceOracle::new();
+        oracle.update_price("SOL", 100).unwrap();
+        oracle.advance_time(30);
+        let (start_cumulative, start_ts) = oracle.cumulative_price("SOL").unwrap();
+        
+        // 100 for 20s, 160 for 50s, 130 for 30s
+        oracle.advance_time(20);
+        oracle.update_price("SOL", 160).unwrap();
+        oracle.advance_time(50);
+        oracle.update_price("SOL", 130).unwrap();
+        oracle.advance_time(30);
+        let (now_cumulative, now_ts) = oracle.cumulative_price("SOL").unwrap();
+        
+        let expected = (100 * 20 + 160 * 50 + 130 * 30) / 100;
+        assert_eq!(oracle.twap_between(start_cumulative, start_ts, now_cumulative, now_ts), expected);
+        assert_eq!(oracle.get_twap("SOL", 100), Some(expected));
+        
+        // Snapshots don't write to the accumulator
+        assert_eq!(oracle.token_prices["SOL"].last_cumulative_ts, 100);
+        assert_eq!(oracle.twap_between(now_cumulative, now_ts, now_cumulative, now_ts), 0);
+    }
+    
+    #[test]
+    fn test_oracle_rejects_time_moving_backwards() {
+        let mut oracle = secure::PriceOracle::new();
+        oracle.advance_time(100);
+        oracle.update_price("SOL", 100).unwrap();
+        
+        // A clock set behind the last update must not underflow the accumulator
+        oracle.current_time = 50;
+        assert_eq!(oracle.update_price("SOL", 160), Err("Oracle time moved backwards"));
+        assert_eq!(oracle.cumulative_price("SOL"), None);
+        assert_eq!(oracle.get_spot_price("SOL"), Some(100));
+        
+        oracle.current_time = 100;
+        assert_eq!(oracle.cumulative_price("SOL"), Some((0, 100)));
+    }
+    
+    #[test]
+    fn test_dex_pool_large_reserves_do_not_overflow() {
+        let half_max = u64::MAX / 2;
+        let mut pool = secure::DexPool {
+            token_a: "TOKEN".to_string(),
+            token_b: "USDC".to_string(),
+            token_a_reserves: half_max,
+            token_b_reserves: half_max,
+        };
+        
+        // 1:1 pool, so the scaled price is exactly 1.0
+        assert_eq!(pool.get_price("TOKEN", "USDC"), 1_000_000);
+        
+        // Plain u64 multiplication of the reserves would overflow here
+        let amount_out = pool.swap("TOKEN", 1_000_000);
+        assert!(amount_out > 0 && amount_out <= 1_000_000);
+        assert_eq!(pool.token_a_reserves, half_max + 1_000_000);
+        assert_eq!(pool.token_b_reserves, half_max - amount_out);
+        
+        // Prices that don't fit in a u64 saturate instead of wrapping
+        let skewed = secure::DexPool {
+            token_a: "TOKEN".to_string(),
+            token_b: "USDC".to_string(),
+            token_a_reserves: 1,
+            token_b_reserves: half_max,
+        };
+        assert_eq!(skewed.get_price("TOKEN", "USDC"), u64::MAX);
+        
+        // A swap that would push a reserve past u64::MAX is rejected
+        let before = (pool.token_a_reserves, pool.token_b_reserves);
+        assert_eq!(pool.swap("TOKEN", u64::MAX), 0);
+        assert_eq!((pool.token_a_reserves, pool.token_b_reserves), before);
+    }
+    
+    /// Secure protocol with a 1.0 TWAP and full collateral factor for
+    /// `tokens` over the last day
+    fn seeded_secure_protocol(tokens: &[&str]) -> secure::LendingProtocol {
+        let mut protocol = secure::LendingProtocol::new();
+        for token in tokens {
+            protocol.set_collateral_factor(token, 100).unwrap();
+        }
+        for _ in 0..24 {
+            for token in tokens {
+                protocol.update_oracle_price(token, 1_000_000).unwrap();
+            }
+            protocol.advance_oracle_time(3600);
+        }
+        protocol
+    }
+    
+    #[test]
+    fn test_multi_collateral_health_uses_total_value() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "OTHER", "USDC"]);
+        let user = [1u8; 32];
+        let liquidator = [2u8; 32];
+        
+        // 60k + 40k collateral against 80k debt: 100k >= 88k required, but
+        // either asset alone would be below the 110% threshold
+        let collateral = HashMap::from([
+            ("TOKEN".to_string(), 60_000),
+            ("OTHER".to_string(), 40_000),
+        ]);
+        let position_id = protocol.create_multi_collateral_position(user, collateral, "USDC", 80_000);
+        assert_eq!(protocol.liquidate_position(position_id, liquidator), Err("Position is not liquidatable"));
+        
+        let single_asset = protocol.create_position(user, "TOKEN", 60_000, "USDC", 80_000);
+        assert!(protocol.liquidate_position(single_asset, liquidator).is_ok());
+        
+        // The vulnerable protocol sums collateral too (at 1.0 default prices)
+        let mut vulnerable_protocol = vulnerable::LendingProtocol::new();
+        let collateral = HashMap::from([
+            ("TOKEN".to_string(), 60_000),
+            ("OTHER".to_string(), 40_000),
+        ]);
+        let position_id = vulnerable_protocol.create_multi_collateral_position(user, collateral, "USDC", 80_000);
+        assert_eq!(vulnerable_protocol.liquidate_position(position_id, liquidator), Err("Position is not liquidatable"));
+    }
+    
+    #[test]
+    fn test_multi_collateral_edge_cases() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        let user = [1u8; 32];
+        let liquidator = [2u8; 32];
+        
+        // A zero-balance entry is ignored rather than priced
+        let collateral = HashMap::from([
+            ("TOKEN".to_string(), 100_000),
+            ("EMPTY".to_string(), 0),
+        ]);
+        let position_id = protocol.create_multi_collateral_position(user, collateral, "USDC", 80_000);
+        assert_eq!(protocol.liquidate_position(position_id, liquidator), Err("Position is not liquidatable"));
+        
+        // Any funded collateral without price data fails the whole valuation
+        let collateral = HashMap::from([
+            ("TOKEN".to_string(), 100_000),
+            ("UNPRICED".to_string(), 1),
+        ]);
+        let position_id = protocol.create_multi_collateral_position(user, collateral, "USDC", 80_000);
+        assert_eq!(
+            protocol.liquidate_position(position_id, liquidator),
+            Err("Insufficient price data for collateral token")
+        );
+        assert!(protocol.positions.contains_key(&position_id));
+    }
+    
+    #[test]
+    fn test_partial_liquidation_restores_health() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        let user = [1u8; 32];
+        let liquidator = [2u8; 32];
+        
+        // 100k collateral against 92k debt needs 101.2k: marginally unhealthy
+        let position_id = protocol.create_position(user, "TOKEN", 100_000, "USDC", 92_000);
+        
+        // Repaying everything owed and more is rejected
+        assert_eq!(protocol.liquidate_partial(position_id, 92_001), Err("Repay amount exceeds outstanding debt"));
+        
+        // So is a repayment too small to make the position healthy (98.95k < 100.1k)
+        assert_eq!(protocol.liquidate_partial(position_id, 1_000), Err("Repay amount does not restore position health"));
+        assert_eq!(protocol.positions[&position_id].collateral["TOKEN"], 100_000);
+        assert_eq!(protocol.positions[&position_id].borrowed_amount, 92_000);
+        
+        // Repaying 25k seizes 25k plus the 5% bonus
+        let seized = protocol.liquidate_partial(position_id, 25_000).unwrap();
+        assert_eq!(seized, 26_250);
+        
+        // The position stays open and is healthy again (73.75k >= 73.7k)
+        let position = &protocol.positions[&position_id];
+        assert_eq!(position.collateral["TOKEN"], 73_750);
+        assert_eq!(position.borrowed_amount, 67_000);
+        assert_eq!(protocol.liquidate_position(position_id, liquidator), Err("Position is not liquidatable"));
+        assert_eq!(protocol.liquidate_partial(position_id, 1_000), Err("Position is not liquidatable"));
+    }
+    
+    #[test]
+    fn test_secure_flash_loan_emits_repayment_event() {
+        let mut protocol = secure_protocol_with_pools(&["USDC"]);
+        
+        // Borrower repays principal plus the 0.05% small-loan fee
+        protocol.flash_loan("USDC", 10_000, |protocol| {
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += 10_005;
+            Ok(())
+        }).unwrap();
+        
+        // A failed loan is rolled back and emits nothing
+        let _ = protocol.flash_loan("USDC", 10_000, |_| Err("callback failed"));
+        
+        let repayments = protocol.events.by_kind("FlashLoanRepaid");
+        assert_eq!(repayments.len(), 1);
+        assert_eq!(repayments[0].field("amount"), Some("10000"));
+        assert_eq!(repayments[0].field("fee"), Some("5"));
+    }
+    
+    #[test]
+    fn test_paused_protocol_rejects_new_flash_loans() {
+        use crate::utils::rbac::Role;
+        
+        let pauser = [9u8; 32];
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        protocol.lending_pools.insert("USDC".to_string(), lending_pool("USDC"));
+        
+        assert_eq!(protocol.pause([1u8; 32]), Err("Only pauser can pause"));
+        protocol.roles.grant_role(pauser, Role::Pauser);
+        assert_eq!(protocol.unpause(pauser), Err("Protocol is not paused"));
+        
+        // Pausing mid-loan still lets the in-flight repayment complete
+        protocol.flash_loan("USDC", 10_000, |protocol| {
+            protocol.pause(pauser)?;
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += 10_005;
+            Ok(())
+        }).unwrap();
+        assert_eq!(protocol.lending_pools["USDC"].token_reserves, 1_000_005);
+        
+        // New loans and liquidations are rejected while paused
+        assert_eq!(protocol.flash_loan("USDC", 10_000, |_| Ok(())), Err("Protocol is paused"));
+        assert_eq!(protocol.flash_loan_multi(&[("USDC".to_string(), 10_000)], |_| Ok(())), Err("Protocol is paused"));
+        let position_id = protocol.create_position([1u8; 32], "TOKEN", 1, "USDC", 80_000);
+        assert_eq!(protocol.liquidate_position(position_id, pauser), Err("Protocol is paused"));
+        assert_eq!(protocol.liquidate_partial(position_id, 1_000), Err("Protocol is paused"));
+        
+        protocol.unpause(pauser).unwrap();
+        assert!(protocol.liquidate_position(position_id, pauser).is_ok());
+    }
+    
+    #[test]
+    fn test_severe_price_drop_records_bad_debt() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        let user = [1u8; 32];
+        let liquidator = [2u8; 32];
+        
+        // 100k collateral against 80k debt is healthy at a 1.0 price
+        let position_id = protocol.create_position(user, "TOKEN", 100_000, "USDC", 80_000);
+        assert_eq!(protocol.liquidate_position(position_id, liquidator), Err("Position is not liquidatable"));
+        
+        // TOKEN crashes to 0.3 and stays there for longer than the TWAP window
+        protocol.update_oracle_price("TOKEN", 300_000).unwrap();
+        protocol.update_oracle_price("USDC", 1_000_000).unwrap();
+        protocol.advance_oracle_time(3601);
+        protocol.update_oracle_price("TOKEN", 300_000).unwrap();
+        protocol.update_oracle_price("USDC", 1_000_000).unwrap();
+        
+        // 30k of collateral cannot cover 80k of debt
+        assert!(protocol.liquidate_position(position_id, liquidator).is_ok());
+        assert!(!protocol.positions.contains_key(&position_id));
+        assert_eq!(protocol.total_bad_debt(), 50_000);
+        
+        let recorded = protocol.events.by_kind("BadDebtRecorded");
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].field("amount"), Some("50000"));
+        
+        // A merely unhealthy position is covered in full and adds nothing
+        let covered = protocol.create_position(user, "TOKEN", 300_000, "USDC", 85_000);
+        assert!(protocol.liquidate_position(covered, liquidator).is_ok());
+        assert_eq!(protocol.total_bad_debt(), 50_000);
+    }
+    
+    #[test]
+    fn test_liquidation_auction_price_decays_until_expiry() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        let user = [1u8; 32];
+        
+        // 100k of collateral against 100k of debt is below the 110% threshold
+        let position_id = protocol.create_position(user, "TOKEN", 100_000, "USDC", 100_000);
+        let healthy = protocol.create_position(user, "TOKEN", 150_000, "USDC", 100_000);
+        assert_eq!(protocol.start_liquidation_auction(healthy), Err("Position is not liquidatable"));
+        
+        let start = protocol.price_oracle.current_time;
+        assert_eq!(protocol.start_liquidation_auction(position_id), Ok(120_000));
+        assert_eq!(protocol.start_liquidation_auction(position_id), Err("Auction already running"));
+        
+        // The asking price falls from 120% to 80% of the collateral value over the hour
+        let auction = &protocol.liquidation_auctions[&position_id];
+        let prices: Vec<u64> = [0, 900, 1800, 3599].iter().map(|&t| auction.price_at(start + t).unwrap()).collect();
+        assert_eq!(prices[..3], [120_000, 110_000, 100_000]);
+        assert!(prices.windows(2).all(|pair| pair[0] > pair[1]));
+        assert_eq!(auction.price_at(start + secure::LIQUIDATION_AUCTION_DURATION), None);
+        
+        // A bid under the current price is rejected; a higher bid clears at that price
+        protocol.advance_oracle_time(900);
+        assert_eq!(protocol.bid_liquidation(position_id, 109_999), Err("Bid below current auction price"));
+        protocol.advance_oracle_time(900);
+        assert_eq!(protocol.bid_liquidation(position_id, 109_999), Ok(100_000));
+        assert!(!protocol.positions.contains_key(&position_id));
+        assert_eq!(protocol.total_bad_debt(), 0);
+    }
+    
+    #[test]
+    fn test_liquidation_auction_prices_large_positions_without_overflow() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        
+        // 120% of this collateral no longer fits in a u64, 80% of it still does
+        let huge = u64::MAX / 100 * 90;
+        let position_id = protocol.create_position([1u8; 32], "TOKEN", huge, "USDC", huge);
+        assert_eq!(protocol.start_liquidation_auction(position_id), Err("Auction price overflow"));
+        
+        let position_id = protocol.create_position([1u8; 32], "TOKEN", u64::MAX / 2, "USDC", u64::MAX / 2);
+        assert_eq!(protocol.start_liquidation_auction(position_id), Ok(((u64::MAX / 2) as u128 * 120 / 100) as u64));
+    }
+    
+    #[test]
+    fn test_expired_liquidation_auction_rejects_bids_and_can_restart() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        let position_id = protocol.create_position([1u8; 32], "TOKEN", 100_000, "USDC", 100_000);
+        
+        protocol.start_liquidation_auction(position_id).unwrap();
+        protocol.advance_oracle_time(secure::LIQUIDATION_AUCTION_DURATION);
+        assert_eq!(protocol.bid_liquidation(position_id, u64::MAX), Err("Auction expired"));
+        
+        // Once expired, the auction restarts from the current time
+        protocol.update_oracle_price("TOKEN", 1_000_000).unwrap();
+        protocol.update_oracle_price("USDC", 1_000_000).unwrap();
+        assert_eq!(protocol.start_liquidation_auction(position_id), Ok(120_000));
+        
+        // Clearing below the debt leaves the difference as bad debt
+        protocol.advance_oracle_time(3599);
+        assert_eq!(protocol.bid_liquidation(position_id, 90_000), Ok(80_012));
+        assert_eq!(protocol.total_bad_debt(), 19_988);
+    }
+    
+    #[test]
+    fn test_collateral_factor_weights_position_health() {
+        let mut protocol = seeded_secure_protocol(&["STABLE", "VOLATILE", "USDC"]);
+        protocol.set_collateral_factor("STABLE", 90).unwrap();
+        protocol.set_collateral_factor("VOLATILE", 50).unwrap();
+        let user = [1u8; 32];
+        
+        // Same 100k nominal collateral against the same 60k debt
+        let stable = protocol.create_position(user, "STABLE", 100_000, "USDC", 60_000);
+        let volatile = protocol.create_position(user, "VOLATILE", 100_000, "USDC", 60_000);
+        assert_eq!(protocol.health_factor(stable), Ok(15_000));
+        assert_eq!(protocol.health_factor(volatile), Ok(8_333));
+        
+        // Only the volatile position falls below the 110% threshold
+        assert_eq!(protocol.liquidate_position(stable, [2u8; 32]), Err("Position is not liquidatable"));
+        assert_eq!(protocol.liquidate_position(volatile, [2u8; 32]), Ok(()));
+        assert_eq!(protocol.total_bad_debt(), 0);
+        
+        assert_eq!(protocol.set_collateral_factor("STABLE", 101), Err("Collateral factor cannot exceed 100%"));
+    }
+    
+    #[test]
+    fn test_collateral_without_factor_is_rejected() {
+        let mut protocol = seeded_secure_protocol(&["USDC"]);
+        for _ in 0..24 {
+            protocol.update_oracle_price("UNLISTED", 1_000_000).unwrap();
+            protocol.advance_oracle_time(3600);
+        }
+        let position_id = protocol.create_position([1u8; 32], "UNLISTED", 100_000, "USDC", 10_000);
+        
+        assert_eq!(protocol.health_factor(position_id), Err("No collateral factor for collateral token"));
+        assert_eq!(protocol.liquidate_position(position_id, [2u8; 32]), Err("No collateral factor for collateral token"));
+    }
+    
+    #[test]
+    fn test_borrow_is_limited_by_weighted_collateral() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        protocol.set_collateral_factor("TOKEN", 55).unwrap();
+        protocol.lending_pools.insert("USDC".to_string(), lending_pool("USDC"));
+        
+        // 110k at a 55% factor supports 60.5k weighted, so at most 55k of debt
+        let collateral = HashMap::from([("TOKEN".to_string(), 110_000)]);
+        let position_id = protocol.open_position([1u8; 32], collateral);
+        assert_eq!(protocol.borrow(position_id, "USDC", 55_001), Err("Insufficient collateral for debt"));
+        assert_eq!(protocol.lending_pools["USDC"].token_reserves, 1_000_000);
+        
+        protocol.borrow(position_id, "USDC", 50_000).unwrap();
+        assert_eq!(protocol.borrow(position_id, "USDC", 5_001), Err("Insufficient collateral for debt"));
+        protocol.borrow(position_id, "USDC", 5_000).unwrap();
+        assert_eq!(protocol.positions[&position_id].borrowed_amount, 55_000);
+        assert_eq!(protocol.lending_pools["USDC"].token_reserves, 945_000);
+        assert_eq!(protocol.borrow(position_id, "TOKEN", 1), Err("Position already borrows a different token"));
+    }
+    
+    #[test]
+    fn test_repay_reduces_debt_and_frees_collateral() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        protocol.lending_pools.insert("USDC".to_string(), lending_pool("USDC"));
+        let collateral = HashMap::from([("TOKEN".to_string(), 110_000)]);
+        let position_id = protocol.open_position([1u8; 32], collateral);
+        protocol.borrow(position_id, "USDC", 100_000).unwrap();
+        
+        // Fully borrowed: none of the collateral can leave
+        assert_eq!(protocol.withdraw_collateral(position_id, "TOKEN", 1), Err("Insufficient collateral for debt"));
+        
+        assert_eq!(protocol.repay(position_id, "USDC", 100_001), Err("Repay amount exceeds outstanding debt"));
+        assert_eq!(protocol.repay(position_id, "TOKEN", 1), Err("Position has no debt in this token"));
+        assert_eq!(protocol.repay(position_id, "USDC", 50_000), Ok(50_000));
+        assert_eq!(protocol.lending_pools["USDC"].token_reserves, 950_000);
+        
+        // Half the debt repaid: 55k of collateral still covers 110% of 50k
+        assert_eq!(protocol.withdraw_collateral(position_id, "TOKEN", 55_001), Err("Insufficient collateral for debt"));
+        protocol.withdraw_collateral(position_id, "TOKEN", 55_000).unwrap();
+        
+        assert_eq!(protocol.repay(position_id, "USDC", 50_000), Ok(0));
+        protocol.withdraw_collateral(position_id, "TOKEN", 55_000).unwrap();
+        assert_eq!(protocol.positions[&position_id].collateral["TOKEN"], 0);
+        assert_eq!(protocol.lending_pools["USDC"].token_reserves, 1_000_000);
+    }
+    
+    #[test]
+    fn test_accrued_interest_makes_position_liquidatable() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        protocol.lending_pools.insert("USDC".to_string(), secure::LendingPool {
+            borrow_rate_bps_per_sec: 1,
+            ..lending_pool("USDC")
+        });
+        let collateral = HashMap::from([("TOKEN".to_string(), 100_000)]);
+        let position_id = protocol.open_position([1u8; 32], collateral);
+        protocol.borrow(position_id, "USDC", 80_000).unwrap();
+        
+        // No time has passed, so no interest
+        assert_eq!(protocol.accrue_interest(position_id), Ok(80_000));
+        assert_eq!(protocol.health_factor(position_id), Ok(12_500));
+        assert_eq!(protocol.liquidate_position(position_id, [2u8; 32]), Err("Position is not liquidatable"));
+        
+        // 2000s at 1 bps/s adds 20%: 96k of debt needs 105.6k of collateral
+        protocol.advance_oracle_time(2000);
+        protocol.update_oracle_price("TOKEN", 1_000_000).unwrap();
+        protocol.update_oracle_price("USDC", 1_000_000).unwrap();
+        assert_eq!(protocol.health_factor(position_id), Ok(10_416));
+        assert_eq!(protocol.positions[&position_id].borrowed_amount, 80_000);
+        assert_eq!(protocol.liquidate_position(position_id, [2u8; 32]), Ok(()));
+    }
+    
+    #[test]
+    fn test_interest_over_extreme_periods_saturates() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        protocol.lending_pools.insert("USDC".to_string(), secure::LendingPool {
+            token_reserves: u64::MAX,
+            borrow_rate_bps_per_sec: u64::MAX,
+            ..lending_pool("USDC")
+        });
+        let position_id = protocol.create_position([1u8; 32], "TOKEN", 100_000, "USDC", u64::MAX / 2);
+        
+        protocol.advance_oracle_time(u64::MAX / 2);
+        assert_eq!(protocol.accrue_interest(position_id), Ok(u64::MAX));
+        assert_eq!(protocol.accrue_interest(position_id), Ok(u64::MAX));
+    }
+    
+    #[test]
+    fn test_health_factor_reports_without_liquidating() {
+        let mut protocol = seeded_secure_protocol(&["TOKEN", "USDC"]);
+        let user = [1u8; 32];
+        
+        // Healthy: 150k collateral against 100k debt
+        let healthy = protocol.create_position(user, "TOKEN", 150_000, "USDC", 100_000);
+        assert_eq!(protocol.health_factor(healthy), Ok(15_000));
+        
+        // Borderline: exactly at the 110% threshold, which is not yet liquidatable
+        let borderline = protocol.create_position(user, "TOKEN", 110_000, "USDC", 100_000);
+        assert_eq!(protocol.health_factor(borderline), Ok(11_000));
+        assert_eq!(protocol.liquidate_position(borderline, user), Err("Position is not liquidatable"));
+        
+        // Just below the threshold it is, but querying doesn't close it
+        let unhealthy = protocol.create_position(user, "TOKEN", 109_999, "USDC", 100_000);
+        assert_eq!(protocol.health_factor(unhealthy), Ok(10_999));
+        assert!(protocol.positions.contains_key(&unhealthy));
+        
+        // Zero debt is infinitely healthy rather than a division by zero
+        let debt_free = protocol.create_position(user, "TOKEN", 1_000, "USDC", 0);
+        assert_eq!(protocol.health_factor(debt_free), Ok(u64::MAX));
+        
+        assert_eq!(protocol.health_factor(999), Err("Position not found"));
+    }
+    
+    #[test]
+    fn test_nested_flash_loan_on_same_pool_is_rejected() {
+        let mut protocol = secure_protocol_with_pools(&["USDC"]);
+        
+        let result = protocol.flash_loan("USDC", 10_000, |protocol| {
+            protocol.flash_loan("USDC", 10_000, |_| Ok(()))
+        });
+        assert_eq!(result, Err("nested flash loan"));
+        
+        // The failed loan is rolled back and the guard released
+        let pool = &protocol.lending_pools["USDC"];
+        assert_eq!(pool.token_reserves, 1_000_000);
+        assert!(!pool.in_flash_loan);
+        protocol.flash_loan("USDC", 10_000, |protocol| {
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += 10_005;
+            Ok(())
+        }).unwrap();
+    }
+    
+    #[test]
+    fn test_nested_flash_loan_on_other_pool_is_allowed() {
+        let mut protocol = secure_protocol_with_pools(&["USDC", "DAI"]);
+        
+        protocol.flash_loan("USDC", 10_000, |protocol| {
+            protocol.flash_loan("DAI", 20_000, |protocol| {
+                protocol.lending_pools.get_mut("DAI").unwrap().token_reserves += 20_010;
+                Ok(())
+            })?;
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += 10_005;
+            Ok(())
+        }).unwrap();
+        
+        assert_eq!(protocol.lending_pools["USDC"].token_reserves, 1_000_005);
+        assert_eq!(protocol.lending_pools["DAI"].token_reserves, 1_000_010);
+        assert_eq!(protocol.events.by_kind("FlashLoanRepaid").len(), 2);
+    }
+    
+    /// Idle interest-free lending pool for `token` with 1M reserves
+    fn lending_pool(token: &str) -> secure::LendingPool {
+        secure::LendingPool {
+            token_reserves: 1_000_000,
+            name: format!("{} Pool", token),
+            in_flash_loan: false,
+            borrow_rate_bps_per_sec: 0,
+        }
+    }
+    
+    /// Secure protocol with 1M-reserve lending pools for `tokens`
+    fn secure_protocol_with_pools(tokens: &[&str]) -> secure::LendingProtocol {
+        let mut protocol = secure::LendingProtocol::new();
+        for token in tokens {
+            protocol.lending_pools.insert(token.to_string(), lending_pool(token));
+        }
+        protocol
+    }
+    
+    #[test]
+    fn test_multi_token_flash_loan_repaid() {
+        let mut protocol = secure_protocol_with_pools(&["USDC", "DAI"]);
+        let loans = [("USDC".to_string(), 10_000), ("DAI".to_string(), 20_000)];
+        
+        protocol.flash_loan_multi(&loans, |protocol| {
+            // Both legs are lent out while the callback runs
+            assert_eq!(protocol.lending_pools["USDC"].token_reserves, 990_000);
+            assert_eq!(protocol.lending_pools["DAI"].token_reserves, 980_000);
+            
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += 10_005;
+            protocol.lending_pools.get_mut("DAI").unwrap().token_reserves += 20_010;
+            Ok(())
+        }).unwrap();
+        
+        assert_eq!(protocol.lending_pools["USDC"].token_reserves, 1_000_005);
+        assert_eq!(protocol.lending_pools["DAI"].token_reserves, 1_000_010);
+        assert_eq!(protocol.events.by_kind("FlashLoanRepaid").len(), 2);
+    }
+    
+    #[test]
+    fn test_multi_token_flash_loan_partial_repayment_reverts() {
+        let mut protocol = secure_protocol_with_pools(&["USDC", "DAI"]);
+        let loans = [("USDC".to_string(), 10_000), ("DAI".to_string(), 20_000)];
+        
+        // USDC is repaid in full, DAI is short by its fee
+        let result = protocol.flash_loan_multi(&loans, |protocol| {
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += 10_005;
+            protocol.lending_pools.get_mut("DAI").unwrap().token_reserves += 20_000;
+            Ok(())
+        });
+        assert_eq!(result, Err("Flash loan not repaid with fee"));
+        
+        for token in ["USDC", "DAI"] {
+            let pool = &protocol.lending_pools[token];
+            assert_eq!(pool.token_reserves, 1_000_000);
+            assert!(!pool.in_flash_loan);
+        }
+        assert!(protocol.events.by_kind("FlashLoanRepaid").is_empty());
+        
+        // Duplicates and empty batches are rejected before anything is lent
+        let duplicated = [("USDC".to_string(), 10_000), ("USDC".to_string(), 10_000)];
+        assert_eq!(protocol.flash_loan_multi(&duplicated, |_| Ok(())), Err("Duplicate token in flash loan batch"));
+        assert_eq!(protocol.flash_loan_multi(&[], |_| Ok(())), Err("No flash loans requested"));
+        assert_eq!(protocol.lending_pools["USDC"].token_reserves, 1_000_000);
+    }
+    
+    #[test]
+    fn test_multi_token_flash_loan_settles_every_leg_when_a_pool_disappears() {
+        let mut protocol = secure_protocol_with_pools(&["DAI", "USDC"]);
+        let loans = [("DAI".to_string(), 20_000), ("USDC".to_string(), 10_000)];
+        
+        // DAI is checked first and goes missing; USDC must still be unflagged and restored
+        let result = protocol.flash_loan_multi(&loans, |protocol| {
+            protocol.lending_pools.remove("DAI");
+            Ok(())
+        });
+        assert_eq!(result, Err("Pool disappeared during flash loan"));
+        
+        let pool = &protocol.lending_pools["USDC"];
+        assert_eq!(pool.token_reserves, 1_000_000);
+        assert!(!pool.in_flash_loan);
+        
+        // So a later single-token loan from the surviving pool is not mistaken for a nested one
+        protocol.flash_loan("USDC", 10_000, |protocol| {
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += 10_005;
+            Ok(())
+        }).unwrap();
+        assert_eq!(protocol.lending_pools["USDC"].token_reserves, 1_000_005);
+    }
+    
+    #[test]
+    fn test_flash_fee_tiers_at_boundaries() {
+        let reserves = 1_000_000;
+        
+        assert_eq!(secure::compute_flash_fee(0, reserves), 0);
+        assert_eq!(secure::compute_flash_fee(0, 0), 0);
+        
+        // Exactly 10% of the pool still pays the small-loan rate, one more unit does not
+        assert_eq!(secure::compute_flash_fee(100_000, reserves), 50);
+        assert_eq!(secure::compute_flash_fee(100_001, reserves), 300);
+        
+        // Exactly 50% is the top of the middle tier
+        assert_eq!(secure::compute_flash_fee(500_000, reserves), 1_500);
+        assert_eq!(secure::compute_flash_fee(500_001, reserves), 5_000);
+    }
+    
+    #[test]
+    fn test_flash_loan_straddling_a_tier_charges_the_higher_fee() {
+        let mut protocol = secure_protocol_with_pools(&["USDC"]);
+        
+        // 150k of 1M is past the 10% tier, so 0.05% is no longer enough
+        let result = protocol.flash_loan("USDC", 150_000, |protocol| {
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += 150_075;
+            Ok(())
+        });
+        assert_eq!(result, Err("Flash loan not repaid with fee"));
+        
+        protocol.lending_pools.get_mut("USDC").unwrap().token_reserves = 1_000_000;
+        protocol.flash_loan("USDC", 150_000, |protocol| {
+            protocol.lending_pools.get_mut("USDC").unwrap().token_reserves += 150_450;
+            Ok(())
+        }).unwrap();
+        
+        let repayments = protocol.events.by_kind("FlashLoanRepaid");
+        assert_eq!(repayments[0].field("fee"), Some("450"));
+    }
+    
+    #[test]
+    fn test_sync_clock_never_moves_backwards() {
+        let mut chain = crate::utils::MockBlockchain::new();
+        let mut protocol = secure_protocol_with_pools(&["USDC"]);
+        protocol.sync_clock(&chain).unwrap();
+        assert_eq!(protocol.price_oracle.current_time, chain.unix_timestamp());
+        
+        // The oracle clock has run ahead of the chain; syncing must not rewind it
+        protocol.advance_oracle_time(100);
+        let ahead = protocol.price_oracle.current_time;
+        assert!(matches!(
+            protocol.sync_clock(&chain),
+            Err(crate::Error::ClockRegression { current, .. }) if current == ahead
+        ));
+        assert_eq!(protocol.price_oracle.current_time, ahead);
+        
+        chain.advance_slots(150);
+        protocol.sync_clock(&chain).unwrap();
+        assert_eq!(protocol.price_oracle.current_time, ahead + 50);
+    }
 }
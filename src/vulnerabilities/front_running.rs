@@ -8,7 +8,7 @@
 //! - Transaction ordering manipulation
 //! - Lack of commit-reveal schemes for sensitive operations
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 use crate::utils::{Account, MockBlockchain};
 
 /// Represents a front-running vulnerability example
@@ -29,6 +29,18 @@ impl Vulnerability for FrontRunningVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "CosmWasm"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::DeFi, Category::Economic]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Advanced
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable DEX swap function
@@ -64,6 +76,34 @@ impl Vulnerability for FrontRunningVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure swap executed from a hidden commitment, with a deadline and impact cap
+        pub fn reveal_and_execute_swap(&mut self, swap_id: [u8; 32], pool_id: [u8; 32], user: &mut UserAccount,
+                                      token_in: [u8; 32], amount_in: u64, min_amount_out: u64, secret: [u8; 32],
+                                      deadline: u64, max_price_impact_bps: u64, current_block: u64) -> Result<u64, &'static str> {
+            // SECURE: Reject executions submitted after the caller's deadline
+            if current_block > deadline {
+                return Err("deadline exceeded");
+            }
+            
+            let pending_swap = self.pending_swaps.get_mut(&swap_id).ok_or("Swap commitment not found")?;
+            if current_block > pending_swap.expiry {
+                return Err("Commitment expired");
+            }
+            
+            // SECURE: The swap's terms were hidden until now, so nobody could trade ahead of them
+            let preimage = swap_preimage(token_in, amount_in, min_amount_out);
+            if !commit_reveal::verify(&pending_swap.commitment, &preimage, &secret) {
+                return Err("Reveal does not match commitment");
+            }
+            
+            // ...price the swap, enforce min_amount_out and max_price_impact_bps, transfer...
+            Ok(amount_out)
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Identify time-sensitive operations that affect pricing or value",
@@ -161,7 +201,9 @@ pub mod vulnerable {
             // higher gas to execute first
             
             // Calculate output amount based on constant product formula (x * y = k)
-            let amount_out = (amount_in * out_reserves) / (in_reserves + amount_in);
+            // using u128 intermediates; the result is below out_reserves so it fits in a u64
+            let amount_out = ((amount_in as u128 * out_reserves as u128)
+                / (in_reserves as u128 + amount_in as u128)) as u64;
             
             // Check minimum output
             if amount_out < min_amount_out {
@@ -189,11 +231,14 @@ pub mod vulnerable {
 /// Module containing a secure implementation
 pub mod secure {
     use std::collections::HashMap;
+    use crate::utils::commit_reveal;
     
     /// A DEX with front-running protections
     pub struct DEX {
         pub pools: HashMap<[u8; 32], LiquidityPool>,
         pub pending_swaps: HashMap<[u8; 32], PendingSwap>, // Commit-reveal scheme
+        pub batches: HashMap<[u8; 32], Batch>, // pool_id -> orders awaiting settlement
+        pub batch_proceeds: HashMap<[u8; 32], HashMap<[u8; 32], u64>>, // owner -> token -> amount to claim
     }
     
     /// Liquidity pool structure
@@ -220,12 +265,78 @@ pub mod secure {
         pub expiry: u64, // Block number/timestamp when this commitment expires
     }
     
+    /// An order waiting in a batch auction
+    pub struct BatchOrder {
+        pub user: [u8; 32],
+        pub token_in: [u8; 32],
+        pub amount_in: u64, // Held in escrow by the DEX until settlement
+        pub min_amount_out: u64,
+    }
+    
+    /// Orders collected for a pool within a single block
+    pub struct Batch {
+        pub block: u64,
+        pub orders: Vec<BatchOrder>,
+    }
+    
+    /// Uniform clearing price for a batch, as token B per token A (`numerator / denominator`).
+    ///
+    /// Opposing flow is matched at the clearing price and only the net imbalance trades against
+    /// the pool, with the price set to the pool's marginal price after absorbing that imbalance.
+    fn clearing_price(reserve_a: u128, reserve_b: u128, a_in: u128, b_in: u128) -> (u128, u128) {
+        if a_in * reserve_b >= b_in * reserve_a {
+            // Net selling A: the pool absorbs x of A, where A sellers fund B sellers at the same price
+            let net_a = (a_in * reserve_b - b_in * reserve_a).div_ceil(reserve_b + b_in);
+            (reserve_b, reserve_a + net_a)
+        } else {
+            let net_b = (b_in * reserve_a - a_in * reserve_b).div_ceil(reserve_a + a_in);
+            (reserve_b + net_b, reserve_a)
+        }
+    }
+    
+    /// Encode the swap parameters that a commitment binds to
+    fn swap_preimage(token_in: [u8; 32], amount_in: u64, min_amount_out: u64) -> Vec<u8> {
+        let mut preimage = Vec::with_capacity(48);
+        preimage.extend_from_slice(&token_in);
+        preimage.extend_from_slice(&amount_in.to_le_bytes());
+        preimage.extend_from_slice(&min_amount_out.to_le_bytes());
+        preimage
+    }
+    
+    /// Compute the commitment a user submits in `commit_swap`
+    pub fn swap_commitment(token_in: [u8; 32], amount_in: u64, min_amount_out: u64, secret: [u8; 32]) -> [u8; 32] {
+        commit_reveal::commitment(&swap_preimage(token_in, amount_in, min_amount_out), &secret)
+    }
+    
+    /// Price movement caused by a swap, in basis points of the pre-swap price (out per in)
+    fn price_impact_bps(in_reserves: u64,
+                        out_reserves: u64,
+                        amount_in: u64,
+                        amount_out: u64) -> Result<u64, &'static str> {
+        let new_in = in_reserves as u128 + amount_in as u128;
+        let new_out = (out_reserves - amount_out) as u128;
+        
+        // new_price / old_price = (new_out / new_in) / (out_reserves / in_reserves)
+        let numerator = new_out.checked_mul(in_reserves as u128)
+            .and_then(|n| n.checked_mul(10_000))
+            .ok_or("Price impact overflow")?;
+        let denominator = (out_reserves as u128).checked_mul(new_in)
+            .ok_or("Price impact overflow")?;
+        if denominator == 0 {
+            return Err("Empty pool");
+        }
+        
+        Ok(10_000u64.saturating_sub((numerator / denominator) as u64))
+    }
+    
     impl DEX {
         /// Create a new DEX
         pub fn new() -> Self {
             Self {
                 pools: HashMap::new(),
                 pending_swaps: HashMap::new(),
+                batches: HashMap::new(),
+                batch_proceeds: HashMap::new(),
             }
         }
         
@@ -268,6 +379,10 @@ pub mod secure {
         }
         
         /// SECURE: Step 2 - Reveal and execute the swap
+        ///
+        /// `deadline` is the last block (inclusive) at which the swap may execute, independent of
+        /// the commitment expiry. `max_price_impact_bps` bounds how far the swap may move the pool
+        /// price, in basis points of the pre-swap price.
         pub fn reveal_and_execute_swap(&mut self,
                                       swap_id: [u8; 32],
                                       pool_id: [u8; 32],
@@ -276,8 +391,15 @@ pub mod secure {
                                       amount_in: u64,
                                       min_amount_out: u64,
                                       secret: [u8; 32],
+                                      deadline: u64,
+                                      max_price_impact_bps: u64,
                                       current_block: u64) -> Result<u64, &'static str> {
             
+            // SECURE: Reject executions submitted after the caller's deadline
+            if current_block > deadline {
+                return Err("deadline exceeded");
+            }
+            
             // Get the pending swap
             let pending_swap = self.pending_swaps.get_mut(&swap_id)
                 .ok_or("Swap commitment not found")?;
@@ -292,9 +414,11 @@ pub mod secure {
                 return Err("Unauthorized user");
             }
             
-            // Verify the commitment matches the revealed data
-            // In a real implementation, this would hash the token_in, amount_in, min_amount_out, and secret
-            // to verify it matches the stored commitment
+            // SECURE: Verify the revealed parameters open the stored commitment
+            let preimage = swap_preimage(token_in, amount_in, min_amount_out);
+            if !commit_reveal::verify(&pending_swap.commitment, &preimage, &secret) {
+                return Err("Reveal does not match commitment");
+            }
             
             // Update the pending swap with revealed data
             pending_swap.token_in = token_in;
@@ -315,13 +439,20 @@ pub mod secure {
             };
             
             // Calculate output amount based on constant product formula (x * y = k)
-            let amount_out = (amount_in * out_reserves) / (in_reserves + amount_in);
+            // using u128 intermediates; the result is below out_reserves so it fits in a u64
+            let amount_out = ((amount_in as u128 * out_reserves as u128)
+                / (in_reserves as u128 + amount_in as u128)) as u64;
             
             // Check minimum output
             if amount_out < min_amount_out {
                 return Err("Slippage too high");
             }
             
+            // SECURE: Bound how far this swap moves the pool price
+            if price_impact_bps(in_reserves, out_reserves, amount_in, amount_out)? > max_price_impact_bps {
+                return Err("Price impact too high");
+            }
+            
             // Update user balances
             *user.balances.entry(token_in).or_insert(0) -= amount_in;
             *user.balances.entry(token_out).or_insert(0) += amount_out;
@@ -340,5 +471,345 @@ pub mod secure {
             
             Ok(amount_out)
         }
+        
+        /// SECURE: Queue an order for the pool's batch auction in `current_block`
+        ///
+        /// Orders in a batch all settle at one clearing price, so their position within the block
+        /// gives no advantage. `amount_in` is taken from `user` into escrow until the batch
+        /// settles. Returns the order's index in the batch.
+        pub fn submit_batch_order(&mut self,
+                                 pool_id: [u8; 32],
+                                 user: &mut UserAccount,
+                                 token_in: [u8; 32],
+                                 amount_in: u64,
+                                 min_amount_out: u64,
+                                 current_block: u64) -> Result<usize, &'static str> {
+            let pool = self.pools.get(&pool_id)
+                .ok_or("Pool not found")?;
+            if token_in != pool.token_a && token_in != pool.token_b {
+                return Err("Invalid token");
+            }
+            if amount_in == 0 {
+                return Err("Amount must be positive");
+            }
+            
+            let balance = user.balances.get(&token_in).copied().unwrap_or(0);
+            if balance < amount_in {
+                return Err("Insufficient balance");
+            }
+            
+            let batch = self.batches.entry(pool_id).or_insert(Batch {
+                block: current_block,
+                orders: Vec::new(),
+            });
+            if batch.block != current_block {
+                return Err("Batch for an earlier block has not been settled");
+            }
+            
+            // SECURE: Escrow the input so every queued order is backed by real funds
+            user.balances.insert(token_in, balance - amount_in);
+            batch.orders.push(BatchOrder { user: user.owner, token_in, amount_in, min_amount_out });
+            Ok(batch.orders.len() - 1)
+        }
+        
+        /// SECURE: Settle every order in the pool's batch at a single clearing price
+        ///
+        /// Returns the output amount for each order in submission order. Orders whose
+        /// `min_amount_out` cannot be met at the clearing price are reverted (output 0) and the
+        /// price is recomputed without them. Outputs and the escrow of reverted orders are
+        /// credited to `batch_proceeds` for their owners to claim.
+        pub fn settle_batch(&mut self, pool_id: [u8; 32]) -> Result<Vec<u64>, &'static str> {
+            let pool = self.pools.get_mut(&pool_id)
+                .ok_or("Pool not found")?;
+            let batch = self.batches.get(&pool_id)
+                .ok_or("No orders in batch")?;
+            
+            let reserve_a = pool.token_a_reserves as u128;
+            let reserve_b = pool.token_b_reserves as u128;
+            let mut active = vec![true; batch.orders.len()];
+            let mut outputs = vec![0u64; batch.orders.len()];
+            
+            loop {
+                let (mut a_in, mut b_in) = (0u128, 0u128);
+                for (order, _) in batch.orders.iter().zip(&active).filter(|(_, active)| **active) {
+                    if order.token_in == pool.token_a {
+                        a_in += order.amount_in as u128;
+                    } else {
+                        b_in += order.amount_in as u128;
+                    }
+                }
+                if a_in == 0 && b_in == 0 {
+                    break;
+                }
+                
+                let (numerator, denominator) = clearing_price(reserve_a, reserve_b, a_in, b_in);
+                let mut reverted = false;
+                for (i, order) in batch.orders.iter().enumerate() {
+                    if !active[i] {
+                        continue;
+                    }
+                    let amount_out = if order.token_in == pool.token_a {
+                        order.amount_in as u128 * numerator / denominator
+                    } else {
+                        order.amount_in as u128 * denominator / numerator
+                    } as u64;
+                    
+                    if amount_out < order.min_amount_out {
+                        active[i] = false;
+                        outputs[i] = 0;
+                        reverted = true;
+                    } else {
+                        outputs[i] = amount_out;
+                    }
+                }
+                
+                if !reverted {
+                    break;
+                }
+            }
+            
+            // Net the settled flows before touching the pool: opposing orders
+            // largely cancel out, so debiting them one at a time could underflow
+            let (mut a_in, mut b_in, mut a_out, mut b_out) = (0u128, 0u128, 0u128, 0u128);
+            for (i, order) in batch.orders.iter().enumerate().filter(|(i, _)| active[*i]) {
+                if order.token_in == pool.token_a {
+                    a_in += order.amount_in as u128;
+                    b_out += outputs[i] as u128;
+                } else {
+                    b_in += order.amount_in as u128;
+                    a_out += outputs[i] as u128;
+                }
+            }
+            let new_reserve_a = (reserve_a + a_in).checked_sub(a_out).ok_or("Batch would overdraw the pool")?;
+            let new_reserve_b = (reserve_b + b_in).checked_sub(b_out).ok_or("Batch would overdraw the pool")?;
+            let new_reserve_a = u64::try_from(new_reserve_a).map_err(|_| "Pool reserves overflow")?;
+            let new_reserve_b = u64::try_from(new_reserve_b).map_err(|_| "Pool reserves overflow")?;
+            
+            pool.token_a_reserves = new_reserve_a;
+            pool.token_b_reserves = new_reserve_b;
+            
+            // Credit the settled orders' outputs and refund the reverted ones
+            let batch = self.batches.remove(&pool_id).expect("batch checked above");
+            for (i, order) in batch.orders.iter().enumerate() {
+                let proceeds = self.batch_proceeds.entry(order.user).or_default();
+                if !active[i] {
+                    *proceeds.entry(order.token_in).or_insert(0) += order.amount_in;
+                } else if order.token_in == pool.token_a {
+                    *proceeds.entry(pool.token_b).or_insert(0) += outputs[i];
+                } else {
+                    *proceeds.entry(pool.token_a).or_insert(0) += outputs[i];
+                }
+            }
+            
+            Ok(outputs)
+        }
+        
+        /// Credit `user` with everything their settled batch orders left to claim
+        pub fn claim_batch_proceeds(&mut self, user: &mut UserAccount) {
+            for (token, amount) in self.batch_proceeds.remove(&user.owner).unwrap_or_default() {
+                *user.balances.entry(token).or_insert(0) += amount;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    
+    #[test]
+    fn test_secure_reveal_must_match_commitment() {
+        let token_a = [1u8; 32];
+        let token_b = [2u8; 32];
+        let owner = [3u8; 32];
+        let secret = [4u8; 32];
+        
+        let mut dex = secure::DEX::new();
+        let pool_id = dex.create_pool(token_a, token_b, 1_000_000, 1_000_000);
+        
+        let mut user = secure::UserAccount {
+            owner,
+            balances: HashMap::from([(token_a, 10_000)]),
+        };
+        
+        // Commit to swapping 1_000 token A with a 900 minimum output
+        let commitment = secure::swap_commitment(token_a, 1_000, 900, secret);
+        let swap_id = dex.commit_swap(owner, commitment, 100).unwrap();
+        
+        // A reveal with a tampered amount is rejected and leaves the commitment in place
+        let result = dex.reveal_and_execute_swap(swap_id, pool_id, &mut user, token_a, 5_000, 900, secret, 110, 100, 101);
+        assert_eq!(result.unwrap_err(), "Reveal does not match commitment");
+        assert!(dex.pending_swaps.contains_key(&swap_id));
+        
+        // So is a reveal with the wrong secret
+        let result = dex.reveal_and_execute_swap(swap_id, pool_id, &mut user, token_a, 1_000, 900, [5u8; 32], 110, 100, 101);
+        assert_eq!(result.unwrap_err(), "Reveal does not match commitment");
+        
+        // The honest reveal executes
+        let amount_out = dex.reveal_and_execute_swap(swap_id, pool_id, &mut user, token_a, 1_000, 900, secret, 110, 100, 101).unwrap();
+        assert!(amount_out >= 900);
+        assert_eq!(user.balances[&token_a], 9_000);
+        assert!(!dex.pending_swaps.contains_key(&swap_id));
+    }
+    
+    #[test]
+    fn test_secure_swap_rejects_expired_deadline() {
+        let token_a = [1u8; 32];
+        let token_b = [2u8; 32];
+        let owner = [3u8; 32];
+        let secret = [4u8; 32];
+        
+        let mut dex = secure::DEX::new();
+        let pool_id = dex.create_pool(token_a, token_b, 1_000_000, 1_000_000);
+        let mut user = secure::UserAccount {
+            owner,
+            balances: HashMap::from([(token_a, 10_000)]),
+        };
+        
+        let commitment = secure::swap_commitment(token_a, 1_000, 900, secret);
+        let swap_id = dex.commit_swap(owner, commitment, 100).unwrap();
+        
+        // Still inside the commitment window, but past the caller's deadline
+        let result = dex.reveal_and_execute_swap(swap_id, pool_id, &mut user, token_a, 1_000, 900, secret, 104, 100, 105);
+        assert_eq!(result.unwrap_err(), "deadline exceeded");
+        assert!(dex.pending_swaps.contains_key(&swap_id));
+        
+        // A deadline equal to the current block is still accepted
+        let result = dex.reveal_and_execute_swap(swap_id, pool_id, &mut user, token_a, 1_000, 900, secret, 105, 100, 105);
+        assert!(result.is_ok());
+    }
+    
+    #[test]
+    fn test_secure_swap_rejects_excessive_price_impact() {
+        let token_a = [1u8; 32];
+        let token_b = [2u8; 32];
+        let owner = [3u8; 32];
+        let secret = [4u8; 32];
+        
+        let mut dex = secure::DEX::new();
+        let pool_id = dex.create_pool(token_a, token_b, 1_000_000, 1_000_000);
+        let mut user = secure::UserAccount {
+            owner,
+            balances: HashMap::from([(token_a, 500_000)]),
+        };
+        
+        // Swapping 10% of the reserves moves the price by roughly 17%
+        let commitment = secure::swap_commitment(token_a, 100_000, 0, secret);
+        let swap_id = dex.commit_swap(owner, commitment, 100).unwrap();
+        
+        let result = dex.reveal_and_execute_swap(swap_id, pool_id, &mut user, token_a, 100_000, 0, secret, 110, 500, 101);
+        assert_eq!(result.unwrap_err(), "Price impact too high");
+        assert_eq!(dex.pools[&pool_id].token_a_reserves, 1_000_000);
+        assert_eq!(user.balances[&token_a], 500_000);
+        
+        // The same swap passes with a looser bound
+        let result = dex.reveal_and_execute_swap(swap_id, pool_id, &mut user, token_a, 100_000, 0, secret, 110, 2_000, 101);
+        assert!(result.is_ok());
+    }
+    
+    #[test]
+    fn test_batch_settlement_is_order_independent() {
+        let token_a = [1u8; 32];
+        let token_b = [2u8; 32];
+        let alice = [3u8; 32];
+        let bob = [4u8; 32];
+        let carol = [5u8; 32];
+        
+        let settle = |orders: &[([u8; 32], [u8; 32], u64, u64)]| {
+            let mut dex = secure::DEX::new();
+            let pool_id = dex.create_pool(token_a, token_b, 1_000_000, 1_000_000);
+            for &(owner, token_in, amount_in, min_amount_out) in orders {
+                let mut user = secure::UserAccount {
+                    owner,
+                    balances: HashMap::from([(token_in, amount_in)]),
+                };
+                dex.submit_batch_order(pool_id, &mut user, token_in, amount_in, min_amount_out, 100).unwrap();
+            }
+            let outputs = dex.settle_batch(pool_id).unwrap();
+            (outputs, dex.pools[&pool_id].token_a_reserves, dex.pools[&pool_id].token_b_reserves)
+        };
+        
+        let alice_order = (alice, token_a, 50_000, 40_000);
+        let bob_order = (bob, token_b, 20_000, 15_000);
+        // Carol demands more than any uniform price can give and is reverted on her own
+        let carol_order = (carol, token_a, 1_000, 2_000);
+        
+        let (forward, fwd_a, fwd_b) = settle(&[alice_order, bob_order, carol_order]);
+        let (reversed, rev_a, rev_b) = settle(&[carol_order, bob_order, alice_order]);
+        
+        assert_eq!(forward[0], reversed[2]);
+        assert_eq!(forward[1], reversed[1]);
+        assert_eq!(forward[2], 0);
+        assert_eq!(reversed[0], 0);
+        assert!(forward[0] >= 40_000 && forward[1] >= 15_000);
+        assert_eq!((fwd_a, fwd_b), (rev_a, rev_b));
+    }
+    
+    #[test]
+    fn test_batch_orders_are_escrowed_and_refunded_on_revert() {
+        let token_a = [1u8; 32];
+        let token_b = [2u8; 32];
+        
+        let mut dex = secure::DEX::new();
+        let pool_id = dex.create_pool(token_a, token_b, 1_000_000, 1_000_000);
+        let mut alice = secure::UserAccount {
+            owner: [3u8; 32],
+            balances: HashMap::from([(token_a, 50_000)]),
+        };
+        let mut carol = secure::UserAccount {
+            owner: [5u8; 32],
+            balances: HashMap::from([(token_a, 1_000)]),
+        };
+        
+        // An order cannot outrun the funds behind it
+        assert_eq!(dex.submit_batch_order(pool_id, &mut alice, token_a, 50_001, 0, 100), Err("Insufficient balance"));
+        assert_eq!(alice.balances[&token_a], 50_000);
+        
+        dex.submit_batch_order(pool_id, &mut alice, token_a, 50_000, 40_000, 100).unwrap();
+        dex.submit_batch_order(pool_id, &mut carol, token_a, 1_000, 2_000, 100).unwrap();
+        assert_eq!((alice.balances[&token_a], carol.balances[&token_a]), (0, 0));
+        
+        let outputs = dex.settle_batch(pool_id).unwrap();
+        assert_eq!(outputs[1], 0);
+        
+        // Alice receives her fill, Carol's reverted order is refunded in full
+        dex.claim_batch_proceeds(&mut alice);
+        dex.claim_batch_proceeds(&mut carol);
+        assert_eq!(alice.balances[&token_b], outputs[0]);
+        assert_eq!(carol.balances[&token_a], 1_000);
+        assert!(!carol.balances.contains_key(&token_b));
+        assert!(dex.batch_proceeds.is_empty());
+    }
+    
+    #[test]
+    fn test_opposing_batch_flows_settle_on_their_net() {
+        let token_a = [1u8; 32];
+        let token_b = [2u8; 32];
+        
+        let mut dex = secure::DEX::new();
+        let pool_id = dex.create_pool(token_a, token_b, 1_000_000, 1_000_000);
+        let mut alice = secure::UserAccount {
+            owner: [3u8; 32],
+            balances: HashMap::from([(token_a, 10_000_000)]),
+        };
+        let mut bob = secure::UserAccount {
+            owner: [4u8; 32],
+            balances: HashMap::from([(token_b, 10_000_000)]),
+        };
+        
+        // Each order alone is ten times the opposite reserve, but they cancel out
+        dex.submit_batch_order(pool_id, &mut alice, token_a, 10_000_000, 0, 100).unwrap();
+        dex.submit_batch_order(pool_id, &mut bob, token_b, 10_000_000, 0, 100).unwrap();
+        let outputs = dex.settle_batch(pool_id).unwrap();
+        
+        let pool = &dex.pools[&pool_id];
+        assert_eq!(pool.token_a_reserves, 1_000_000 + 10_000_000 - outputs[1]);
+        assert_eq!(pool.token_b_reserves, 1_000_000 + 10_000_000 - outputs[0]);
+        
+        dex.claim_batch_proceeds(&mut alice);
+        dex.claim_batch_proceeds(&mut bob);
+        assert_eq!(alice.balances[&token_b], outputs[0]);
+        assert_eq!(bob.balances[&token_a], outputs[1]);
     }
 }
@@ -8,7 +8,7 @@
 //! These vulnerabilities can exist in various forms, from fee parameters that
 //! can be manipulated to outright theft of funds.
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 
 /// Represents an illicit fee collection vulnerability example
 pub struct IllicitFeeVulnerability;
@@ -28,6 +28,18 @@ impl Vulnerability for IllicitFeeVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "All DeFi platforms"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::DeFi, Category::Economic]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Moderate
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable function with manipulable fee destination
@@ -74,6 +86,38 @@ impl Vulnerability for IllicitFeeVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure fee handling: a quorum of fee signers redirects fees, and every fee is disclosed
+        pub fn set_fee_recipient(&mut self, ctx: &TxContext, proposal_id: u64) -> Result<(), &'static str> {
+            // FIXED: Redirecting fees takes a quorum of fee signers, not one key
+            self.fee_signers_in(ctx)?;
+            let &(pool_id, new_recipient) = self.pending_recipient_changes.get(&proposal_id).ok_or("Proposal not found")?;
+            
+            self.fee_signers.execute(proposal_id)?; // Err("Not enough approvals") below 2 of 3
+            self.pending_recipient_changes.remove(&proposal_id);
+            self.pools.get_mut(&pool_id).ok_or("Pool not found")?.fee_recipient = new_recipient;
+            Ok(())
+        }
+        
+        pub fn swap(&mut self, user_id: [u8; 32], pool_id: u64, token_a_amount: u64) -> Result<u64, &'static str> {
+            // ...load user and pool, check balance...
+            
+            // FIXED: Transparent fee calculation from the pool's published rates
+            let lp_fee_amount = token_a_amount * pool.fee_percentage / 10000;
+            let protocol_fee_amount = token_a_amount * pool.protocol_fee_percentage / 10000;
+            let amount_after_fee = token_a_amount - lp_fee_amount - protocol_fee_amount;
+            
+            // ...constant product swap on amount_after_fee...
+            
+            // FIXED: Protocol fee accrues in a dedicated ledger until the fee admin withdraws it
+            *self.accrued_protocol_fees.entry(TOKEN_A.to_string()).or_insert(0) += protocol_fee_amount;
+            
+            Ok(token_b_out)
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Examine fee calculation logic for manipulation opportunities",
@@ -208,11 +252,75 @@ pub mod vulnerable {
             
             Ok(actual_token_b_out)
         }
+        
+        /// Audit helper: the output `swap` should pay if only the documented fee applied
+        pub fn expected_output_no_hidden_fee(&self, pool_id: u64, token_a_amount: u64) -> Result<u64, &'static str> {
+            let pool = self.pools.get(&pool_id).ok_or("Pool not found")?;
+            
+            // Widened so large pools don't overflow the invariant
+            let k = pool.token_a_reserves as u128 * pool.token_b_reserves as u128;
+            let fee_amount = token_a_amount as u128 * pool.fee_percentage as u128 / 10000;
+            let new_token_a_reserves = pool.token_a_reserves as u128 + token_a_amount as u128 - fee_amount;
+            let new_token_b_reserves = k.checked_div(new_token_a_reserves).ok_or("Pool has no liquidity")?;
+            
+            // At most the current reserves, so it always fits back into a u64
+            Ok((pool.token_b_reserves as u128 - new_token_b_reserves) as u64)
+        }
+        
+        /// Audit helper: the output `swap` actually pays for the same input
+        ///
+        /// Runs the real `swap` against a copy of the pool, so the quote can't
+        /// drift from whatever the swap path deducts.
+        pub fn actual_output(&self, pool_id: u64, token_a_amount: u64) -> Result<u64, &'static str> {
+            let pool = self.pools.get(&pool_id).ok_or("Pool not found")?.clone();
+            
+            let trader = [0u8; 32];
+            let mut sandbox = DexProtocol::new(self.admin);
+            sandbox.pools.insert(pool_id, pool);
+            sandbox.users.insert(trader, UserAccount {
+                owner: trader,
+                token_a_balance: token_a_amount,
+                token_b_balance: 0,
+                lp_tokens: 0,
+            });
+            
+            sandbox.swap(trader, pool_id, token_a_amount)
+        }
+        
+        /// Audit helper: undisclosed fee taken by `swap`, in basis points of the documented output
+        ///
+        /// Rounded to the nearest basis point; 0 for an unknown pool or a swap with no output.
+        pub fn hidden_fee_bps(&self, pool_id: u64, token_a_amount: u64) -> u64 {
+            let (Ok(expected), Ok(actual)) = (
+                self.expected_output_no_hidden_fee(pool_id, token_a_amount),
+                self.actual_output(pool_id, token_a_amount),
+            ) else {
+                return 0;
+            };
+            if expected == 0 {
+                return 0;
+            }
+            
+            ((expected - actual) * 10000 + expected / 2) / expected
+        }
     }
 }
 
 /// Example of secure code that prevents illicit fee collection
 pub mod secure {
+    use crate::utils::{Amount, EventLog, MultiSig, Timelock, TxContext};
+    use crate::utils::display::format_address;
+    
+    /// Ledger keys for `accrued_protocol_fees`
+    pub const TOKEN_A: &str = "token_a";
+    pub const TOKEN_B: &str = "token_b";
+    
+    /// Delay before a proposed fee change can be applied
+    pub const FEE_CHANGE_DELAY: u64 = 86400; // 24 hours
+    
+    /// Approvals needed from the three fee signers to redirect a pool's fees
+    pub const FEE_SIGNER_THRESHOLD: usize = 2;
+    
     #[derive(Debug, Clone)]
     pub struct LiquidityPool {
         pub token_a_reserves: u64,
@@ -235,8 +343,7 @@ pub mod secure {
     pub struct FeeChange {
         pub pool_id: u64,
         pub new_fee: u64,
-        pub timestamp: u64,
-        pub effective_time: u64,
+        pub timestamp: u64, // When the change was proposed
     }
     
     pub struct DexProtocol {
@@ -244,33 +351,100 @@ pub mod secure {
         pub fee_admin: [u8; 32],
         pub pools: std::collections::HashMap<u64, LiquidityPool>,
         pub users: std::collections::HashMap<[u8; 32], UserAccount>,
-        pub pending_fee_changes: Vec<FeeChange>,
-        pub current_time: u64,
+        pub pending_fee_changes: Timelock<FeeChange>,
+        pub events: EventLog,
+        pub accrued_protocol_fees: std::collections::HashMap<String, u64>, // token -> fees awaiting withdrawal
+        pub fee_signers: MultiSig, // Must approve every fee recipient change
+        pub pending_recipient_changes: std::collections::HashMap<u64, (u64, [u8; 32])>, // proposal id -> (pool id, new recipient)
+        pub current_time: u64, // Protocol clock; fee timelocks never trust a caller's timestamp
     }
     
     impl DexProtocol {
-        pub fn new(admin: [u8; 32]) -> Self {
-            Self {
+        /// Create a protocol whose fee recipient changes need 2 of `fee_signers`
+        pub fn new(admin: [u8; 32], fee_signers: [[u8; 32]; 3]) -> Result<Self, &'static str> {
+            // FIXED: There is no single-key default; the fee council is required up front
+            let fee_signers = MultiSig::new(fee_signers.to_vec(), FEE_SIGNER_THRESHOLD)?;
+            Ok(Self {
                 admin,
                 fee_admin: admin, // Initially the same, can be changed
                 pools: std::collections::HashMap::new(),
                 users: std::collections::HashMap::new(),
-                pending_fee_changes: Vec::new(),
+                pending_fee_changes: Timelock::new(FEE_CHANGE_DELAY),
+                events: EventLog::new(),
+                accrued_protocol_fees: std::collections::HashMap::new(),
+                fee_signers,
+                pending_recipient_changes: std::collections::HashMap::new(),
                 current_time: 0,
+            })
+        }
+        
+        /// Helper to advance the protocol clock (for testing)
+        pub fn advance_time(&mut self, seconds: u64) {
+            self.current_time = self.current_time.saturating_add(seconds);
+        }
+        
+        /// Fee signers who validly signed `ctx`
+        fn fee_signers_in(&self, ctx: &TxContext) -> Result<Vec<[u8; 32]>, &'static str> {
+            if !ctx.signature_valid {
+                return Err("Invalid transaction");
+            }
+            let mut signers = Vec::new();
+            for signer in std::iter::once(&ctx.signer).chain(&ctx.signers) {
+                if self.fee_signers.is_owner(signer) && !signers.contains(signer) {
+                    signers.push(*signer);
+                }
+            }
+            if signers.is_empty() {
+                return Err("Only fee signers can change fee recipient");
             }
+            Ok(signers)
         }
         
-        /// Secure function with proper access control for fee recipient
-        pub fn set_fee_recipient(&mut self, caller: [u8; 32], pool_id: u64, new_recipient: [u8; 32]) -> Result<(), &'static str> {
-            // FIXED: Proper access control check
-            if caller != self.fee_admin {
-                return Err("Only fee admin can change fee recipient");
+        /// Propose redirecting `pool_id`'s fees to `new_recipient`
+        ///
+        /// Every fee signer on `ctx` approves the proposal. Returns the
+        /// proposal id for `approve_fee_recipient` and `set_fee_recipient`.
+        pub fn propose_fee_recipient(&mut self, ctx: &TxContext, pool_id: u64, new_recipient: [u8; 32]) -> Result<u64, &'static str> {
+            let signers = self.fee_signers_in(ctx)?;
+            if !self.pools.contains_key(&pool_id) {
+                return Err("Pool not found");
             }
             
-            let pool = match self.pools.get_mut(&pool_id) {
-                Some(pool) => pool,
-                None => return Err("Pool not found"),
-            };
+            let proposal_id = self.fee_signers.propose(signers[0])?;
+            for signer in &signers[1..] {
+                self.fee_signers.approve(proposal_id, *signer)?;
+            }
+            self.pending_recipient_changes.insert(proposal_id, (pool_id, new_recipient));
+            Ok(proposal_id)
+        }
+        
+        /// Approve a pending fee recipient change, returning its approval count
+        pub fn approve_fee_recipient(&mut self, ctx: &TxContext, proposal_id: u64) -> Result<usize, &'static str> {
+            let mut approvals = 0;
+            for signer in self.fee_signers_in(ctx)? {
+                approvals = self.fee_signers.approve(proposal_id, signer)?;
+            }
+            Ok(approvals)
+        }
+        
+        /// Apply a fee recipient change once enough fee signers have approved it
+        pub fn set_fee_recipient(&mut self, ctx: &TxContext, proposal_id: u64) -> Result<(), &'static str> {
+            // FIXED: Redirecting fees takes a quorum of fee signers, not one key
+            self.fee_signers_in(ctx)?;
+            let &(pool_id, new_recipient) = self.pending_recipient_changes.get(&proposal_id).ok_or("Proposal not found")?;
+            if !self.pools.contains_key(&pool_id) {
+                return Err("Pool not found");
+            }
+            
+            self.fee_signers.execute(proposal_id)?;
+            self.pending_recipient_changes.remove(&proposal_id);
+            let pool = self.pools.get_mut(&pool_id).unwrap();
+            
+            self.events.emit("FeeRecipientChanged", &[
+                ("pool_id", pool_id.to_string()),
+                ("old_recipient", format_address(&pool.fee_recipient)),
+                ("new_recipient", format_address(&new_recipient)),
+            ], ctx.timestamp);
             
             pool.fee_recipient = new_recipient;
             
@@ -278,9 +452,12 @@ pub mod secure {
         }
         
         /// Secure function with timelock for fee changes
-        pub fn propose_fee_change(&mut self, caller: [u8; 32], pool_id: u64, new_fee: u64) -> Result<(), &'static str> {
+        pub fn propose_fee_change(&mut self, ctx: &TxContext, pool_id: u64, new_fee: u64) -> Result<(), &'static str> {
             // FIXED: Proper access control
-            if caller != self.fee_admin {
+            if !ctx.signature_valid {
+                return Err("Invalid transaction");
+            }
+            if !ctx.is_signed_by(&self.fee_admin) {
                 return Err("Only fee admin can propose fee changes");
             }
             
@@ -295,41 +472,74 @@ pub mod secure {
                 return Err("Pool not found");
             }
             
-            // Create timelock for change (24 hours)
+            // Create timelock for change (24 hours), running on the protocol clock
             let change = FeeChange {
                 pool_id,
                 new_fee,
                 timestamp: self.current_time,
-                effective_time: self.current_time + 86400, // 24 hours later
             };
+            let effective_time = self.pending_fee_changes.queue(change, self.current_time);
             
-            self.pending_fee_changes.push(change);
+            self.events.emit("FeeChangeProposed", &[
+                ("pool_id", pool_id.to_string()),
+                ("new_fee", new_fee.to_string()),
+                ("effective_time", effective_time.to_string()),
+            ], self.current_time);
             
             Ok(())
         }
         
+        /// Cancel every pending fee change for `pool_id`, returning how many were cancelled
+        ///
+        /// Lets the fee admin stop a mistaken or malicious proposal while its
+        /// timelock is still running. Cancelling with nothing pending is `Ok(0)`.
+        pub fn cancel_fee_change(&mut self, ctx: &TxContext, pool_id: u64) -> Result<usize, &'static str> {
+            if !ctx.signature_valid {
+                return Err("Invalid transaction");
+            }
+            if !ctx.is_signed_by(&self.fee_admin) {
+                return Err("Only fee admin can cancel fee changes");
+            }
+            
+            let cancelled = self.pending_fee_changes.cancel(|change| change.pool_id == pool_id);
+            for change in &cancelled {
+                self.events.emit("FeeChangeCancelled", &[
+                    ("pool_id", change.pool_id.to_string()),
+                    ("new_fee", change.new_fee.to_string()),
+                ], self.current_time);
+            }
+            
+            Ok(cancelled.len())
+        }
+        
         /// Apply pending fee changes that have passed their timelock
-        pub fn apply_pending_fee_changes(&mut self) -> Result<usize, &'static str> {
-            let mut applied_count = 0;
+        ///
+        /// Anyone may call this. Readiness is judged by the protocol clock,
+        /// never by `ctx.timestamp`, which the caller controls.
+        pub fn apply_pending_fee_changes(&mut self, ctx: &TxContext) -> Result<usize, &'static str> {
+            if !ctx.signature_valid {
+                return Err("Invalid transaction");
+            }
             
-            // Find changes that can be applied
-            let pending_changes: Vec<FeeChange> = self.pending_fee_changes
-                .iter()
-                .filter(|c| c.effective_time <= self.current_time)
-                .cloned()
-                .collect();
+            // FIXED: A caller-supplied timestamp of u64::MAX would make every change ready
+            let now = self.current_time;
+            let mut applied_count = 0;
             
-            // Apply each change
-            for change in pending_changes {
+            // Apply each change that has passed its timelock; changes for
+            // pools removed in the meantime are dropped
+            for change in self.pending_fee_changes.ready(now) {
                 if let Some(pool) = self.pools.get_mut(&change.pool_id) {
+                    self.events.emit("FeeChanged", &[
+                        ("pool_id", change.pool_id.to_string()),
+                        ("old_fee", pool.fee_percentage.to_string()),
+                        ("new_fee", change.new_fee.to_string()),
+                    ], now);
+                    
                     pool.fee_percentage = change.new_fee;
                     applied_count += 1;
                 }
             }
             
-            // Remove applied changes
-            self.pending_fee_changes.retain(|c| c.effective_time > self.current_time);
-            
             Ok(applied_count)
         }
         
@@ -350,19 +560,22 @@ pub mod secure {
                 return Err("Insufficient token A balance");
             }
             
+            let overflow = |_| "Arithmetic overflow in swap";
+            
             // Calculate swap amount using constant product formula (A * B = k)
-            let k = pool.token_a_reserves * pool.token_b_reserves;
+            let k = u64::from(Amount::from(pool.token_a_reserves).checked_mul(pool.token_b_reserves).map_err(overflow)?);
             
             // FIXED: Transparent fee calculation
-            let lp_fee_amount = token_a_amount * pool.fee_percentage / 10000;
-            let protocol_fee_amount = token_a_amount * pool.protocol_fee_percentage / 10000;
-            let total_fee = lp_fee_amount + protocol_fee_amount;
+            let amount_in = Amount::from(token_a_amount);
+            let lp_fee_amount = u64::from(amount_in.checked_mul(pool.fee_percentage).map_err(overflow)?) / 10000;
+            let protocol_fee_amount = u64::from(amount_in.checked_mul(pool.protocol_fee_percentage).map_err(overflow)?) / 10000;
+            let total_fee = Amount::from(lp_fee_amount).checked_add(protocol_fee_amount).map_err(overflow)?;
             
             // Apply fees
-            let amount_after_fee = token_a_amount - total_fee;
+            let amount_after_fee = amount_in.checked_sub(total_fee).map_err(overflow)?;
             
             // Calculate output amount
-            let new_token_a_reserves = pool.token_a_reserves + amount_after_fee;
+            let new_token_a_reserves = u64::from(Amount::from(pool.token_a_reserves).checked_add(amount_after_fee).map_err(overflow)?);
             let new_token_b_reserves = k / new_token_a_reserves;
             let token_b_out = pool.token_b_reserves - new_token_b_reserves;
             
@@ -377,24 +590,54 @@ pub mod secure {
             pool.token_a_reserves = new_token_a_reserves + lp_fee_amount;
             pool.token_b_reserves = new_token_b_reserves;
             
-            // Send protocol fee to designated recipient
+            // FIXED: Protocol fee accrues in a dedicated ledger until the fee admin withdraws it
             if protocol_fee_amount > 0 {
-                let fee_recipient = self.users.entry(pool.fee_recipient).or_insert(UserAccount {
-                    owner: pool.fee_recipient,
-                    token_a_balance: 0,
-                    token_b_balance: 0,
-                    lp_tokens: 0,
-                });
-                
-                fee_recipient.token_a_balance += protocol_fee_amount;
+                *self.accrued_protocol_fees.entry(TOKEN_A.to_string()).or_insert(0) += protocol_fee_amount;
             }
             
             Ok(token_b_out)
         }
         
-        /// Helper to advance time (for testing)
-        pub fn advance_time(&mut self, seconds: u64) {
-            self.current_time += seconds;
+        /// Move all accrued protocol fees for `token` into the fee admin's account
+        ///
+        /// Returns the amount withdrawn; `Ok(0)` when nothing has accrued.
+        pub fn withdraw_protocol_fees(&mut self, ctx: &TxContext, token: &str) -> Result<u64, &'static str> {
+            // SECURE: Only the fee admin can withdraw protocol fees
+            if !ctx.signature_valid {
+                return Err("Invalid transaction");
+            }
+            if !ctx.is_signed_by(&self.fee_admin) {
+                return Err("Only fee admin can withdraw protocol fees");
+            }
+            if token != TOKEN_A && token != TOKEN_B {
+                return Err("Unknown token");
+            }
+            
+            let amount = self.accrued_protocol_fees.remove(token).unwrap_or(0);
+            if amount == 0 {
+                return Ok(0);
+            }
+            
+            let fee_admin = self.fee_admin;
+            let account = self.users.entry(fee_admin).or_insert(UserAccount {
+                owner: fee_admin,
+                token_a_balance: 0,
+                token_b_balance: 0,
+                lp_tokens: 0,
+            });
+            if token == TOKEN_A {
+                account.token_a_balance += amount;
+            } else {
+                account.token_b_balance += amount;
+            }
+            
+            self.events.emit("ProtocolFeesWithdrawn", &[
+                ("token", token.to_string()),
+                ("amount", amount.to_string()),
+                ("recipient", format_address(&fee_admin)),
+            ], ctx.timestamp);
+            
+            Ok(amount)
         }
     }
 }
@@ -402,6 +645,38 @@ pub mod secure {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::TxContext;
+    
+    /// Second member of the test fee council, alongside the admin
+    const CO_SIGNER: [u8; 32] = [5u8; 32];
+    
+    /// Secure protocol whose fee council is `admin`, `CO_SIGNER` and one more key
+    fn secure_dex(admin: [u8; 32]) -> secure::DexProtocol {
+        secure::DexProtocol::new(admin, [admin, CO_SIGNER, [6u8; 32]]).unwrap()
+    }
+    
+    /// 1M/1M pool charging a 0.3% fee to `fee_recipient`
+    fn vulnerable_pool(fee_recipient: [u8; 32]) -> vulnerable::LiquidityPool {
+        vulnerable::LiquidityPool {
+            token_a_reserves: 1_000_000,
+            token_b_reserves: 1_000_000,
+            fee_percentage: 30,
+            fee_recipient,
+            total_supply: 1_000_000,
+        }
+    }
+    
+    /// 1M/1M pool charging a 0.3% fee to `fee_recipient` plus `protocol_fee_percentage` basis points
+    fn secure_pool(fee_recipient: [u8; 32], protocol_fee_percentage: u64) -> secure::LiquidityPool {
+        secure::LiquidityPool {
+            token_a_reserves: 1_000_000,
+            token_b_reserves: 1_000_000,
+            fee_percentage: 30,
+            fee_recipient,
+            total_supply: 1_000_000,
+            protocol_fee_percentage,
+        }
+    }
     
     #[test]
     fn test_vulnerable_fee_manipulation() {
@@ -411,13 +686,7 @@ mod tests {
         let pool_id = 1;
         
         // Create pool with 0.3% fee
-        dex.pools.insert(pool_id, vulnerable::LiquidityPool {
-            token_a_reserves: 1_000_000,
-            token_b_reserves: 1_000_000,
-            fee_percentage: 30, // 0.3%
-            fee_recipient: [1u8; 32], // Admin
-            total_supply: 1_000_000,
-        });
+        dex.pools.insert(pool_id, vulnerable_pool([1u8; 32]));
         
         // Create user account
         dex.users.insert(user, vulnerable::UserAccount {
@@ -443,20 +712,13 @@ mod tests {
     
     #[test]
     fn test_secure_fee_protection() {
-        let mut dex = secure::DexProtocol::new([1u8; 32]);
+        let mut dex = secure_dex([1u8; 32]);
         let user = [2u8; 32];
         let attacker = [3u8; 32];
         let pool_id = 1;
         
         // Create pool with standard fees
-        dex.pools.insert(pool_id, secure::LiquidityPool {
-            token_a_reserves: 1_000_000,
-            token_b_reserves: 1_000_000,
-            fee_percentage: 30, // 0.3%
-            fee_recipient: [1u8; 32], // Admin
-            total_supply: 1_000_000,
-            protocol_fee_percentage: 5, // 0.05% protocol fee
-        });
+        dex.pools.insert(pool_id, secure_pool([1u8; 32], 5));
         
         // Create user account
         dex.users.insert(user, secure::UserAccount {
@@ -467,29 +729,31 @@ mod tests {
         });
         
         // Attacker cannot change fee recipient
-        let result = dex.set_fee_recipient(attacker, pool_id, attacker);
+        let result = dex.propose_fee_recipient(&TxContext::new(attacker), pool_id, attacker);
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Only fee admin can change fee recipient");
+        assert_eq!(result.unwrap_err(), "Only fee signers can change fee recipient");
         
         // Admin can propose a fee change, but it's timelocked
-        let result = dex.propose_fee_change(dex.fee_admin, pool_id, 40); // 0.4%
+        let admin_tx = TxContext::new(dex.fee_admin);
+        let result = dex.propose_fee_change(&admin_tx, pool_id, 40); // 0.4%
         assert!(result.is_ok());
         
         // Admin cannot set excessive fees
-        let result = dex.propose_fee_change(dex.fee_admin, pool_id, 200); // 2%
+        let result = dex.propose_fee_change(&admin_tx, pool_id, 200); // 2%
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "Fee too high (max 1%)");
         
         // Fee change not applied until timelock expires
-        let result = dex.apply_pending_fee_changes();
+        let result = dex.apply_pending_fee_changes(&TxContext::new(user));
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 0); // No changes applied yet
         
-        // Advance time past the timelock
-        dex.advance_time(90000); // 25 hours
+        // Claiming a far-future time on the transaction doesn't skip the timelock
+        assert_eq!(dex.apply_pending_fee_changes(&TxContext::new(user).at(u64::MAX)), Ok(0));
         
-        // Now the fee change can be applied
-        let result = dex.apply_pending_fee_changes();
+        // Past the timelock (25 hours later), the fee change can be applied
+        dex.advance_time(90000);
+        let result = dex.apply_pending_fee_changes(&TxContext::new(user));
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 1); // 1 change applied
         
@@ -498,4 +762,238 @@ mod tests {
         assert_eq!(pool.fee_percentage, 40); // Updated to 0.4%
         assert_eq!(pool.fee_recipient, [1u8; 32]); // Still the admin
     }
+    
+    #[test]
+    fn test_secure_emits_fee_change_events() {
+        let mut dex = secure_dex([1u8; 32]);
+        let pool_id = 1;
+        dex.pools.insert(pool_id, secure_pool([1u8; 32], 5));
+        
+        dex.propose_fee_change(&TxContext::new(dex.fee_admin), pool_id, 40).unwrap();
+        assert_eq!(dex.events.by_kind("FeeChangeProposed").len(), 1);
+        assert!(dex.events.by_kind("FeeChanged").is_empty());
+        
+        dex.advance_time(90000);
+        dex.apply_pending_fee_changes(&TxContext::new([9u8; 32])).unwrap();
+        
+        let applied = dex.events.by_kind("FeeChanged");
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].field("old_fee"), Some("30"));
+        assert_eq!(applied[0].field("new_fee"), Some("40"));
+        assert_eq!(applied[0].timestamp, 90000);
+    }
+    
+    #[test]
+    fn test_hidden_fee_shorts_vulnerable_swap() {
+        let user = [2u8; 32];
+        let pool_id = 1;
+        
+        let mut vulnerable_dex = vulnerable::DexProtocol::new([1u8; 32]);
+        vulnerable_dex.pools.insert(pool_id, vulnerable_pool([1u8; 32]));
+        vulnerable_dex.users.insert(user, vulnerable::UserAccount {
+            owner: user,
+            token_a_balance: 10_000,
+            token_b_balance: 0,
+            lp_tokens: 0,
+        });
+        
+        // Same documented fee and no protocol fee, so only the hidden fee differs
+        let mut secure_dex = secure_dex([1u8; 32]);
+        secure_dex.pools.insert(pool_id, secure_pool([1u8; 32], 0));
+        secure_dex.users.insert(user, secure::UserAccount {
+            owner: user,
+            token_a_balance: 10_000,
+            token_b_balance: 0,
+            lp_tokens: 0,
+        });
+        
+        assert_eq!(vulnerable_dex.hidden_fee_bps(pool_id, 10_000), 50);
+        let expected = vulnerable_dex.expected_output_no_hidden_fee(pool_id, 10_000).unwrap();
+        let quoted = vulnerable_dex.actual_output(pool_id, 10_000).unwrap();
+        
+        let vulnerable_out = vulnerable_dex.swap(user, pool_id, 10_000).unwrap();
+        let secure_out = secure_dex.swap(user, pool_id, 10_000).unwrap();
+        
+        assert_eq!(vulnerable_out, quoted);
+        assert_eq!(secure_out, expected);
+        assert!(vulnerable_out < secure_out);
+    }
+    
+    #[test]
+    fn test_expected_output_handles_reserves_beyond_u64_product() {
+        let pool_id = 1;
+        let mut dex = vulnerable::DexProtocol::new([1u8; 32]);
+        dex.pools.insert(pool_id, vulnerable::LiquidityPool {
+            token_a_reserves: 1_000_000_000_000,
+            token_b_reserves: 1_000_000_000_000,
+            fee_percentage: 0,
+            total_supply: 1_000_000_000_000,
+            ..vulnerable_pool([1u8; 32])
+        });
+        
+        // k = 10^24 would overflow a u64; 1% more of token A buys just under 1% of token B
+        let expected = dex.expected_output_no_hidden_fee(pool_id, 10_000_000_000).unwrap();
+        assert_eq!(expected, 9_900_990_100);
+    }
+    
+    #[test]
+    fn test_protocol_fees_accrue_and_only_fee_admin_withdraws() {
+        let admin = [1u8; 32];
+        let user = [2u8; 32];
+        let attacker = [3u8; 32];
+        let pool_id = 1;
+        
+        let mut dex = secure_dex(admin);
+        dex.pools.insert(pool_id, secure_pool(admin, 5));
+        dex.users.insert(user, secure::UserAccount {
+            owner: user,
+            token_a_balance: 100_000,
+            token_b_balance: 0,
+            lp_tokens: 0,
+        });
+        
+        // Nothing accrued yet
+        assert_eq!(dex.withdraw_protocol_fees(&TxContext::new(admin), secure::TOKEN_A), Ok(0));
+        
+        // Each 20_000 swap accrues a 10 protocol fee
+        for _ in 0..3 {
+            dex.swap(user, pool_id, 20_000).unwrap();
+        }
+        assert_eq!(dex.accrued_protocol_fees[secure::TOKEN_A], 30);
+        assert!(!dex.users.contains_key(&admin));
+        
+        assert_eq!(dex.withdraw_protocol_fees(&TxContext::new(attacker), secure::TOKEN_A), Err("Only fee admin can withdraw protocol fees"));
+        assert_eq!(dex.accrued_protocol_fees[secure::TOKEN_A], 30);
+        
+        assert_eq!(dex.withdraw_protocol_fees(&TxContext::new(admin), secure::TOKEN_A), Ok(30));
+        assert_eq!(dex.users[&admin].token_a_balance, 30);
+        assert_eq!(dex.withdraw_protocol_fees(&TxContext::new(admin), secure::TOKEN_A), Ok(0));
+    }
+    
+    #[test]
+    fn test_secure_fee_admin_may_co_sign() {
+        let (admin, relayer) = ([1u8; 32], [7u8; 32]);
+        let pool_id = 1;
+        let mut dex = secure_dex(admin);
+        dex.pools.insert(pool_id, secure_pool(admin, 5));
+        
+        let relayed = TxContext::new(relayer).with_signer(admin).with_signer(CO_SIGNER).at(500);
+        assert_eq!(dex.propose_fee_recipient(&TxContext::new(relayer), pool_id, relayer), Err("Only fee signers can change fee recipient"));
+        let proposal_id = dex.propose_fee_recipient(&relayed, pool_id, [8u8; 32]).unwrap();
+        dex.set_fee_recipient(&relayed, proposal_id).unwrap();
+        assert_eq!(dex.pools[&pool_id].fee_recipient, [8u8; 32]);
+        assert_eq!(dex.events.by_kind("FeeRecipientChanged")[0].timestamp, 500);
+        
+        // The timelock runs from the protocol clock, not the transaction's timestamp
+        dex.advance_time(100);
+        dex.propose_fee_change(&relayed, pool_id, 40).unwrap();
+        assert_eq!(dex.pending_fee_changes.pending()[0].1, 100 + secure::FEE_CHANGE_DELAY);
+    }
+    
+    #[test]
+    fn test_secure_rejects_invalid_signatures() {
+        let admin = [1u8; 32];
+        let mut dex = secure_dex(admin);
+        let forged = TxContext { signature_valid: false, ..TxContext::new(admin) };
+        
+        assert_eq!(dex.propose_fee_recipient(&forged, 1, admin), Err("Invalid transaction"));
+        assert_eq!(dex.set_fee_recipient(&forged, 1), Err("Invalid transaction"));
+        assert_eq!(dex.propose_fee_change(&forged, 1, 40), Err("Invalid transaction"));
+        assert_eq!(dex.apply_pending_fee_changes(&forged), Err("Invalid transaction"));
+        assert_eq!(dex.withdraw_protocol_fees(&forged, secure::TOKEN_A), Err("Invalid transaction"));
+    }
+    
+    #[test]
+    fn test_fee_recipient_change_needs_two_of_three_signers() {
+        let (admin, a, b, c) = ([1u8; 32], [2u8; 32], [3u8; 32], [4u8; 32]);
+        let pool_id = 1;
+        let mut dex = secure::DexProtocol::new(admin, [a, b, c]).unwrap();
+        dex.pools.insert(pool_id, secure_pool(admin, 5));
+        
+        // The fee admin alone is no longer enough
+        assert_eq!(dex.propose_fee_recipient(&TxContext::new(admin), pool_id, admin), Err("Only fee signers can change fee recipient"));
+        
+        let proposal_id = dex.propose_fee_recipient(&TxContext::new(a), pool_id, [9u8; 32]).unwrap();
+        assert_eq!(dex.approve_fee_recipient(&TxContext::new(a), proposal_id), Ok(1));
+        assert_eq!(dex.set_fee_recipient(&TxContext::new(a), proposal_id), Err("Not enough approvals"));
+        assert_eq!(dex.pools[&pool_id].fee_recipient, admin);
+        
+        assert_eq!(dex.approve_fee_recipient(&TxContext::new(c), proposal_id), Ok(2));
+        dex.set_fee_recipient(&TxContext::new(b), proposal_id).unwrap();
+        assert_eq!(dex.pools[&pool_id].fee_recipient, [9u8; 32]);
+        assert_eq!(dex.set_fee_recipient(&TxContext::new(a), proposal_id), Err("Proposal not found"));
+        
+        // Two co-signers on one transaction meet the threshold at once
+        let both = TxContext::new(b).with_signer(c);
+        let proposal_id = dex.propose_fee_recipient(&both, pool_id, admin).unwrap();
+        dex.set_fee_recipient(&both, proposal_id).unwrap();
+        assert_eq!(dex.pools[&pool_id].fee_recipient, admin);
+    }
+    
+    #[test]
+    fn test_fee_changes_apply_in_proposal_order_after_delay() {
+        let admin = [1u8; 32];
+        let mut dex = secure_dex(admin);
+        for pool_id in [1, 2] {
+            dex.pools.insert(pool_id, secure_pool(admin, 5));
+        }
+        let tx = TxContext::new(admin);
+        
+        // Two changes to pool 1 and one to pool 2, proposed at different times
+        dex.propose_fee_change(&tx, 1, 40).unwrap();
+        dex.propose_fee_change(&tx, 2, 50).unwrap();
+        dex.advance_time(100);
+        dex.propose_fee_change(&tx, 1, 60).unwrap();
+        
+        dex.advance_time(secure::FEE_CHANGE_DELAY - 101);
+        assert_eq!(dex.apply_pending_fee_changes(&tx), Ok(0));
+        
+        // Both changes proposed at t=0 land together; the later one waits
+        dex.advance_time(1);
+        assert_eq!(dex.apply_pending_fee_changes(&tx), Ok(2));
+        assert_eq!((dex.pools[&1].fee_percentage, dex.pools[&2].fee_percentage), (40, 50));
+        dex.advance_time(100);
+        assert_eq!(dex.apply_pending_fee_changes(&tx), Ok(1));
+        assert_eq!(dex.pools[&1].fee_percentage, 60);
+        assert!(dex.pending_fee_changes.is_empty());
+        
+        let applied: Vec<_> = dex.events.by_kind("FeeChanged").iter()
+            .map(|event| (event.field("pool_id").unwrap().to_string(), event.field("old_fee").unwrap().to_string(), event.timestamp))
+            .collect();
+        assert_eq!(applied, [
+            ("1".to_string(), "30".to_string(), 86400),
+            ("2".to_string(), "30".to_string(), 86400),
+            ("1".to_string(), "40".to_string(), 86500),
+        ]);
+    }
+    
+    #[test]
+    fn test_cancelled_fee_change_never_applies() {
+        let admin = [1u8; 32];
+        let mut dex = secure_dex(admin);
+        for pool_id in [1, 2] {
+            dex.pools.insert(pool_id, secure_pool(admin, 5));
+        }
+        let tx = TxContext::new(admin);
+        
+        assert_eq!(dex.cancel_fee_change(&tx, 1), Ok(0));
+        
+        dex.propose_fee_change(&tx, 1, 90).unwrap();
+        dex.propose_fee_change(&tx, 1, 80).unwrap();
+        dex.propose_fee_change(&tx, 2, 40).unwrap();
+        assert_eq!(dex.cancel_fee_change(&TxContext::new([3u8; 32]), 1), Err("Only fee admin can cancel fee changes"));
+        dex.advance_time(10);
+        assert_eq!(dex.cancel_fee_change(&tx, 1), Ok(2));
+        
+        let cancelled = dex.events.by_kind("FeeChangeCancelled");
+        assert_eq!(cancelled.len(), 2);
+        assert_eq!(cancelled[0].field("new_fee"), Some("90"));
+        assert_eq!(cancelled[1].timestamp, 10);
+        
+        // Only the other pool's change survives the timelock
+        dex.advance_time(secure::FEE_CHANGE_DELAY - 10);
+        assert_eq!(dex.apply_pending_fee_changes(&tx), Ok(1));
+        assert_eq!(dex.pools[&1].fee_percentage, 30);
+        assert_eq!(dex.pools[&2].fee_percentage, 40);
+    }
 }
@@ -8,7 +8,7 @@
 //! In Rust smart contracts, logic errors can manifest as incorrect state transitions,
 //! faulty validation, or improper handling of edge cases.
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 
 /// Represents a logic error vulnerability example
 pub struct LogicErrorVulnerability;
@@ -28,6 +28,18 @@ impl Vulnerability for LogicErrorVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "All Rust-based contracts"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::StateManagement, Category::Economic]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Moderate
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable function with incorrect business logic
@@ -81,6 +93,37 @@ impl Vulnerability for LogicErrorVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure finalization that walks the auction state machine explicitly
+        pub fn finalize_auction(&mut self, auction_id: u64) -> Result<(), &'static str> {
+            let auction = self.auctions.get_mut(&auction_id).ok_or("Auction not found")?;
+            
+            // FIXED: Proper state validation with specific states
+            match auction.state {
+                AuctionState::Initialized => return Err("Auction not started"),
+                AuctionState::Active => {
+                    // An active auction can only be finalized once its time is up
+                    if self.current_time < auction.end_time {
+                        return Err("Auction still active");
+                    }
+                    auction.state = AuctionState::Ended;
+                },
+                AuctionState::Ended => {},
+                AuctionState::Finalized => return Err("Auction already finalized"),
+            }
+            
+            if auction.highest_bidder.is_none() {
+                auction.state = AuctionState::Finalized;
+                return Err("No bids placed, auction closed without winner");
+            }
+            
+            auction.state = AuctionState::Finalized;
+            Ok(())
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Review business logic against functional requirements",
@@ -275,6 +318,12 @@ pub mod vulnerable {
 
 /// Example of secure code with correct business logic
 pub mod secure {
+    /// Total staking rewards emitted per day, shared pro rata by stake
+    pub const REWARD_RATE_PER_DAY: u64 = 100_000;
+    
+    /// Fixed-point scale for the reward-per-token index
+    pub const REWARD_INDEX_PRECISION: u128 = 1_000_000_000_000;
+    
     #[derive(Debug, Clone, PartialEq)]
     pub enum AuctionState {
         Initialized,
@@ -293,6 +342,24 @@ pub mod secure {
         pub highest_bid: u64,
         pub highest_bidder: Option<[u8; 32]>,
         pub state: AuctionState,
+        pub extension_window: u64, // anti-sniping window in seconds, 0 disables it
+        pub max_extensions: u32,
+        pub extensions: u32,
+        pub min_increment_bps: u64, // required raise over the highest bid, in basis points
+        pub pending_returns: std::collections::HashMap<[u8; 32], u64>,
+    }
+    
+    impl Auction {
+        /// Pull-pattern refund for bidders who have been outbid
+        ///
+        /// Refunds are never pushed from `place_bid`, so a bidder that cannot
+        /// receive funds only blocks their own withdrawal, not the auction.
+        pub fn withdraw_pending_return(&mut self, bidder: [u8; 32]) -> Result<u64, &'static str> {
+            match self.pending_returns.remove(&bidder) {
+                Some(amount) if amount > 0 => Ok(amount),
+                _ => Err("No pending return"),
+            }
+        }
     }
     
     #[derive(Debug, Clone)]
@@ -302,6 +369,8 @@ pub mod secure {
         pub reward_balance: u64,
         pub last_claim_time: u64,
         pub accumulated_rewards: u64,
+        pub reward_debt: u128, // reward_per_token_stored when last settled
+        pub unpaid_rewards: u64, // Earned but not paid because the reward pool ran short
     }
     
     pub struct AuctionPlatform {
@@ -310,6 +379,8 @@ pub mod secure {
         pub reward_pool: u64,
         pub current_time: u64,
         pub total_staked: u64,
+        pub reward_per_token_stored: u128, // scaled by REWARD_INDEX_PRECISION
+        pub last_reward_update: u64,
     }
     
     impl AuctionPlatform {
@@ -320,6 +391,8 @@ pub mod secure {
                 reward_pool: 1_000_000,
                 current_time: 0,
                 total_staked: 0,
+                reward_per_token_stored: 0,
+                last_reward_update: 0,
             }
         }
         
@@ -372,59 +445,139 @@ pub mod secure {
                 return Err("Bid too low");
             }
             
+            // FIXED: Outbidding needs a meaningful raise, not a 1-unit nudge
+            if auction.highest_bid > 0 {
+                let required = auction.highest_bid as u128 * (10_000 + auction.min_increment_bps as u128);
+                if (bid_amount as u128) * 10_000 < required {
+                    return Err("increment too small");
+                }
+            }
+            
             if auction.highest_bid == 0 && bid_amount < auction.reserve_price {
                 return Err("Bid below reserve price");
             }
             
+            // FIXED: Credit the outbid amount for later withdrawal; this also
+            // covers a bidder raising their own bid
+            if let Some(previous_bidder) = auction.highest_bidder {
+                let pending = auction.pending_returns.entry(previous_bidder).or_insert(0);
+                *pending = pending.checked_add(auction.highest_bid).ok_or("Pending return overflow")?;
+            }
+            
             // Update highest bid
             auction.highest_bid = bid_amount;
             auction.highest_bidder = Some(bidder);
             
+            // FIXED: A bid inside the closing window pushes the end out so
+            // other bidders get a chance to respond, up to a fixed cap
+            let remaining = auction.end_time - self.current_time;
+            if remaining <= auction.extension_window && auction.extensions < auction.max_extensions {
+                auction.end_time = auction.end_time.saturating_add(auction.extension_window);
+                auction.extensions += 1;
+            }
+            
             Ok(())
         }
         
-        /// Secure reward claim function with correct logic
-        pub fn claim_rewards(&mut self, staker: [u8; 32]) -> Result<u64, &'static str> {
+        /// Fold the rewards emitted since the last update into the index
+        ///
+        /// Must run before anything that changes `total_staked` or reads a
+        /// staker's pending rewards, so each period is split by the stake
+        /// that was actually present during it.
+        fn update_reward_index(&mut self) {
+            let elapsed = self.current_time.saturating_sub(self.last_reward_update);
+            
+            if elapsed > 0 && self.total_staked > 0 {
+                let emitted = REWARD_RATE_PER_DAY as u128 * elapsed as u128 * REWARD_INDEX_PRECISION / 86400;
+                self.reward_per_token_stored += emitted / self.total_staked as u128;
+            }
+            
+            self.last_reward_update = self.current_time;
+        }
+        
+        /// Credit a staker's pending rewards and move their debt up to the current index
+        ///
+        /// Pays out at most what the reward pool holds; the rest is carried
+        /// forward in `unpaid_rewards` and paid once the pool is topped up.
+        /// A shortfall never blocks staking or unstaking.
+        fn settle_rewards(&mut self, staker: [u8; 32]) -> Result<u64, &'static str> {
+            let index = self.reward_per_token_stored;
             let account = match self.staking_accounts.get_mut(&staker) {
                 Some(account) => account,
                 None => return Err("Staking account not found"),
             };
             
-            // Calculate time since last claim
-            let time_delta = self.current_time - account.last_claim_time;
-            
-            // No rewards if no time has passed
-            if time_delta == 0 {
-                return Ok(0);
-            }
-            
-            // FIXED: Improved reward calculation logic
-            // Uses a pro-rated approach based on total staked
-            let daily_reward_rate = 100; // Base rate
-            
-            // Calculate rewards based on proportion of total stake
-            let reward_amount = if self.total_staked > 0 {
-                account.staked_amount * daily_reward_rate * time_delta / 86400
-            } else {
-                0
-            };
+            // FIXED: Rewards are owed only for the index growth since the last settlement
+            let pending = account.staked_amount as u128 * (index - account.reward_debt) / REWARD_INDEX_PRECISION;
+            let owed = u64::try_from(pending).ok()
+                .and_then(|pending| pending.checked_add(account.unpaid_rewards))
+                .ok_or("Reward overflow")?;
             
-            // Check reward pool
-            if reward_amount > self.reward_pool {
-                return Err("Insufficient rewards in pool");
-            }
+            // FIXED: Cap the payout at the pool instead of failing, so an empty
+            // reward pool can't lock stakers' principal
+            let reward_amount = owed.min(self.reward_pool);
+            account.unpaid_rewards = owed - reward_amount;
             
             // Update balances
             self.reward_pool -= reward_amount;
             account.reward_balance += reward_amount;
             account.accumulated_rewards += reward_amount;
             
-            // FIXED: Always update last claim time
+            // FIXED: Always advance the debt and last claim time
+            account.reward_debt = index;
             account.last_claim_time = self.current_time;
             
             Ok(reward_amount)
         }
         
+        /// Stake `amount`, settling any rewards earned on the previous stake first
+        pub fn stake(&mut self, staker: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            self.update_reward_index();
+            
+            if self.staking_accounts.contains_key(&staker) {
+                self.settle_rewards(staker)?;
+            } else {
+                self.staking_accounts.insert(staker, StakingAccount {
+                    owner: staker,
+                    staked_amount: 0,
+                    reward_balance: 0,
+                    last_claim_time: self.current_time,
+                    accumulated_rewards: 0,
+                    reward_debt: self.reward_per_token_stored,
+                    unpaid_rewards: 0,
+                });
+            }
+            
+            let new_total = self.total_staked.checked_add(amount).ok_or("Stake overflow")?;
+            let account = self.staking_accounts.get_mut(&staker).unwrap();
+            account.staked_amount += amount;
+            self.total_staked = new_total;
+            
+            Ok(())
+        }
+        
+        /// Unstake `amount`, settling rewards earned on the full stake first
+        pub fn unstake(&mut self, staker: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            self.update_reward_index();
+            self.settle_rewards(staker)?;
+            
+            let account = self.staking_accounts.get_mut(&staker).unwrap();
+            if account.staked_amount < amount {
+                return Err("Insufficient stake");
+            }
+            
+            account.staked_amount -= amount;
+            self.total_staked -= amount;
+            
+            Ok(())
+        }
+        
+        /// Secure reward claim function using a reward-per-token index
+        pub fn claim_rewards(&mut self, staker: [u8; 32]) -> Result<u64, &'static str> {
+            self.update_reward_index();
+            self.settle_rewards(staker)
+        }
+        
         /// Secure function with correct finality logic
         pub fn finalize_auction(&mut self, auction_id: u64) -> Result<(), &'static str> {
             let auction = match self.auctions.get_mut(&auction_id) {
@@ -469,6 +622,12 @@ pub mod secure {
         pub fn advance_time(&mut self, seconds: u64) {
             self.current_time += seconds;
         }
+        
+        /// Take the current time from a shared `MockBlockchain` instead of
+        /// tracking it locally, refusing to move the clock backwards
+        pub fn sync_clock(&mut self, chain: &crate::utils::MockBlockchain) -> Result<(), crate::Error> {
+            chain.sync_clock(&mut self.current_time)
+        }
     }
 }
 
@@ -547,24 +706,33 @@ mod tests {
         assert_eq!(rewards2, 100_000); // Same amount again
     }
     
+    /// Auction of item 1 by `[1u8; 32]` running from 100 to 200, with no extensions or minimum increment
+    fn auction(reserve_price: u64) -> secure::Auction {
+        secure::Auction {
+            item_id: 1,
+            creator: [1u8; 32],
+            start_time: 100,
+            end_time: 200,
+            reserve_price,
+            highest_bid: 0,
+            highest_bidder: None,
+            state: secure::AuctionState::Initialized,
+            extension_window: 0,
+            max_extensions: 0,
+            extensions: 0,
+            min_increment_bps: 0,
+            pending_returns: std::collections::HashMap::new(),
+        }
+    }
+    
     #[test]
     fn test_secure_auction_logic() {
         let mut platform = secure::AuctionPlatform::new();
         let auction_id = 1;
-        let creator = [1u8; 32];
         let bidder = [2u8; 32];
         
         // Create auction
-        platform.auctions.insert(auction_id, secure::Auction {
-            item_id: 1,
-            creator,
-            start_time: 100,
-            end_time: 200,
-            reserve_price: 100,
-            highest_bid: 0,
-            highest_bidder: None,
-            state: secure::AuctionState::Initialized,
-        });
+        platform.auctions.insert(auction_id, auction(100));
         
         // Try to start auction before start time
         platform.current_time = 50;
@@ -611,6 +779,8 @@ mod tests {
             reward_balance: 0,
             last_claim_time: 0,
             accumulated_rewards: 0,
+            reward_debt: 0,
+            unpaid_rewards: 0,
         });
         
         // Advance time 1 day
@@ -628,4 +798,179 @@ mod tests {
         let rewards2 = result.unwrap();
         assert_eq!(rewards2, 0); // No new rewards since last claim
     }
+    
+    #[test]
+    fn test_secure_staking_rewards_are_pro_rata() {
+        let mut platform = secure::AuctionPlatform::new();
+        let alice = [1u8; 32];
+        let bob = [2u8; 32];
+        
+        // Alice stakes alone for the first day
+        platform.stake(alice, 1000).unwrap();
+        platform.advance_time(86400);
+        
+        // Bob joins with an equal stake for the second day
+        platform.stake(bob, 1000).unwrap();
+        platform.advance_time(86400);
+        
+        // Alice earns all of day one and half of day two; Bob only half of day two
+        assert_eq!(platform.claim_rewards(alice), Ok(150_000));
+        assert_eq!(platform.claim_rewards(bob), Ok(50_000));
+        
+        // Two days of emissions were paid out exactly once
+        assert_eq!(platform.reward_pool, 1_000_000 - 200_000);
+        assert_eq!(platform.claim_rewards(alice), Ok(0));
+        
+        // After Alice unstakes, Bob earns the whole emission
+        platform.unstake(alice, 1000).unwrap();
+        platform.advance_time(86400);
+        assert_eq!(platform.claim_rewards(alice), Ok(0));
+        assert_eq!(platform.claim_rewards(bob), Ok(100_000));
+        assert_eq!(platform.unstake(alice, 1), Err("Insufficient stake"));
+    }
+    
+    #[test]
+    fn test_secure_unstake_succeeds_against_empty_reward_pool() {
+        let mut platform = secure::AuctionPlatform::new();
+        let staker = [1u8; 32];
+        platform.reward_pool = 30_000;
+        
+        platform.stake(staker, 1000).unwrap();
+        platform.advance_time(86400);
+        
+        // 100k earned but only 30k available: the pool is drained, not the call failed
+        assert_eq!(platform.claim_rewards(staker), Ok(30_000));
+        assert_eq!(platform.reward_pool, 0);
+        assert_eq!(platform.staking_accounts[&staker].unpaid_rewards, 70_000);
+        
+        // Principal still moves while the pool is empty
+        platform.advance_time(86400);
+        platform.stake(staker, 500).unwrap();
+        platform.unstake(staker, 1500).unwrap();
+        assert_eq!(platform.total_staked, 0);
+        assert_eq!(platform.staking_accounts[&staker].unpaid_rewards, 170_000);
+        
+        // The carried-forward rewards are paid once the pool is topped up
+        platform.reward_pool = 1_000_000;
+        assert_eq!(platform.claim_rewards(staker), Ok(170_000));
+        assert_eq!(platform.staking_accounts[&staker].unpaid_rewards, 0);
+    }
+    
+    #[test]
+    fn test_secure_auction_late_bids_extend_until_cap() {
+        let mut platform = secure::AuctionPlatform::new();
+        let auction_id = 1;
+        
+        platform.auctions.insert(auction_id, secure::Auction {
+            extension_window: 10,
+            max_extensions: 3,
+            ..auction(100)
+        });
+        
+        platform.current_time = 100;
+        platform.start_auction(auction_id).unwrap();
+        
+        // A bid well before the window leaves the end time alone
+        platform.place_bid(auction_id, [2u8; 32], 100).unwrap();
+        assert_eq!(platform.auctions[&auction_id].end_time, 200);
+        
+        // Each snipe one second before the close extends the auction
+        for (i, bid) in [110u64, 120, 130].into_iter().enumerate() {
+            platform.current_time = platform.auctions[&auction_id].end_time - 1;
+            platform.place_bid(auction_id, [3 + i as u8; 32], bid).unwrap();
+            assert_eq!(platform.auctions[&auction_id].end_time, 200 + 10 * (i as u64 + 1));
+        }
+        assert_eq!(platform.auctions[&auction_id].extensions, 3);
+        
+        // The cap is reached, so a further late bid no longer extends
+        platform.current_time = 229;
+        platform.place_bid(auction_id, [9u8; 32], 140).unwrap();
+        assert_eq!(platform.auctions[&auction_id].end_time, 230);
+        assert_eq!(platform.auctions[&auction_id].extensions, 3);
+        
+        // Finalization waits for the extended end time
+        platform.current_time = 225;
+        assert_eq!(platform.finalize_auction(auction_id), Err("Auction still active"));
+        
+        platform.current_time = 230;
+        assert_eq!(platform.place_bid(auction_id, [10u8; 32], 150), Err("Auction has ended"));
+        assert!(platform.finalize_auction(auction_id).is_ok());
+        assert_eq!(platform.auctions[&auction_id].highest_bidder, Some([9u8; 32]));
+        assert_eq!(platform.auctions[&auction_id].highest_bid, 140);
+    }
+    
+    #[test]
+    fn test_secure_auction_min_bid_increment() {
+        let mut platform = secure::AuctionPlatform::new();
+        let auction_id = 1;
+        
+        platform.auctions.insert(auction_id, secure::Auction {
+            min_increment_bps: 500, // 5%
+            ..auction(1_000)
+        });
+        
+        platform.current_time = 100;
+        platform.start_auction(auction_id).unwrap();
+        
+        // The first bid only has to meet the reserve
+        assert_eq!(platform.place_bid(auction_id, [2u8; 32], 999), Err("Bid below reserve price"));
+        assert!(platform.place_bid(auction_id, [2u8; 32], 1_000).is_ok());
+        
+        // A 1-unit raise is rejected, as is anything short of 5%
+        assert_eq!(platform.place_bid(auction_id, [3u8; 32], 1_001), Err("increment too small"));
+        assert_eq!(platform.place_bid(auction_id, [3u8; 32], 1_049), Err("increment too small"));
+        assert_eq!(platform.auctions[&auction_id].highest_bid, 1_000);
+        
+        // Exactly 5% more is accepted
+        assert!(platform.place_bid(auction_id, [3u8; 32], 1_050).is_ok());
+        assert_eq!(platform.auctions[&auction_id].highest_bidder, Some([3u8; 32]));
+    }
+    
+    #[test]
+    fn test_secure_auction_outbid_refunds_are_withdrawn() {
+        let mut platform = secure::AuctionPlatform::new();
+        let auction_id = 1;
+        let alice = [2u8; 32];
+        let bob = [3u8; 32];
+        
+        platform.auctions.insert(auction_id, auction(100));
+        
+        platform.current_time = 100;
+        platform.start_auction(auction_id).unwrap();
+        
+        platform.place_bid(auction_id, alice, 100).unwrap();
+        // Alice raises her own bid; her superseded 100 is credited once
+        platform.place_bid(auction_id, alice, 150).unwrap();
+        platform.place_bid(auction_id, bob, 200).unwrap();
+        
+        let auction = platform.auctions.get_mut(&auction_id).unwrap();
+        assert_eq!(auction.pending_returns.get(&alice), Some(&250));
+        assert_eq!(auction.pending_returns.get(&bob), None);
+        
+        assert_eq!(auction.withdraw_pending_return(alice), Ok(250));
+        assert_eq!(auction.withdraw_pending_return(alice), Err("No pending return"));
+        // The current highest bidder has nothing to withdraw
+        assert_eq!(auction.withdraw_pending_return(bob), Err("No pending return"));
+        assert_eq!(auction.highest_bid, 200);
+    }
+    
+    #[test]
+    fn test_auction_sync_clock_never_moves_backwards() {
+        let mut chain = crate::utils::MockBlockchain::new();
+        let mut platform = secure::AuctionPlatform::new();
+        platform.sync_clock(&chain).unwrap();
+        assert_eq!(platform.current_time, chain.unix_timestamp());
+        
+        platform.advance_time(100);
+        let ahead = platform.current_time;
+        assert!(matches!(
+            platform.sync_clock(&chain),
+            Err(crate::Error::ClockRegression { current, .. }) if current == ahead
+        ));
+        assert_eq!(platform.current_time, ahead);
+        
+        chain.advance_slots(150);
+        platform.sync_clock(&chain).unwrap();
+        assert_eq!(platform.current_time, ahead + 50);
+    }
 }
@@ -22,6 +22,42 @@ pub mod front_running;
 pub mod inadequate_events;
 pub mod storage_management;
 
+/// Broad class a vulnerability belongs to, for filtering the catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Category {
+    DeFi,
+    Cryptography,
+    AccessControl,
+    Economic,
+    Arithmetic,
+    InputValidation,
+    StateManagement,
+    Availability,
+    Observability,
+}
+
+/// How much skill an attacker needs to exploit a vulnerability, easiest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Exploitability {
+    /// A single ordinary call with crafted arguments
+    Trivial,
+    /// Several coordinated calls, or a contract built for the attack
+    Moderate,
+    /// Capital, transaction ordering or market conditions as well as code
+    Advanced,
+}
+
+/// How much damage a successful exploit does, most severe first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Severity {
+    /// Funds can be stolen or permanently locked
+    High,
+    /// Funds are at risk only in some states, or the protocol can be disrupted
+    Medium,
+    /// Users or monitors are misled, but funds stay safe
+    Low,
+}
+
 /// Common trait for all vulnerability examples
 pub trait Vulnerability {
     /// Name of the vulnerability
@@ -33,12 +69,155 @@ pub trait Vulnerability {
     /// Platforms where this vulnerability is commonly found
     fn affected_platforms(&self) -> Vec<&'static str>;
     
+    /// Categories this vulnerability falls under
+    fn categories(&self) -> Vec<Category>;
+    
+    /// How hard the vulnerability is to exploit
+    fn exploitability(&self) -> Exploitability;
+    
+    /// How much damage a successful exploit does
+    fn severity(&self) -> Severity;
+    
     /// Example of exploiting the vulnerability
     fn exploit_example(&self) -> &'static str;
     
+    /// The corrected version of the exploited code, from the `secure` module
+    fn secure_example(&self) -> &'static str;
+    
     /// Detection methods for auditors
     fn detection_methods(&self) -> Vec<&'static str>;
     
     /// Remediation strategies
     fn remediation(&self) -> Vec<&'static str>;
+    
+    /// Run the attack against the `vulnerable` module, if a simulation exists
+    fn simulate(&self) -> Option<SimulationResult> {
+        None
+    }
+}
+
+/// Transcript of an attack simulated against a vulnerable implementation
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SimulationResult {
+    /// Human-readable steps, including the state before and after the attack
+    pub steps: Vec<String>,
+    /// Whether the attack achieved its goal
+    pub succeeded: bool,
+}
+
+impl SimulationResult {
+    /// Record a step in the transcript
+    pub fn step(&mut self, description: impl Into<String>) {
+        self.steps.push(description.into());
+    }
+}
+
+/// Platform entries that apply to every platform query
+const WILDCARD_PLATFORMS: [&str; 3] = [
+    "All DeFi platforms",
+    "All Rust-based contracts",
+    "All blockchain platforms",
+];
+
+/// Short command-line name of every vulnerability in the catalog, in catalog order
+pub const VULNERABILITY_SLUGS: [&str; 15] = [
+    "reentrancy",
+    "overflow",
+    "unchecked",
+    "oracle",
+    "access",
+    "dos",
+    "fee",
+    "flash",
+    "logic",
+    "random",
+    "signature",
+    "account_confusion",
+    "front_running",
+    "inadequate_events",
+    "storage",
+];
+
+/// Every vulnerability in the catalog
+pub fn all_vulnerabilities() -> Vec<Box<dyn Vulnerability>> {
+    VULNERABILITY_SLUGS.iter()
+        .map(|slug| vulnerability_by_slug(slug).expect("every listed slug is registered"))
+        .collect()
+}
+
+/// Look up a vulnerability by its short command-line name, e.g. `reentrancy` or `dos` (case-insensitive)
+pub fn vulnerability_by_slug(slug: &str) -> Option<Box<dyn Vulnerability>> {
+    let vuln: Box<dyn Vulnerability> = match slug.to_lowercase().as_str() {
+        "reentrancy" => Box::new(reentrancy::ReentrancyVulnerability),
+        "overflow" => Box::new(overflow::OverflowVulnerability),
+        "unchecked" => Box::new(unchecked_inputs::UncheckedInputsVulnerability),
+        "oracle" => Box::new(oracle_manipulation::OracleManipulationVulnerability),
+        "access" => Box::new(access_control::AccessControlVulnerability),
+        "dos" => Box::new(denial_of_service::DoSVulnerability),
+        "fee" => Box::new(illicit_fee_collection::IllicitFeeVulnerability),
+        "flash" => Box::new(flash_loan::FlashLoanVulnerability),
+        "logic" => Box::new(logic_errors::LogicErrorVulnerability),
+        "random" => Box::new(random_manipulation::RandomManipulationVulnerability),
+        "signature" => Box::new(signature_verification::SignatureVerificationVulnerability),
+        "account_confusion" => Box::new(account_confusion::AccountConfusionVulnerability),
+        "front_running" => Box::new(front_running::FrontRunningVulnerability),
+        "inadequate_events" => Box::new(inadequate_events::InadequateEventsVulnerability),
+        "storage" => Box::new(storage_management::StorageManagementVulnerability),
+        _ => return None,
+    };
+    Some(vuln)
+}
+
+/// Vulnerabilities affecting `platform` (case-insensitive)
+///
+/// Catch-all entries such as "All DeFi platforms" match any platform.
+pub fn vulnerabilities_for_platform(platform: &str) -> Vec<Box<dyn Vulnerability>> {
+    all_vulnerabilities()
+        .into_iter()
+        .filter(|vuln| {
+            vuln.affected_platforms().iter().any(|affected| {
+                affected.eq_ignore_ascii_case(platform) || WILDCARD_PLATFORMS.contains(affected)
+            })
+        })
+        .collect()
+}
+
+/// Vulnerabilities tagged with `category`
+pub fn vulnerabilities_in_category(category: Category) -> Vec<Box<dyn Vulnerability>> {
+    all_vulnerabilities()
+        .into_iter()
+        .filter(|vuln| vuln.categories().contains(&category))
+        .collect()
+}
+
+/// The catalog ordered for study, easiest to exploit first
+///
+/// Vulnerabilities of equal exploitability are ordered most severe first, and
+/// keep their catalog order when severity ties too.
+pub fn learning_path() -> Vec<Box<dyn Vulnerability>> {
+    let mut path = all_vulnerabilities();
+    path.sort_by_key(|vuln| (vuln.exploitability(), vuln.severity()));
+    path
+}
+
+/// Vulnerabilities whose writeup mentions every word of `query` (case-insensitive)
+///
+/// Name, description, detection methods and remediation are searched; a word
+/// may match in any of them. An empty query matches the whole catalog.
+pub fn search_vulnerabilities(query: &str) -> Vec<Box<dyn Vulnerability>> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    
+    all_vulnerabilities()
+        .into_iter()
+        .filter(|vuln| {
+            let mut text = format!("{}\n{}", vuln.name(), vuln.description());
+            for line in vuln.detection_methods().into_iter().chain(vuln.remediation()) {
+                text.push('\n');
+                text.push_str(line);
+            }
+            let text = text.to_lowercase();
+            
+            words.iter().all(|word| text.contains(word.as_str()))
+        })
+        .collect()
 }
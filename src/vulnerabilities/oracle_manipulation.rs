@@ -6,7 +6,7 @@
 //!
 //! This is particularly important in DeFi applications on any blockchain platform.
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 
 /// Represents an oracle manipulation vulnerability example
 pub struct OracleManipulationVulnerability;
@@ -26,6 +26,18 @@ impl Vulnerability for OracleManipulationVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "All DeFi platforms"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::DeFi, Category::Economic]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Advanced
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable function that relies on a single oracle
@@ -63,6 +75,34 @@ impl Vulnerability for OracleManipulationVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure oracle reads: stale prices are refused and settlement uses a TWAP
+        pub fn get_price(&self, current_time: u64) -> Result<u64, &'static str> {
+            // FIXED: Refuse to serve prices while the circuit breaker is tripped
+            if self.circuit_breaker.as_ref().is_some_and(|breaker| breaker.halted) {
+                return Err("oracle halted");
+            }
+            
+            // FIXED: Check if the price data is stale
+            const MAX_AGE: u64 = 300; // 5 minutes
+            if current_time.saturating_sub(self.last_update_time) > MAX_AGE {
+                return Err("Oracle data is stale");
+            }
+            
+            Ok(self.latest_price)
+        }
+        
+        pub fn get_twap(&self, period: u64, current_time: u64) -> Result<u64, &'static str> {
+            // FIXED: Weight each price by how long it held over the window, so a
+            // price that lasted one manipulated block barely moves the average
+            let window_start = current_time.saturating_sub(period);
+            time_weighted_average(self.historical_prices.iter().copied(), window_start, current_time)
+                .ok_or("Insufficient historical price data")
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Check if the contract relies on a single oracle for critical price data",
@@ -166,11 +206,29 @@ pub mod vulnerable {
 
 /// Example of secure code that prevents oracle manipulation
 pub mod secure {
+    use crate::utils::{median_price, time_weighted_average};
+    
+    /// Minimum number of fresh oracles needed to price a liquidation
+    pub const ORACLE_QUORUM: usize = 3;
+    
+    /// Halts an oracle instead of clamping when a price moves too far
+    #[derive(Debug, Clone)]
+    pub struct CircuitBreaker {
+        pub max_change_percentage: u64,
+        pub halted: bool,
+        pub admin: [u8; 32], // Only account allowed to resume a halted oracle
+    }
+    
     #[derive(Debug, Clone)]
     pub struct PriceOracle {
         pub latest_price: u64,
         pub historical_prices: Vec<(u64, u64)>, // (timestamp, price)
         pub last_update_time: u64,
+        pub circuit_breaker: Option<CircuitBreaker>,
+        /// Incremented by every accepted `update_price`; the initial price is round 0
+        pub round_id: u64,
+        /// Longest the feed may go without a new round, if it publishes on a schedule
+        pub heartbeat: Option<u64>,
     }
     
     impl PriceOracle {
@@ -179,47 +237,98 @@ pub mod secure {
                 latest_price: initial_price,
                 historical_prices: vec![(0, initial_price)],
                 last_update_time: 0,
+                circuit_breaker: None,
+                round_id: 0,
+                heartbeat: None,
             }
         }
         
+        /// Treat the feed as stuck once `interval` passes without a new round
+        pub fn with_heartbeat(mut self, interval: u64) -> Self {
+            self.heartbeat = Some(interval);
+            self
+        }
+        
+        /// `(round_id, price, timestamp)` of the latest round
+        pub fn latest_round(&self) -> (u64, u64, u64) {
+            (self.round_id, self.latest_price, self.last_update_time)
+        }
+        
+        /// Halt on deviations above `max_pct` instead of clamping them; only `admin` may resume
+        pub fn with_circuit_breaker(mut self, max_pct: u64, admin: [u8; 32]) -> Self {
+            self.circuit_breaker = Some(CircuitBreaker {
+                max_change_percentage: max_pct,
+                halted: false,
+                admin,
+            });
+            self
+        }
+        
+        /// Resume reads after a halt, once the cause of the deviation has been investigated
+        pub fn reset_breaker(&mut self, caller: [u8; 32]) -> Result<(), &'static str> {
+            if let Some(breaker) = &mut self.circuit_breaker {
+                // FIXED: Otherwise anyone could lift the halt and trade on the manipulated price
+                if caller != breaker.admin {
+                    return Err("Only the breaker admin can reset it");
+                }
+                breaker.halted = false;
+            }
+            
+            Ok(())
+        }
+        
         /// Get current price with staleness check
         pub fn get_price(&self, current_time: u64) -> Result<u64, &'static str> {
+            // FIXED: Refuse to serve prices while the circuit breaker is tripped
+            if self.circuit_breaker.as_ref().is_some_and(|breaker| breaker.halted) {
+                return Err("oracle halted");
+            }
+            
             // FIXED: Check if the price data is stale
             const MAX_AGE: u64 = 300; // 5 minutes
             
-            if current_time - self.last_update_time > MAX_AGE {
+            if current_time.saturating_sub(self.last_update_time) > MAX_AGE {
                 return Err("Oracle data is stale");
             }
             
+            // FIXED: A feed that publishes every `heartbeat` seconds is stuck if it
+            // has not started a round within that window, even though its last
+            // price is not yet MAX_AGE old. Round 0 is only the seed price.
+            if let Some(heartbeat) = self.heartbeat {
+                let (round_id, _, round_time) = self.latest_round();
+                if round_id == 0 || current_time.saturating_sub(round_time) > heartbeat {
+                    return Err("oracle round is stuck");
+                }
+            }
+            
             Ok(self.latest_price)
         }
         
         /// Get TWAP (Time-Weighted Average Price)
         pub fn get_twap(&self, period: u64, current_time: u64) -> Result<u64, &'static str> {
-            // FIXED: Get time-weighted average price over a period
-            let min_time = current_time.saturating_sub(period);
-            
-            let relevant_prices: Vec<(u64, u64)> = self
-                .historical_prices
-                .iter()
-                .filter(|&&(timestamp, _)| timestamp >= min_time)
-                .cloned()
-                .collect();
-            
-            if relevant_prices.is_empty() {
-                return Err("Insufficient historical price data");
-            }
-            
-            // Simplified TWAP calculation
-            let sum: u64 = relevant_prices.iter().map(|&(_, price)| price).sum();
-            Ok(sum / relevant_prices.len() as u64)
+            // FIXED: Weight each price by how long it held over the window, so a
+            // price that lasted one manipulated block barely moves the average
+            let window_start = current_time.saturating_sub(period);
+            time_weighted_average(self.historical_prices.iter().copied(), window_start, current_time)
+                .ok_or("Insufficient historical price data")
         }
         
         pub fn update_price(&mut self, new_price: u64, timestamp: u64) {
             // FIXED: Check for extreme price movements
             const MAX_PRICE_CHANGE_PERCENTAGE: u64 = 20; // 20%
             
-            if !self.historical_prices.is_empty() {
+            if let Some(breaker) = &mut self.circuit_breaker {
+                // Halt mode: reject the deviating price outright rather than
+                // accepting a clamped version of it
+                if let Some(&(_, last_price)) = self.historical_prices.last() {
+                    let change = new_price.abs_diff(last_price) * 100 / last_price.max(1);
+                    if change > breaker.max_change_percentage {
+                        breaker.halted = true;
+                        return;
+                    }
+                }
+                self.latest_price = new_price;
+            } else if !self.historical_prices.is_empty() {
                 let last_price = self.historical_prices.last().unwrap().1;
                 
                 // Calculate percentage change
@@ -246,6 +355,7 @@ pub mod secure {
             
             self.historical_prices.push((timestamp, self.latest_price));
             self.last_update_time = timestamp;
+            self.round_id += 1;
             
             // Keep only recent history (e.g., last 24 hours)
             const HISTORY_RETENTION: u64 = 86400; // 24 hours
@@ -254,6 +364,23 @@ pub mod secure {
         }
     }
     
+    /// Health ratio, in basis points, below which a position may be liquidated
+    pub const LIQUIDATION_THRESHOLD_BPS: u64 = 11_000;
+    
+    /// Collateral value at `token_price` as a share of the loan, in basis points
+    ///
+    /// Computed in u128 so large positions can't overflow; saturates at
+    /// `u64::MAX`, which is also the ratio of a position with no loan.
+    fn health_ratio(position: &LendingPosition, token_price: u64) -> u64 {
+        if position.loan_amount == 0 {
+            return u64::MAX;
+        }
+        
+        let collateral_value = position.collateral_amount as u128 * token_price as u128;
+        let ratio = collateral_value * 10_000 / position.loan_amount as u128;
+        u64::try_from(ratio).unwrap_or(u64::MAX)
+    }
+    
     #[derive(Debug, Clone)]
     pub struct LendingPosition {
         pub owner: [u8; 32],
@@ -262,17 +389,21 @@ pub mod secure {
     }
     
     pub struct LendingProtocol {
-        pub primary_oracle: PriceOracle,
-        pub backup_oracle: PriceOracle,
+        pub oracles: Vec<PriceOracle>,
         pub positions: std::collections::HashMap<[u8; 32], LendingPosition>,
         pub current_time: u64,
     }
     
     impl LendingProtocol {
+        /// Create a protocol backed by `ORACLE_QUORUM` independent feeds
         pub fn new(initial_price: u64) -> Self {
+            Self::with_oracles(vec![PriceOracle::new(initial_price); ORACLE_QUORUM])
+        }
+        
+        /// Create a protocol backed by the given oracle feeds
+        pub fn with_oracles(oracles: Vec<PriceOracle>) -> Self {
             Self {
-                primary_oracle: PriceOracle::new(initial_price),
-                backup_oracle: PriceOracle::new(initial_price),
+                oracles,
                 positions: std::collections::HashMap::new(),
                 current_time: 0,
             }
@@ -285,50 +416,70 @@ pub mod secure {
                 None => return Err("Position not found"),
             };
             
-            // FIXED: Use multiple sources and TWAP
-            
-            // Get primary oracle price with staleness check
-            let primary_price = match self.primary_oracle.get_price(self.current_time) {
-                Ok(price) => price,
-                Err(_) => {
-                    // Fallback to backup oracle if primary is stale
-                    match self.backup_oracle.get_price(self.current_time) {
-                        Ok(price) => price,
-                        Err(_) => return Err("All oracle data is stale"),
-                    }
-                }
-            };
-            
-            // Get TWAP for more stability
-            const TWAP_PERIOD: u64 = 3600; // 1 hour
-            let twap_price = self.primary_oracle.get_twap(TWAP_PERIOD, self.current_time)
-                .unwrap_or(primary_price); // Fallback to spot price if TWAP fails
-            
-            // Use the more conservative price (lower price is worse for the borrower)
-            let token_price = std::cmp::min(primary_price, twap_price);
-            
-            let collateral_value = position.collateral_amount * token_price;
-            let loan_value = position.loan_amount;
+            let token_price = self.conservative_price()?;
             
             // If collateral value falls below threshold, liquidate
-            if collateral_value < loan_value * 110 / 100 {
+            if health_ratio(position, token_price) < LIQUIDATION_THRESHOLD_BPS {
                 // Liquidation logic
                 self.positions.remove(&position_id);
-                return Ok(());
+                Ok(())
             } else {
-                return Err("Position is not eligible for liquidation");
+                Err("Position is not eligible for liquidation")
             }
         }
         
-        /// Functions to update oracle prices (for testing)
-        pub fn update_primary_oracle_price(&mut self, new_price: u64) {
-            self.current_time += 1;
-            self.primary_oracle.update_price(new_price, self.current_time);
+        /// Collateral value as a share of loan value, in basis points
+        ///
+        /// Priced the same way as `liquidate_position`, which liquidates below
+        /// `LIQUIDATION_THRESHOLD_BPS`. A position with no loan returns `u64::MAX`.
+        pub fn health_factor(&self, position_id: [u8; 32]) -> Result<u64, &'static str> {
+            let position = self.positions.get(&position_id).ok_or("Position not found")?;
+            if position.loan_amount == 0 {
+                return Ok(u64::MAX);
+            }
+            
+            Ok(health_ratio(position, self.conservative_price()?))
+        }
+        
+        /// Price used to value collateral: the lower of the median spot and
+        /// median TWAP across fresh oracles
+        fn conservative_price(&self) -> Result<u64, &'static str> {
+            // FIXED: Use the median of several independent sources and TWAP
+            const TWAP_PERIOD: u64 = 3600; // 1 hour
+            
+            // Only oracles that pass the staleness check get a vote
+            let mut spot_prices = Vec::new();
+            let mut twap_prices = Vec::new();
+            for oracle in &self.oracles {
+                if let Ok(price) = oracle.get_price(self.current_time) {
+                    spot_prices.push(price);
+                    // Fallback to spot price if TWAP fails
+                    twap_prices.push(oracle.get_twap(TWAP_PERIOD, self.current_time).unwrap_or(price));
+                }
+            }
+            
+            if spot_prices.len() < ORACLE_QUORUM {
+                return Err("Not enough fresh oracles for a quorum");
+            }
+            
+            // A single manipulated feed can't move the median
+            let spot_price = median_price(&spot_prices).ok_or("No oracle prices")?;
+            let twap_price = median_price(&twap_prices).ok_or("No oracle prices")?;
+            
+            // Use the more conservative price (lower price is worse for the borrower)
+            Ok(std::cmp::min(spot_price, twap_price))
         }
         
-        pub fn update_backup_oracle_price(&mut self, new_price: u64) {
+        /// Function to update one oracle's price (for testing)
+        pub fn update_oracle_price(&mut self, oracle_index: usize, new_price: u64) {
             self.current_time += 1;
-            self.backup_oracle.update_price(new_price, self.current_time);
+            self.oracles[oracle_index].update_price(new_price, self.current_time);
+        }
+        
+        /// Take the current time from a shared `MockBlockchain` instead of
+        /// tracking it locally, refusing to move the clock backwards
+        pub fn sync_clock(&mut self, chain: &crate::utils::MockBlockchain) -> Result<(), crate::Error> {
+            chain.sync_clock(&mut self.current_time)
         }
     }
 }
@@ -375,7 +526,7 @@ mod tests {
         });
         
         // Try to manipulate the price with a large drop
-        protocol.update_primary_oracle_price(45);
+        protocol.update_oracle_price(0, 45);
         
         // Cannot liquidate because the price change was too extreme and was dampened
         // Also, the TWAP will take into account the previous prices
@@ -385,11 +536,185 @@ mod tests {
         // Even with a sustained attack over multiple blocks, the dampening
         // and TWAP mechanisms make it much harder to manipulate the price
         for _ in 0..5 {
-            protocol.update_primary_oracle_price(45);
+            protocol.update_oracle_price(0, 45);
         }
         
         // It would take much longer to drop the price enough for liquidation
         let result = protocol.liquidate_position(position_id);
         assert!(result.is_err());
     }
+    
+    #[test]
+    fn test_secure_median_ignores_outlier_oracle() {
+        // One feed reports a wildly manipulated price
+        let mut protocol = secure::LendingProtocol::with_oracles(vec![
+            secure::PriceOracle::new(100),
+            secure::PriceOracle::new(1),
+            secure::PriceOracle::new(102),
+        ]);
+        let position_id = [1u8; 32];
+        
+        // Healthy at ~100, liquidatable if the outlier price of 1 were used
+        protocol.positions.insert(position_id, secure::LendingPosition {
+            owner: position_id,
+            collateral_amount: 2,
+            loan_amount: 100,
+        });
+        
+        let result = protocol.liquidate_position(position_id);
+        assert_eq!(result, Err("Position is not eligible for liquidation"));
+    }
+    
+    #[test]
+    fn test_secure_requires_oracle_quorum() {
+        let mut protocol = secure::LendingProtocol::new(100);
+        let position_id = [1u8; 32];
+        protocol.positions.insert(position_id, secure::LendingPosition {
+            owner: position_id,
+            collateral_amount: 200,
+            loan_amount: 100,
+        });
+        
+        // Only one oracle reports after the others go stale
+        protocol.current_time = 1_000;
+        protocol.update_oracle_price(0, 100);
+        
+        let result = protocol.liquidate_position(position_id);
+        assert_eq!(result, Err("Not enough fresh oracles for a quorum"));
+    }
+    
+    #[test]
+    fn test_circuit_breaker_halts_and_resets() {
+        let admin = [9u8; 32];
+        let mut oracle = secure::PriceOracle::new(100).with_circuit_breaker(50, admin);
+        
+        // Moves within the threshold are accepted as-is, not clamped
+        oracle.update_price(140, 1);
+        assert_eq!(oracle.get_price(1), Ok(140));
+        
+        // A 55% drop trips the breaker and the price is not recorded
+        oracle.update_price(63, 2);
+        assert_eq!(oracle.get_price(2), Err("oracle halted"));
+        assert_eq!(oracle.latest_price, 140);
+        
+        // Only the breaker's admin may lift the halt
+        assert_eq!(oracle.reset_breaker([1u8; 32]), Err("Only the breaker admin can reset it"));
+        assert_eq!(oracle.get_price(2), Err("oracle halted"));
+        oracle.reset_breaker(admin).unwrap();
+        assert_eq!(oracle.get_price(2), Ok(140));
+        
+        // Without a breaker the same jump is dampened instead
+        let mut clamped = secure::PriceOracle::new(100);
+        clamped.update_price(45, 1);
+        assert_eq!(clamped.get_price(1), Ok(80));
+    }
+    
+    #[test]
+    fn test_secure_twap_weights_prices_by_duration() {
+        let mut oracle = secure::PriceOracle::new(100);
+        oracle.update_price(110, 90);
+        
+        // 100 held for 90s and 110 for 10s; the plain mean of the samples would be 105
+        assert_eq!(oracle.get_twap(100, 100), Ok(101));
+        // Only the last 20s are in the window: 100 for 10s, then 110 for 10s
+        assert_eq!(oracle.get_twap(20, 100), Ok(105));
+    }
+    
+    #[test]
+    fn test_secure_rejects_stuck_round() {
+        let mut oracle = secure::PriceOracle::new(100).with_heartbeat(60);
+        
+        // A scheduled feed still serving its seed price has never published a round
+        assert_eq!(oracle.get_price(1), Err("oracle round is stuck"));
+        
+        oracle.update_price(105, 100);
+        assert_eq!(oracle.latest_round(), (1, 105, 100));
+        assert_eq!(oracle.get_price(160), Ok(105));
+        
+        // No new round for over a heartbeat: stuck, though well inside the staleness window
+        assert_eq!(oracle.get_price(161), Err("oracle round is stuck"));
+        let unscheduled = secure::PriceOracle { heartbeat: None, ..oracle.clone() };
+        assert_eq!(unscheduled.get_price(161), Ok(105));
+        
+        // The next round unsticks the feed
+        oracle.update_price(110, 170);
+        assert_eq!(oracle.latest_round(), (2, 110, 170));
+        assert_eq!(oracle.get_price(200), Ok(110));
+        
+        // A clock behind the latest round is not an underflow
+        assert_eq!(oracle.get_price(150), Ok(110));
+        
+        // A price rejected by the circuit breaker does not start a round
+        let mut halted = secure::PriceOracle::new(100).with_circuit_breaker(10, [9u8; 32]);
+        halted.update_price(200, 1);
+        assert_eq!(halted.latest_round().0, 0);
+    }
+    
+    #[test]
+    fn test_secure_health_factor() {
+        let mut protocol = secure::LendingProtocol::new(100);
+        let healthy = [1u8; 32];
+        let borderline = [2u8; 32];
+        let debt_free = [3u8; 32];
+        
+        protocol.positions.insert(healthy, secure::LendingPosition {
+            owner: healthy,
+            collateral_amount: 2,
+            loan_amount: 100,
+        });
+        protocol.positions.insert(borderline, secure::LendingPosition {
+            owner: borderline,
+            collateral_amount: 11,
+            loan_amount: 1_000,
+        });
+        protocol.positions.insert(debt_free, secure::LendingPosition {
+            owner: debt_free,
+            collateral_amount: 5,
+            loan_amount: 0,
+        });
+        
+        assert_eq!(protocol.health_factor(healthy), Ok(20_000));
+        
+        // Exactly 110% is the last ratio that can't be liquidated
+        assert_eq!(protocol.health_factor(borderline), Ok(11_000));
+        assert_eq!(protocol.liquidate_position(borderline), Err("Position is not eligible for liquidation"));
+        
+        assert_eq!(protocol.health_factor(debt_free), Ok(u64::MAX));
+        assert_eq!(protocol.health_factor([9u8; 32]), Err("Position not found"));
+        
+        // Collateral worth more than u64::MAX is valued without overflowing
+        let whale = [4u8; 32];
+        protocol.positions.insert(whale, secure::LendingPosition {
+            owner: whale,
+            collateral_amount: u64::MAX / 2,
+            loan_amount: u64::MAX / 2,
+        });
+        assert_eq!(protocol.health_factor(whale), Ok(1_000_000));
+        assert_eq!(protocol.liquidate_position(whale), Err("Position is not eligible for liquidation"));
+        
+        // Without a fresh quorum there is no price to report health against
+        protocol.current_time = 1_000;
+        assert_eq!(protocol.health_factor(healthy), Err("Not enough fresh oracles for a quorum"));
+    }
+    
+    #[test]
+    fn test_secure_sync_clock_never_moves_backwards() {
+        let mut chain = crate::utils::MockBlockchain::new();
+        let mut protocol = secure::LendingProtocol::new(100);
+        protocol.sync_clock(&chain).unwrap();
+        assert_eq!(protocol.current_time, chain.unix_timestamp());
+        
+        // Each oracle update ticks the protocol clock ahead of the chain
+        protocol.update_oracle_price(0, 100);
+        let ahead = protocol.current_time;
+        assert!(matches!(
+            protocol.sync_clock(&chain),
+            Err(crate::Error::ClockRegression { current, .. }) if current == ahead
+        ));
+        assert_eq!(protocol.current_time, ahead);
+        
+        chain.advance_slots(10);
+        protocol.sync_clock(&chain).unwrap();
+        assert_eq!(protocol.current_time, ahead + 9);
+    }
 }
@@ -7,7 +7,7 @@
 //! While Rust provides some built-in protection in debug mode, these protections
 //! might be disabled in release builds, leading to potential vulnerabilities.
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, SimulationResult, Vulnerability};
 
 /// Represents an integer overflow/underflow vulnerability example
 pub struct OverflowVulnerability;
@@ -27,6 +27,18 @@ impl Vulnerability for OverflowVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "All Rust-based contracts"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::Arithmetic]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Trivial
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable function that doesn't check for overflow
@@ -57,6 +69,35 @@ impl Vulnerability for OverflowVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure function that rejects overflow instead of wrapping
+        pub fn add_tokens(&mut self, account_id: [u8; 32], amount: u64) -> Result<(), Error> {
+            let account = self.accounts.entry(account_id).or_insert(TokenAccount { balance: 0 });
+            
+            // FIXED: Use checked_add to safely handle potential overflow
+            account.balance = Amount::from(account.balance).checked_add(amount)?.into();
+            
+            Ok(())
+        }
+        
+        // Secure function that checks the total before comparing it to the balance
+        pub fn remove_tokens(&mut self, account_id: [u8; 32], amount: u64) -> Result<(), Error> {
+            let account = self.accounts.get_mut(&account_id).ok_or(Error::AccountNotFound)?;
+            let fee = amount / 100; // 1% fee
+            
+            // FIXED: amount + fee can no longer wrap to a small number
+            let total_deduction: u64 = Amount::from(amount).checked_add(fee)?.into();
+            if account.balance < total_deduction {
+                return Err(Error::InsufficientBalance { have: account.balance, need: total_deduction });
+            }
+            
+            account.balance -= total_deduction;
+            Ok(())
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Check arithmetic operations that could potentially overflow/underflow",
@@ -76,6 +117,34 @@ impl Vulnerability for OverflowVulnerability {
             "Keep panic-on-overflow enabled in release builds for critical code paths",
         ]
     }
+    
+    fn simulate(&self) -> Option<SimulationResult> {
+        let mut result = SimulationResult::default();
+        let account_id = [0xa1; 32];
+        let mut program = vulnerable::TokenProgram::new();
+        
+        program.accounts.insert(account_id, vulnerable::TokenAccount {
+            balance: u64::MAX - 10,
+        });
+        result.step(format!("Account starts with a balance of {}", u64::MAX - 10));
+        result.step("Attacker calls add_tokens(20)");
+        
+        if cfg!(debug_assertions) {
+            // Debug builds panic on overflow, so the unchecked addition can't be run here
+            result.step(format!(
+                "Debug build: the addition panics; with overflow checks disabled the balance would wrap to {}",
+                (u64::MAX - 10).wrapping_add(20)
+            ));
+            return Some(result);
+        }
+        
+        let _ = program.add_tokens(account_id, 20);
+        let balance = program.accounts[&account_id].balance;
+        result.step(format!("Balance wrapped around to {}", balance));
+        
+        result.succeeded = balance < u64::MAX - 10;
+        Some(result)
+    }
 }
 
 /// Example of vulnerable code susceptible to overflow/underflow
@@ -131,6 +200,9 @@ pub mod vulnerable {
 
 /// Example of secure code that prevents overflow/underflow
 pub mod secure {
+    use crate::Error;
+    use crate::utils::Amount;
+    
     pub struct TokenAccount {
         pub balance: u64,
     }
@@ -147,37 +219,51 @@ pub mod secure {
         }
         
         /// Secure function that properly checks for overflow
-        pub fn add_tokens(&mut self, account_id: [u8; 32], amount: u64) -> Result<(), &'static str> {
+        pub fn add_tokens(&mut self, account_id: [u8; 32], amount: u64) -> Result<(), Error> {
             let account = self.accounts.entry(account_id).or_insert(TokenAccount { balance: 0 });
             
             // FIXED: Use checked_add to safely handle potential overflow
-            account.balance = match account.balance.checked_add(amount) {
-                Some(new_balance) => new_balance,
-                None => return Err("Arithmetic overflow detected"),
-            };
+            account.balance = Amount::from(account.balance).checked_add(amount)?.into();
             
             Ok(())
         }
         
+        /// Add tokens, clamping the balance at `u64::MAX` instead of failing
+        ///
+        /// Appropriate when hitting the bound is harmless and the caller should not be blocked,
+        /// such as reward counters or statistics. Never use it for balances that must reconcile
+        /// with a supply elsewhere: the clamped excess silently disappears.
+        pub fn add_tokens_saturating(&mut self, account_id: [u8; 32], amount: u64) {
+            let account = self.accounts.entry(account_id).or_insert(TokenAccount { balance: 0 });
+            account.balance = account.balance.saturating_add(amount);
+        }
+        
+        /// Add tokens with explicit modular (wrap-around) arithmetic
+        ///
+        /// Only appropriate when wrapping is the intended semantics, such as sequence numbers,
+        /// hashes or ring-buffer indices. Unlike an unchecked `+=`, it behaves the same in debug
+        /// and release builds and documents that the wrap is deliberate.
+        pub fn add_tokens_wrapping(&mut self, account_id: [u8; 32], amount: u64) {
+            let account = self.accounts.entry(account_id).or_insert(TokenAccount { balance: 0 });
+            account.balance = account.balance.wrapping_add(amount);
+        }
+        
         /// Secure function that properly checks for underflow
-        pub fn remove_tokens(&mut self, account_id: [u8; 32], amount: u64) -> Result<(), &'static str> {
+        pub fn remove_tokens(&mut self, account_id: [u8; 32], amount: u64) -> Result<(), Error> {
             let account = match self.accounts.get_mut(&account_id) {
                 Some(account) => account,
-                None => return Err("Account not found"),
+                None => return Err(Error::AccountNotFound),
             };
             
             // Calculate fee safely
             let fee = amount / 100; // 1% fee
             
             // FIXED: Use checked_add to safely check total amount to deduct
-            let total_deduction = match amount.checked_add(fee) {
-                Some(total) => total,
-                None => return Err("Arithmetic overflow detected in fee calculation"),
-            };
+            let total_deduction: u64 = Amount::from(amount).checked_add(fee)?.into();
             
             // Check if balance is sufficient for the total deduction
             if account.balance < total_deduction {
-                return Err("Insufficient balance including fees");
+                return Err(Error::InsufficientBalance { have: account.balance, need: total_deduction });
             }
             
             // Safe to subtract now
@@ -185,6 +271,24 @@ pub mod secure {
             
             Ok(())
         }
+        
+        /// Remove tokens plus the 1% fee, letting `checked_sub` enforce the balance check
+        ///
+        /// Equivalent to `remove_tokens`, but the arithmetic itself rejects the underflow, so the
+        /// subtraction cannot drift apart from a separate comparison when the code changes.
+        pub fn remove_tokens_checked(&mut self, account_id: [u8; 32], amount: u64) -> Result<(), Error> {
+            let account = self.accounts.get_mut(&account_id)
+                .ok_or(Error::AccountNotFound)?;
+            
+            let fee = amount / 100; // 1% fee
+            let total_deduction = Amount::from(amount).checked_add(fee)?;
+            
+            account.balance = Amount::from(account.balance).checked_sub(total_deduction)
+                .map_err(|_| Error::InsufficientBalance { have: account.balance, need: total_deduction.into() })?
+                .into();
+            
+            Ok(())
+        }
     }
 }
 
@@ -223,10 +327,64 @@ mod tests {
         
         // This should return an error instead of overflowing
         let result = program.add_tokens(account_id, 20);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Arithmetic overflow detected");
+        assert!(matches!(result, Err(crate::Error::Overflow)));
         
         // Balance should remain unchanged
         assert_eq!(program.accounts.get(&account_id).unwrap().balance, u64::MAX - 10);
     }
+    
+    #[test]
+    fn test_checked_saturating_and_wrapping_strategies() {
+        let mut program = secure::TokenProgram::new();
+        let checked = [1u8; 32];
+        let saturating = [2u8; 32];
+        let wrapping = [3u8; 32];
+        for account_id in [checked, saturating, wrapping] {
+            program.accounts.insert(account_id, secure::TokenAccount { balance: u64::MAX - 10 });
+        }
+        
+        // Checked: the overflow is an error and the balance is untouched
+        assert!(matches!(program.add_tokens(checked, 20), Err(crate::Error::Overflow)));
+        assert_eq!(program.accounts[&checked].balance, u64::MAX - 10);
+        
+        // Saturating: clamps to the maximum without an error
+        program.add_tokens_saturating(saturating, 20);
+        assert_eq!(program.accounts[&saturating].balance, u64::MAX);
+        
+        // Wrapping: wraps around modulo 2^64
+        program.add_tokens_wrapping(wrapping, 20);
+        assert_eq!(program.accounts[&wrapping].balance, 9);
+    }
+    
+    #[test]
+    fn test_remove_tokens_checked_includes_fee() {
+        let mut program = secure::TokenProgram::new();
+        let account_id = [1u8; 32];
+        program.accounts.insert(account_id, secure::TokenAccount { balance: 1_000 });
+        
+        // 1_000 plus a 10 fee exceeds the balance
+        assert!(matches!(
+            program.remove_tokens_checked(account_id, 1_000),
+            Err(crate::Error::InsufficientBalance { have: 1_000, need: 1_010 })
+        ));
+        assert_eq!(program.accounts[&account_id].balance, 1_000);
+        
+        program.remove_tokens_checked(account_id, 500).unwrap();
+        assert_eq!(program.accounts[&account_id].balance, 495);
+    }
+    
+    #[test]
+    fn test_secure_errors_carry_balances() {
+        let mut program = secure::TokenProgram::new();
+        let account_id = [1u8; 32];
+        program.accounts.insert(account_id, secure::TokenAccount { balance: 150 });
+        
+        // The error reports what the account has and what the removal needed
+        let err = program.remove_tokens(account_id, 200).unwrap_err();
+        assert!(matches!(err, crate::Error::InsufficientBalance { have: 150, need: 202 }));
+        assert_eq!(err.to_string(), "Insufficient balance: have 150, need 202");
+        
+        assert!(matches!(program.remove_tokens([9u8; 32], 1), Err(crate::Error::AccountNotFound)));
+        assert!(matches!(program.remove_tokens(account_id, u64::MAX), Err(crate::Error::Overflow)));
+    }
 }
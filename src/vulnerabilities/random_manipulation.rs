@@ -8,7 +8,7 @@
 //! In blockchain environments, achieving true randomness is challenging due to
 //! the deterministic nature of transaction processing.
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 
 /// Represents a random number manipulation vulnerability example
 pub struct RandomManipulationVulnerability;
@@ -28,6 +28,18 @@ impl Vulnerability for RandomManipulationVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "All blockchain platforms"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::Cryptography]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Moderate
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable function with predictable randomness
@@ -79,6 +91,33 @@ impl Vulnerability for RandomManipulationVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure lottery draw from a seed committed before the outcome could be known
+        pub fn reveal_lottery_seed(&mut self, lottery_id: u64, seed: [u8; 32], salt: [u8; 32]) -> Result<[u8; 32], &'static str> {
+            let lottery = self.lotteries.get_mut(&lottery_id).ok_or("Lottery not found")?;
+            let commit_hash = lottery.commit_hash.ok_or("No commit found for this lottery")?;
+            
+            if lottery.reveal_deadline.is_some_and(|deadline| self.block_number > deadline) {
+                return Err("Reveal deadline has passed");
+            }
+            
+            // SECURE: Verify the revealed seed and salt open the commitment
+            if !commit_reveal::verify(&commit_hash, &seed, &salt) {
+                return Err("Reveal does not match commitment");
+            }
+            
+            // The committed seed, not block data alone, decides the winner
+            let winner_index = seed[0] as usize % lottery.participants.len();
+            let winner = lottery.participants[winner_index];
+            
+            lottery.winner = Some(winner);
+            lottery.is_complete = true;
+            Ok(winner)
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Identify all sources of randomness in the contract",
@@ -230,6 +269,8 @@ pub mod vulnerable {
 /// Example of secure code with better randomness generation
 pub mod secure {
     use std::collections::HashMap;
+    use sha2::{Digest, Sha256};
+    use crate::utils::{commit_reveal, crypto};
     
     #[derive(Debug, Clone)]
     pub struct Lottery {
@@ -265,6 +306,8 @@ pub mod secure {
     pub struct RandomnessRequest {
         pub id: u64,
         pub requester: [u8; 32],
+        pub seed: [u8; 32], // VRF input, fixed when the request is made
+        pub request_block: u64,
         pub fulfilled: bool,
         pub random_value: Option<[u8; 32]>,
         pub callback_data: Vec<u8>,
@@ -281,6 +324,8 @@ pub mod secure {
         pub randomness_requests: HashMap<u64, RandomnessRequest>,
         pub next_request_id: u64,
         pub vrf_public_key: [u8; 32], // Mock VRF key
+        pub slashed: HashMap<[u8; 32], u64>, // committer -> number of commits left unrevealed
+        pub max_fulfillment_delay: u64, // Blocks a VRF response may arrive after its request
     }
     
     impl GamePlatform {
@@ -296,6 +341,8 @@ pub mod secure {
                 randomness_requests: HashMap::new(),
                 next_request_id: 1,
                 vrf_public_key: [0xaa; 32], // Mock VRF key
+                slashed: HashMap::new(),
+                max_fulfillment_delay: 256,
             }
         }
         
@@ -314,6 +361,11 @@ pub mod secure {
                 return Err("No participants in lottery");
             }
             
+            // SECURE: A pending commit can only be replaced via resolve_expired_commit
+            if lottery.commit_hash.is_some() {
+                return Err("Lottery already has an active commit");
+            }
+            
             // Store commit hash and set reveal deadline
             lottery.commit_hash = Some(commit_hash);
             lottery.reveal_deadline = Some(self.block_number + 10); // 10 blocks to reveal
@@ -355,8 +407,11 @@ pub mod secure {
                 }
             }
             
-            // Verify commit matches reveal
-            // In a real implementation, this would hash the seed+salt and verify it matches commit_hash
+            // SECURE: Verify the revealed seed and salt open the commitment
+            if !commit_reveal::verify(&commit_hash, &seed, &salt) {
+                return Err("Reveal does not match commitment");
+            }
+            
             let commit_reveal = match self.commit_reveals.get_mut(&commit_hash) {
                 Some(cr) => cr,
                 None => return Err("Commit not found"),
@@ -384,15 +439,55 @@ pub mod secure {
             Ok(winner)
         }
         
+        /// Clear a commit whose reveal deadline passed without a reveal
+        ///
+        /// Records a slash against the committer and lets a fresh seed be committed, so a
+        /// committer who dislikes the outcome can't stall the lottery by withholding the reveal.
+        pub fn resolve_expired_commit(&mut self, lottery_id: u64) -> Result<(), &'static str> {
+            let lottery = self.lotteries.get_mut(&lottery_id)
+                .ok_or("Lottery not found")?;
+            
+            if lottery.is_complete {
+                return Err("Lottery already completed");
+            }
+            
+            let (Some(commit_hash), Some(deadline)) = (lottery.commit_hash, lottery.reveal_deadline) else {
+                return Err("No commit found for this lottery");
+            };
+            
+            if self.block_number <= deadline {
+                return Err("Reveal deadline has not passed");
+            }
+            
+            lottery.commit_hash = None;
+            lottery.reveal_deadline = None;
+            
+            if let Some(commit) = self.commit_reveals.remove(&commit_hash) {
+                *self.slashed.entry(commit.committer).or_insert(0) += 1;
+            }
+            
+            Ok(())
+        }
+        
         /// Secure NFT with VRF (mocking external VRF behavior)
         pub fn request_random_nft(&mut self, minter: [u8; 32]) -> Result<u64, &'static str> {
             // Create a randomness request that will be fulfilled by VRF
             let request_id = self.next_request_id;
             self.next_request_id += 1;
             
+            // Bind the VRF input to this request so an output can't be replayed across requests
+            let seed: [u8; 32] = Sha256::new()
+                .chain_update(request_id.to_le_bytes())
+                .chain_update(minter)
+                .chain_update(self.block_number.to_le_bytes())
+                .finalize()
+                .into();
+            
             let request = RandomnessRequest {
                 id: request_id,
                 requester: minter,
+                seed,
+                request_block: self.block_number,
                 fulfilled: false,
                 random_value: None,
                 callback_data: Vec::new(), // In a real implementation, this would store the NFT mint data
@@ -407,8 +502,6 @@ pub mod secure {
         
         /// Callback for VRF to fulfill randomness (would be called by VRF oracle)
         pub fn fulfill_randomness(&mut self, request_id: u64, random_value: [u8; 32], proof: [u8; 64]) -> Result<u64, &'static str> {
-            // In a real implementation, we would verify the proof against our VRF public key
-            
             let request = match self.randomness_requests.get_mut(&request_id) {
                 Some(req) => req,
                 None => return Err("Request not found"),
@@ -418,6 +511,17 @@ pub mod secure {
                 return Err("Request already fulfilled");
             }
             
+            // SECURE: A response held back past the callback window could be
+            // delivered once its outcome has become useful to someone
+            if self.block_number - request.request_block > self.max_fulfillment_delay {
+                return Err("VRF response too late");
+            }
+            
+            // SECURE: The value must be the VRF output for this request's seed under our key
+            if !crypto::verify_vrf(&self.vrf_public_key, &request.seed, &random_value, &proof) {
+                return Err("invalid VRF proof");
+            }
+            
             // Update request
             request.fulfilled = true;
             request.random_value = Some(random_value);
@@ -473,10 +577,15 @@ pub mod secure {
                 combined[i] ^= timestamp_bytes[i];
             }
             
-            // Take first byte and map to 1-6
-            let dice_value = (combined[0] % 6) + 1;
+            // SECURE: `byte % 6` alone is biased: 256 = 6 * 42 + 4, so faces 1-4
+            // each get 43 of the 256 byte values and faces 5-6 only 42. Bytes
+            // in the uneven tail (252..=255) are rejected and the next one tried.
+            const UNBIASED_LIMIT: u8 = 252; // Largest multiple of 6 that fits in a byte
             
-            Ok(dice_value)
+            combined.iter()
+                .find(|&&byte| byte < UNBIASED_LIMIT)
+                .map(|&byte| byte % 6 + 1)
+                .ok_or("Could not draw an unbiased roll")
         }
         
         /// Helper to simulate new block (for testing)
@@ -549,8 +658,10 @@ mod tests {
             reveal_deadline: None,
         });
         
-        // Create commit hash (in reality, this would be hash(seed + salt))
-        let commit_hash = [0xbb; 32];
+        // Commit to hash(seed + salt)
+        let seed = [0xdd; 32];
+        let salt = [0xee; 32];
+        let commit_hash = crate::utils::commit_reveal::commitment(&seed, &salt);
         let committer = [0xcc; 32];
         
         // Commit to a random seed
@@ -561,9 +672,12 @@ mod tests {
         platform.advance_block();
         platform.advance_block();
         
+        // A tampered seed does not open the commitment
+        let result = platform.reveal_lottery_seed(lottery_id, [0x11; 32], salt);
+        assert_eq!(result.unwrap_err(), "Reveal does not match commitment");
+        assert!(!platform.lotteries[&lottery_id].is_complete);
+        
         // Reveal the seed and select winner
-        let seed = [0xdd; 32];
-        let salt = [0xee; 32];
         let winner = platform.reveal_lottery_seed(lottery_id, seed, salt);
         assert!(winner.is_ok());
         
@@ -572,8 +686,8 @@ mod tests {
         let request_id = platform.request_random_nft(minter).unwrap();
         
         // Simulate VRF response
-        let random_value = [0x12; 32];
-        let proof = [0x34; 64];
+        let seed = platform.randomness_requests[&request_id].seed;
+        let (random_value, proof) = crate::utils::crypto::vrf_prove(&platform.vrf_public_key, &seed);
         
         let nft_id = platform.fulfill_randomness(request_id, random_value, proof).unwrap();
         
@@ -595,4 +709,163 @@ mod tests {
         // Not guaranteed to be different, but highly likely
         // In reality, with proper hashing, this would be much more robust
     }
+    
+    /// Secure platform whose mock VRF key comes from a seeded `TestRng`
+    fn platform_with_vrf_key(rng: &mut crate::utils::TestRng) -> secure::GamePlatform {
+        let mut platform = secure::GamePlatform::new();
+        rng.fill_bytes(&mut platform.vrf_public_key);
+        platform
+    }
+    
+    /// Mint `count` NFTs through the VRF flow and return their rarities
+    fn mint_rarities(platform: &mut secure::GamePlatform, rng: &mut crate::utils::TestRng, count: usize) -> Vec<u8> {
+        (0..count).map(|_| {
+            let mut minter = [0u8; 32];
+            rng.fill_bytes(&mut minter);
+            let request_id = platform.request_random_nft(minter).unwrap();
+            let seed = platform.randomness_requests[&request_id].seed;
+            let (output, proof) = crate::utils::crypto::vrf_prove(&platform.vrf_public_key, &seed);
+            let nft_id = platform.fulfill_randomness(request_id, output, proof).unwrap();
+            platform.advance_block();
+            platform.nfts[&nft_id].rarity
+        }).collect()
+    }
+    
+    #[test]
+    fn test_vrf_minting_is_reproducible_with_test_rng() {
+        use crate::utils::TestRng;
+        
+        let mut rng = TestRng::new(7);
+        let mut platform = platform_with_vrf_key(&mut rng);
+        let rarities = mint_rarities(&mut platform, &mut rng, 8);
+        
+        // The same fixture seed replays the exact same mints
+        let mut replay_rng = TestRng::new(7);
+        let mut replay = platform_with_vrf_key(&mut replay_rng);
+        assert_eq!(mint_rarities(&mut replay, &mut replay_rng, 8), rarities);
+        
+        // A different VRF key yields different outputs for the same requests
+        let mut other_rng = TestRng::new(8);
+        let mut other = platform_with_vrf_key(&mut other_rng);
+        assert_ne!(mint_rarities(&mut other, &mut other_rng, 8), rarities);
+        
+        assert!(rarities.iter().all(|rarity| (1..=101).contains(rarity)));
+    }
+    
+    /// Roll the secure dice `samples` times with fresh player seeds and count each face
+    fn roll_distribution(samples: usize) -> [u32; 6] {
+        let mut platform = secure::GamePlatform::new();
+        let mut rng = crate::utils::TestRng::new(6);
+        let mut counts = [0u32; 6];
+        
+        for _ in 0..samples {
+            let mut player_seed = [0u8; 32];
+            rng.fill_bytes(&mut player_seed);
+            let roll = platform.roll_dice([0xaa; 32], player_seed).unwrap();
+            counts[roll as usize - 1] += 1;
+        }
+        counts
+    }
+    
+    #[test]
+    fn test_secure_dice_roll_is_uniform() {
+        const SAMPLES: usize = 600_000;
+        let expected = (SAMPLES / 6) as u32;
+        
+        // 1% of the expected count is over 3 standard deviations of sampling
+        // noise, yet smaller than the ~1.6% skew plain `% 6` would produce
+        let tolerance = expected / 100;
+        for (face, &count) in roll_distribution(SAMPLES).iter().enumerate() {
+            assert!(count.abs_diff(expected) <= tolerance, "face {} rolled {} times, expected about {}", face + 1, count, expected);
+        }
+    }
+    
+    #[test]
+    fn test_fulfill_randomness_verifies_vrf_proof() {
+        let mut platform = secure::GamePlatform::new();
+        let request_id = platform.request_random_nft([0xff; 32]).unwrap();
+        let seed = platform.randomness_requests[&request_id].seed;
+        let (output, proof) = crate::utils::crypto::vrf_prove(&platform.vrf_public_key, &seed);
+        
+        // An oracle can't pick its own value or reuse a proof for another seed
+        assert_eq!(platform.fulfill_randomness(request_id, [0x12; 32], proof), Err("invalid VRF proof"));
+        let (other_output, other_proof) = crate::utils::crypto::vrf_prove(&platform.vrf_public_key, &[0u8; 32]);
+        assert_eq!(platform.fulfill_randomness(request_id, other_output, other_proof), Err("invalid VRF proof"));
+        
+        let mut forged = proof;
+        forged[40] ^= 1;
+        assert_eq!(platform.fulfill_randomness(request_id, output, forged), Err("invalid VRF proof"));
+        assert!(!platform.randomness_requests[&request_id].fulfilled);
+        
+        // The genuine evaluation is accepted
+        assert!(platform.fulfill_randomness(request_id, output, proof).is_ok());
+        assert!(platform.randomness_requests[&request_id].fulfilled);
+    }
+    
+    #[test]
+    fn test_expired_commit_can_be_resolved_and_replaced() {
+        let mut platform = secure::GamePlatform::new();
+        let lottery_id = 1;
+        platform.lotteries.insert(lottery_id, secure::Lottery {
+            id: lottery_id,
+            participants: vec![[1u8; 32], [2u8; 32]],
+            ticket_count: 2,
+            winner: None,
+            prize_amount: 100,
+            is_complete: false,
+            commit_hash: None,
+            reveal_deadline: None,
+        });
+        
+        let stalling_committer = [0xcc; 32];
+        let stalled_hash = crate::utils::commit_reveal::commitment(&[0x42; 32], &[0x07; 32]);
+        platform.commit_lottery_seed(lottery_id, stalling_committer, stalled_hash).unwrap();
+        
+        // The commit can't be replaced or resolved while the reveal window is open
+        let fresh_seed = [0x43; 32];
+        let fresh_salt = [0x08; 32];
+        let fresh_hash = crate::utils::commit_reveal::commitment(&fresh_seed, &fresh_salt);
+        assert_eq!(platform.commit_lottery_seed(lottery_id, [0xdd; 32], fresh_hash), Err("Lottery already has an active commit"));
+        for _ in 0..10 {
+            platform.advance_block();
+        }
+        assert_eq!(platform.resolve_expired_commit(lottery_id), Err("Reveal deadline has not passed"));
+        
+        // One block past the deadline the stalled commit is cleared and slashed
+        platform.advance_block();
+        platform.resolve_expired_commit(lottery_id).unwrap();
+        assert_eq!(platform.slashed[&stalling_committer], 1);
+        assert!(!platform.commit_reveals.contains_key(&stalled_hash));
+        
+        // A fresh commit and reveal completes the lottery
+        platform.commit_lottery_seed(lottery_id, [0xdd; 32], fresh_hash).unwrap();
+        assert!(platform.reveal_lottery_seed(lottery_id, fresh_seed, fresh_salt).is_ok());
+        assert!(platform.lotteries[&lottery_id].is_complete);
+    }
+    
+    #[test]
+    fn test_vrf_fulfillment_window() {
+        let mut platform = secure::GamePlatform::new();
+        platform.max_fulfillment_delay = 3;
+        
+        let on_time = platform.request_random_nft([0x01; 32]).unwrap();
+        let late = platform.request_random_nft([0x02; 32]).unwrap();
+        let respond = |platform: &secure::GamePlatform, request_id: u64| {
+            let seed = platform.randomness_requests[&request_id].seed;
+            crate::utils::crypto::vrf_prove(&platform.vrf_public_key, &seed)
+        };
+        
+        // Exactly `max_fulfillment_delay` blocks later is still on time
+        for _ in 0..3 {
+            platform.advance_block();
+        }
+        let (output, proof) = respond(&platform, on_time);
+        assert!(platform.fulfill_randomness(on_time, output, proof).is_ok());
+        
+        // One block more and an otherwise valid response is rejected
+        platform.advance_block();
+        let (output, proof) = respond(&platform, late);
+        assert_eq!(platform.fulfill_randomness(late, output, proof), Err("VRF response too late"));
+        assert!(!platform.randomness_requests[&late].fulfilled);
+    }
 }
@@ -9,7 +9,7 @@
 //! - Replay attacks due to missing or improper nonce handling
 //! - Signature malleability issues
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 use crate::utils::{Account, MockBlockchain};
 
 /// Represents a signature verification bypass vulnerability example
@@ -30,6 +30,18 @@ impl Vulnerability for SignatureVerificationVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "CosmWasm"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::Cryptography, Category::AccessControl]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Moderate
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable signature verification that doesn't check all relevant data
@@ -55,6 +67,36 @@ impl Vulnerability for SignatureVerificationVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure transfer whose signature covers the domain, both parties, amount and nonce
+        pub fn transfer(&mut self, from: [u8; 32], to: [u8; 32], amount: u64, nonce: u64, signature: [u8; 64]) -> Result<(), &'static str> {
+            let from_account = self.accounts.get(&from).ok_or("Account not found")?;
+            if from_account.balance < amount {
+                return Err("Insufficient funds");
+            }
+            
+            // Check nonce to prevent replay attacks
+            self.check_nonce(&from, nonce)?;
+            
+            // SECURE: Only the reduced encoding of a signature is accepted
+            if !crypto::is_canonical_signature(&signature) {
+                return Err("non-canonical signature");
+            }
+            
+            // SECURE: Include all relevant transaction data in the message to be signed
+            let message = transfer_message(self.domain, from, to, amount, nonce);
+            if !crypto::verify_ed25519(&from, &message, &signature) {
+                return Err("Invalid signature");
+            }
+            
+            self.nonces.insert(from, nonce);
+            // ...move the funds...
+            Ok(())
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Check that all relevant transaction data is included in the signed message (amount, recipient, timestamp/nonce)",
@@ -149,11 +191,25 @@ pub mod vulnerable {
 /// Module containing a secure implementation
 pub mod secure {
     use std::collections::HashMap;
+    use sha2::{Digest, Sha256};
+    use crate::utils::crypto;
+    
+    /// How a transfer's nonce must relate to the sender's last used nonce
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum NoncePolicy {
+        /// Any nonce greater than the last one; gaps are allowed
+        #[default]
+        Monotonic,
+        /// Exactly the last nonce plus one, so no signed message is stranded
+        Sequential,
+    }
     
     /// A wallet program with secure signature verification
     pub struct WalletProgram {
         pub accounts: HashMap<[u8; 32], WalletAccount>,
         pub nonces: HashMap<[u8; 32], u64>, // Store nonces for replay protection
+        pub nonce_policy: NoncePolicy,
+        pub domain: [u8; 32], // Binds signatures to one deployment on one chain
     }
     
     /// Wallet account structure
@@ -162,15 +218,67 @@ pub mod secure {
         pub balance: u64,
     }
     
+    /// Domain separator for a deployment: `sha256(program_id || chain_id)`
+    pub fn domain_separator(program_id: [u8; 32], chain_id: u64) -> [u8; 32] {
+        Sha256::new()
+            .chain_update(program_id)
+            .chain_update(chain_id.to_le_bytes())
+            .finalize()
+            .into()
+    }
+    
+    /// Build the message a sender signs to authorize a transfer within `domain`
+    pub fn transfer_message(domain: [u8; 32], from: [u8; 32], to: [u8; 32], amount: u64, nonce: u64) -> Vec<u8> {
+        let mut message = Vec::with_capacity(32 + 32 + 32 + 8 + 8);
+        message.extend_from_slice(&domain);
+        message.extend_from_slice(&from);
+        message.extend_from_slice(&to);
+        message.extend_from_slice(&amount.to_le_bytes());
+        message.extend_from_slice(&nonce.to_le_bytes());
+        message
+    }
+    
     impl WalletProgram {
-        /// Create a new wallet program
+        /// Create a new wallet program with an all-zero domain
+        ///
+        /// Real deployments should use `new_with_domain` so their signatures
+        /// cannot be replayed against another instance or chain.
         pub fn new() -> Self {
+            Self::new_with_domain([0u8; 32])
+        }
+        
+        /// Create a wallet program that only accepts signatures made for `domain`
+        pub fn new_with_domain(domain: [u8; 32]) -> Self {
             Self {
                 accounts: HashMap::new(),
                 nonces: HashMap::new(),
+                nonce_policy: NoncePolicy::default(),
+                domain,
             }
         }
         
+        /// Choose between monotonic and strictly sequential nonces
+        pub fn set_nonce_policy(&mut self, policy: NoncePolicy) {
+            self.nonce_policy = policy;
+        }
+        
+        /// Check `nonce` against the sender's last used nonce under the current policy
+        fn check_nonce(&self, from: &[u8; 32], nonce: u64) -> Result<(), &'static str> {
+            let current_nonce = self.nonces.get(from).copied().unwrap_or(0);
+            if nonce <= current_nonce {
+                return Err("Invalid nonce - potential replay attack");
+            }
+            
+            if self.nonce_policy == NoncePolicy::Sequential {
+                // nonce > current_nonce, so current_nonce + 1 cannot overflow here
+                if nonce != current_nonce + 1 {
+                    return Err("nonce gap");
+                }
+            }
+            
+            Ok(())
+        }
+        
         /// Transfer with secure signature verification
         pub fn transfer(&mut self, 
                         from: [u8; 32], 
@@ -188,18 +296,17 @@ pub mod secure {
             }
             
             // Check nonce to prevent replay attacks
-            let current_nonce = self.nonces.get(&from).copied().unwrap_or(0);
-            if nonce <= current_nonce {
-                return Err("Invalid nonce - potential replay attack");
+            self.check_nonce(&from, nonce)?;
+            
+            // SECURE: Only the reduced encoding of a signature is accepted, so
+            // no transfer has a second, byte-different signature that passes
+            if !crypto::is_canonical_signature(&signature) {
+                return Err("non-canonical signature");
             }
             
             // SECURE: Include all relevant transaction data in the message to be signed
-            // This includes sender, recipient, amount, and nonce
-            let mut message = Vec::with_capacity(32 + 32 + 8 + 8);
-            message.extend_from_slice(&from);
-            message.extend_from_slice(&to);
-            message.extend_from_slice(&amount.to_le_bytes());
-            message.extend_from_slice(&nonce.to_le_bytes());
+            // This includes the domain, sender, recipient, amount, and nonce
+            let message = transfer_message(self.domain, from, to, amount, nonce);
             
             if !self.verify_signature(&from, &message, &signature) {
                 return Err("Invalid signature");
@@ -224,11 +331,159 @@ pub mod secure {
             Ok(())
         }
         
-        /// Secure signature verification (simplified for example)
+        /// Secure signature verification backed by ed25519
         fn verify_signature(&self, pubkey: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
-            // Simplified mock verification - in a real situation this would use proper crypto
-            // This is just for demonstration
-            true // Always returns true for simplicity in this example
+            // SECURE: The account key is the sender's ed25519 public key
+            crypto::verify_ed25519(pubkey, message, signature)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+    
+    fn test_keypair() -> Keypair {
+        let secret = SecretKey::from_bytes(&[7u8; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        Keypair { secret, public }
+    }
+    
+    #[test]
+    fn test_secure_signature_binds_recipient() {
+        let signing_key = test_keypair();
+        let from = signing_key.public.to_bytes();
+        let recipient = [2u8; 32];
+        let attacker = [3u8; 32];
+        
+        let mut program = secure::WalletProgram::new();
+        program.accounts.insert(from, secure::WalletAccount {
+            owner: from,
+            balance: 1_000,
+        });
+        
+        // A signature over the full (from, to, amount, nonce) message is accepted
+        let message = secure::transfer_message(program.domain, from, recipient, 100, 1);
+        let signature = signing_key.sign(&message).to_bytes();
+        assert!(program.transfer(from, recipient, 100, 1, signature).is_ok());
+        assert_eq!(program.accounts[&recipient].balance, 100);
+        
+        // The same signature cannot be redirected to another recipient
+        let result = program.transfer(from, attacker, 100, 2, signature);
+        assert_eq!(result.unwrap_err(), "Invalid signature");
+        
+        // A signature over only the amount, as the vulnerable program expects,
+        // is rejected when replayed to a different recipient
+        let amount_only = signing_key.sign(&100u64.to_le_bytes()).to_bytes();
+        let result = program.transfer(from, attacker, 100, 2, amount_only);
+        assert_eq!(result.unwrap_err(), "Invalid signature");
+        assert!(!program.accounts.contains_key(&attacker));
+        
+        // The vulnerable program accepts that same replay
+        let mut vulnerable_program = vulnerable::WalletProgram::new();
+        vulnerable_program.accounts.insert(from, vulnerable::WalletAccount {
+            owner: from,
+            balance: 1_000,
+        });
+        assert!(vulnerable_program.transfer(from, attacker, 100, amount_only).is_ok());
+        assert_eq!(vulnerable_program.accounts[&attacker].balance, 100);
+    }
+    
+    #[test]
+    fn test_secure_nonce_policies() {
+        let signing_key = test_keypair();
+        let from = signing_key.public.to_bytes();
+        let to = [2u8; 32];
+        let mut program = secure::WalletProgram::new();
+        let domain = program.domain;
+        let signed = |nonce| signing_key.sign(&secure::transfer_message(domain, from, to, 10, nonce)).to_bytes();
+        
+        program.accounts.insert(from, secure::WalletAccount {
+            owner: from,
+            balance: 1_000,
+        });
+        
+        // Monotonic (default): skipping from 0 to 5 is allowed
+        assert_eq!(program.nonce_policy, secure::NoncePolicy::Monotonic);
+        assert!(program.transfer(from, to, 10, 5, signed(5)).is_ok());
+        
+        // Sequential: a gap is rejected distinctly from a replay
+        program.set_nonce_policy(secure::NoncePolicy::Sequential);
+        assert_eq!(program.transfer(from, to, 10, 100, signed(100)), Err("nonce gap"));
+        assert_eq!(program.transfer(from, to, 10, 5, signed(5)), Err("Invalid nonce - potential replay attack"));
+        assert!(program.transfer(from, to, 10, 6, signed(6)).is_ok());
+        
+        // At u64::MAX no further nonce exists under either policy
+        program.nonces.insert(from, u64::MAX);
+        assert_eq!(program.transfer(from, to, 10, u64::MAX, signed(u64::MAX)), Err("Invalid nonce - potential replay attack"));
+        program.set_nonce_policy(secure::NoncePolicy::Monotonic);
+        assert_eq!(program.transfer(from, to, 10, u64::MAX, signed(u64::MAX)), Err("Invalid nonce - potential replay attack"));
+    }
+    
+    #[test]
+    fn test_secure_signature_is_bound_to_domain() {
+        let signing_key = test_keypair();
+        let from = signing_key.public.to_bytes();
+        let to = [2u8; 32];
+        let program_id = [5u8; 32];
+        
+        // Same program id on two chains, and a second deployment on the first chain
+        let domain_a = secure::domain_separator(program_id, 1);
+        let domain_b = secure::domain_separator(program_id, 2);
+        assert_ne!(domain_a, domain_b);
+        assert_ne!(secure::domain_separator([6u8; 32], 1), domain_a);
+        
+        let funded_program = |domain| {
+            let mut program = secure::WalletProgram::new_with_domain(domain);
+            program.accounts.insert(from, secure::WalletAccount {
+                owner: from,
+                balance: 1_000,
+            });
+            program
+        };
+        let signature = signing_key.sign(&secure::transfer_message(domain_a, from, to, 100, 1)).to_bytes();
+        
+        // Identical (from, to, amount, nonce) is rejected under another domain
+        let mut program_b = funded_program(domain_b);
+        assert_eq!(program_b.transfer(from, to, 100, 1, signature), Err("Invalid signature"));
+        assert!(!program_b.nonces.contains_key(&from));
+        
+        let mut program_a = funded_program(domain_a);
+        assert!(program_a.transfer(from, to, 100, 1, signature).is_ok());
+    }
+    
+    /// `signature` with its `S` scalar replaced by `S + L`, the high-S twin
+    fn high_s_variant(signature: [u8; 64]) -> [u8; 64] {
+        let mut malleated = signature;
+        let mut carry = 0u16;
+        for (byte, order_byte) in malleated[32..].iter_mut().zip(crate::utils::crypto::ED25519_ORDER) {
+            let sum = *byte as u16 + order_byte as u16 + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        malleated
+    }
+    
+    #[test]
+    fn test_secure_rejects_high_s_signature() {
+        let signing_key = test_keypair();
+        let from = signing_key.public.to_bytes();
+        let to = [2u8; 32];
+        
+        let mut program = secure::WalletProgram::new();
+        program.accounts.insert(from, secure::WalletAccount {
+            owner: from,
+            balance: 1_000,
+        });
+        
+        let signature = signing_key.sign(&secure::transfer_message(program.domain, from, to, 100, 1)).to_bytes();
+        let malleated = high_s_variant(signature);
+        assert_ne!(malleated, signature);
+        
+        assert_eq!(program.transfer(from, to, 100, 1, malleated), Err("non-canonical signature"));
+        assert!(!program.accounts.contains_key(&to));
+        
+        assert!(program.transfer(from, to, 100, 1, signature).is_ok());
+    }
+}
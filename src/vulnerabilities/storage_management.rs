@@ -9,7 +9,7 @@
 //! - Inefficient storage patterns leading to high gas costs
 //! - Data corruption due to improper serialization/deserialization
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
 use crate::utils::{Account, MockBlockchain};
 
 /// Represents a storage management vulnerability example
@@ -30,6 +30,18 @@ impl Vulnerability for StorageManagementVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "CosmWasm"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::StateManagement]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Moderate
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::Medium
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable Solana program with storage management issues
@@ -76,6 +88,40 @@ impl Vulnerability for StorageManagementVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure instruction processing with ownership, input and size checks
+        pub fn process_instruction(&mut self, program_id: [u8; 32], account_id: [u8; 32], instruction: u8, instruction_data: &[u8]) -> Result<(), Error> {
+            let account = self.accounts.get_mut(&account_id).ok_or(Error::AccountNotFound)?;
+            
+            // SECURE: Verify account ownership
+            if account.owner != program_id {
+                return Err(Error::AccountConfusion);
+            }
+            
+            let mut state = Self::deserialize_state(&account.data, account.size)?;
+            match instruction {
+                0 => state.counter += 1,
+                1 => {
+                    // SECURE: Validate instruction data
+                    let bytes = instruction_data.get(0..8).ok_or(Error::InvalidInput)?;
+                    state.values.push(u64::from_le_bytes(bytes.try_into().unwrap()));
+                }
+                _ => return Err(Error::InvalidInput),
+            }
+            
+            // SECURE: Validate the new data will fit in the account's allocated size
+            let new_data = Self::serialize_state(&state)?;
+            if new_data.len() > account.size {
+                return Err(Error::AccountTooSmall { needed: new_data.len(), available: account.size });
+            }
+            
+            account.data = new_data;
+            Ok(())
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Check for proper account size validation before operations",
@@ -137,7 +183,7 @@ pub mod vulnerable {
             };
             
             // Serialize state (simplified for example)
-            let data = self.serialize_state(&state)?;
+            let data = Self::serialize_state(&state)?;
             
             // Store the account
             self.accounts.insert(account_id, AccountData {
@@ -163,7 +209,7 @@ pub mod vulnerable {
             // Should verify account.owner == program_id
             
             // Deserialize state (simplified for example)
-            let mut state = self.deserialize_state(&account.data)?;
+            let mut state = Self::deserialize_state(&account.data)?;
             
             // Process based on instruction
             match instruction {
@@ -189,7 +235,7 @@ pub mod vulnerable {
             }
             
             // Serialize state (simplified for example)
-            let data = self.serialize_state(&state)?;
+            let data = Self::serialize_state(&state)?;
             
             // VULNERABILITY: No size check before updating account data
             // In a real blockchain, accounts have fixed sizes and this could fail
@@ -199,7 +245,7 @@ pub mod vulnerable {
         }
         
         /// Simplified serialization (in a real contract, this would use borsh or another serializer)
-        fn serialize_state(&self, state: &State) -> Result<Vec<u8>, &'static str> {
+        fn serialize_state(state: &State) -> Result<Vec<u8>, &'static str> {
             let mut data = Vec::new();
             
             // Serialize counter
@@ -218,7 +264,7 @@ pub mod vulnerable {
         }
         
         /// Simplified deserialization
-        fn deserialize_state(&self, data: &[u8]) -> Result<State, &'static str> {
+        fn deserialize_state(data: &[u8]) -> Result<State, &'static str> {
             if data.len() < 16 {
                 return Err("Data too small to deserialize");
             }
@@ -260,6 +306,7 @@ pub mod vulnerable {
 /// Module containing a secure implementation
 pub mod secure {
     use std::collections::HashMap;
+    use crate::Error;
     
     /// A program with proper storage management
     pub struct Program {
@@ -274,8 +321,19 @@ pub mod secure {
         pub size: usize, // Fixed size of the account (simulating blockchain constraints)
     }
     
+    /// Bytes in front of the values: the counter and the values length
+    #[cfg(not(feature = "borsh"))]
+    pub const STATE_HEADER_LEN: usize = 16;
+    /// Bytes in front of the values: the counter and borsh's u32 values length
+    #[cfg(feature = "borsh")]
+    pub const STATE_HEADER_LEN: usize = 12;
+    
+    /// Most values one account may hold, however large its allocation
+    pub const MAX_VALUES: usize = 64;
+    
     /// State structure (would be serialized/deserialized in a real contract)
-    #[derive(Clone)]
+    #[derive(Clone, Debug, PartialEq)]
+    #[cfg_attr(feature = "borsh", derive(borsh::BorshSerialize, borsh::BorshDeserialize))]
     pub struct State {
         pub counter: u64,
         pub values: Vec<u64>,
@@ -290,7 +348,7 @@ pub mod secure {
         }
         
         /// Initialize an account with a specified size
-        pub fn initialize_account(&mut self, account_id: [u8; 32], owner: [u8; 32], size: usize) -> Result<(), &'static str> {
+        pub fn initialize_account(&mut self, account_id: [u8; 32], owner: [u8; 32], size: usize) -> Result<(), Error> {
             // Create a new account with initial state
             let state = State {
                 counter: 0,
@@ -298,11 +356,11 @@ pub mod secure {
             };
             
             // Serialize state
-            let data = self.serialize_state(&state)?;
+            let data = Self::serialize_state(&state)?;
             
             // Check if the allocated size is sufficient
             if data.len() > size {
-                return Err("Account size too small for initial state");
+                return Err(Error::AccountTooSmall { needed: data.len(), available: size });
             }
             
             // Store the account with fixed size
@@ -320,19 +378,19 @@ pub mod secure {
                                  program_id: [u8; 32],
                                  account_id: [u8; 32], 
                                  instruction: u8, 
-                                 instruction_data: &[u8]) -> Result<(), &'static str> {
+                                 instruction_data: &[u8]) -> Result<(), Error> {
             
             // Get the account
             let account = self.accounts.get_mut(&account_id)
-                .ok_or("Account not found")?;
+                .ok_or(Error::AccountNotFound)?;
             
             // SECURE: Verify account ownership
             if account.owner != program_id {
-                return Err("Account not owned by this program");
+                return Err(Error::AccountConfusion);
             }
             
             // Deserialize state
-            let mut state = self.deserialize_state(&account.data)?;
+            let mut state = Self::deserialize_state(&account.data, account.size)?;
             
             // Process based on instruction
             match instruction {
@@ -343,7 +401,7 @@ pub mod secure {
                 1 => {
                     // SECURE: Validate instruction data
                     if instruction_data.len() < 8 {
-                        return Err("Invalid instruction data");
+                        return Err(Error::InvalidInput);
                     }
                     
                     // Parse value from instruction data
@@ -351,26 +409,32 @@ pub mod secure {
                     bytes.copy_from_slice(&instruction_data[0..8]);
                     let value = u64::from_le_bytes(bytes);
                     
+                    // SECURE: Cap the element count independently of the byte size,
+                    // so a generously sized account can't be filled with pathological counts
+                    if state.values.len() >= MAX_VALUES {
+                        return Err(Error::TooManyValues { max: MAX_VALUES });
+                    }
+                    
                     // SECURE: Check if adding a new value would exceed account size
                     let current_values_size = state.values.len() * 8;
-                    let new_size_estimate = 16 + ((state.values.len() + 1) * 8); // 8 for counter, 8 for length, 8 for each value
+                    let new_size_estimate = STATE_HEADER_LEN + ((state.values.len() + 1) * 8); // header, then 8 for each value
                     
                     if new_size_estimate > account.size {
-                        return Err("Account capacity exceeded");
+                        return Err(Error::AccountTooSmall { needed: new_size_estimate, available: account.size });
                     }
                     
                     // Add value to the list
                     state.values.push(value);
                 }
-                _ => return Err("Invalid instruction"),
+                _ => return Err(Error::InvalidInput),
             }
             
             // Serialize state
-            let new_data = self.serialize_state(&state)?;
+            let new_data = Self::serialize_state(&state)?;
             
             // SECURE: Validate the new data will fit in the account's allocated size
             if new_data.len() > account.size {
-                return Err("Operation would exceed account size");
+                return Err(Error::AccountTooSmall { needed: new_data.len(), available: account.size });
             }
             
             // Update account data
@@ -379,8 +443,37 @@ pub mod secure {
             Ok(())
         }
         
-        /// Serialization with proper error handling
-        fn serialize_state(&self, state: &State) -> Result<Vec<u8>, &'static str> {
+        /// Read the state currently stored in an account
+        pub fn load_state(&self, account_id: [u8; 32]) -> Result<State, Error> {
+            let account = self.accounts.get(&account_id).ok_or(Error::AccountNotFound)?;
+            Self::deserialize_state(&account.data, account.size)
+        }
+        
+        /// Serialization with borsh, as a real Solana program would do it
+        #[cfg(feature = "borsh")]
+        fn serialize_state(state: &State) -> Result<Vec<u8>, Error> {
+            borsh::BorshSerialize::try_to_vec(state).map_err(|e| Error::Serialization(e.to_string()))
+        }
+        
+        /// Deserialization with borsh, bounded by the account's allocated size
+        ///
+        /// `try_from_slice` rejects both truncated data and trailing bytes.
+        #[cfg(feature = "borsh")]
+        fn deserialize_state(data: &[u8], available: usize) -> Result<State, Error> {
+            // SECURE: Data larger than its account can only come from corruption
+            if data.len() > available {
+                return Err(Error::AccountTooSmall { needed: data.len(), available });
+            }
+            
+            borsh::BorshDeserialize::try_from_slice(data).map_err(|e| Error::Serialization(e.to_string()))
+        }
+        
+        /// Hand-rolled serialization with proper error handling
+        ///
+        /// Kept for contrast with the borsh codec: every offset and length below
+        /// is a place a manual codec can go wrong.
+        #[cfg(not(feature = "borsh"))]
+        fn serialize_state(state: &State) -> Result<Vec<u8>, Error> {
             let mut data = Vec::new();
             
             // Serialize counter
@@ -398,11 +491,17 @@ pub mod secure {
             Ok(data)
         }
         
-        /// Deserialization with proper bounds checking and error handling
-        fn deserialize_state(&self, data: &[u8]) -> Result<State, &'static str> {
+        /// Hand-rolled deserialization with proper bounds checking and error handling
+        #[cfg(not(feature = "borsh"))]
+        fn deserialize_state(data: &[u8], available: usize) -> Result<State, Error> {
+            // SECURE: Data larger than its account can only come from corruption
+            if data.len() > available {
+                return Err(Error::AccountTooSmall { needed: data.len(), available });
+            }
+            
             // SECURE: Validate minimum data size
             if data.len() < 16 {
-                return Err("Data too small to deserialize");
+                return Err(Error::Serialization(format!("{} bytes is too small for the state header", data.len())));
             }
             
             // Deserialize counter
@@ -415,9 +514,11 @@ pub mod secure {
             values_len_bytes.copy_from_slice(&data[8..16]);
             let values_len = u64::from_le_bytes(values_len_bytes) as usize;
             
-            // SECURE: Validate there's enough data for all values
-            if data.len() < 16 + (values_len * 8) {
-                return Err("Data too small to deserialize all values");
+            // SECURE: Validate there's enough data for all values, without
+            // letting a corrupt length overflow the size calculation
+            let needed = values_len.checked_mul(8).and_then(|len| len.checked_add(16));
+            if needed.is_none_or(|needed| data.len() < needed) {
+                return Err(Error::Serialization(format!("{} bytes is too small for {} values", data.len(), values_len)));
             }
             
             // Deserialize values
@@ -437,3 +538,115 @@ pub mod secure {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    
+    #[test]
+    fn test_secure_capacity_error_reports_sizes() {
+        let program_id = [1u8; 32];
+        let account_id = [2u8; 32];
+        let mut program = secure::Program::new();
+        
+        // Room for the header and two values
+        let size = secure::STATE_HEADER_LEN + 16;
+        program.initialize_account(account_id, program_id, size).unwrap();
+        for value in [10u64, 20] {
+            program.process_instruction(program_id, account_id, 1, &value.to_le_bytes()).unwrap();
+        }
+        
+        // A third value needs 8 more bytes than the account holds
+        let err = program.process_instruction(program_id, account_id, 1, &30u64.to_le_bytes()).unwrap_err();
+        assert!(matches!(err, Error::AccountTooSmall { needed, available } if needed == size + 8 && available == size));
+        assert_eq!(program.accounts[&account_id].data.len(), size);
+        
+        // Initialization reports the same way
+        let err = program.initialize_account([3u8; 32], program_id, 8).unwrap_err();
+        assert!(matches!(err, Error::AccountTooSmall { needed, available: 8 } if needed == secure::STATE_HEADER_LEN));
+    }
+    
+    #[test]
+    fn test_secure_corrupt_data_is_a_serialization_error() {
+        let program_id = [1u8; 32];
+        let account_id = [2u8; 32];
+        let mut program = secure::Program::new();
+        program.initialize_account(account_id, program_id, 64).unwrap();
+        program.process_instruction(program_id, account_id, 1, &7u64.to_le_bytes()).unwrap();
+        
+        // The length prefix now claims more values than the data holds
+        let account = program.accounts.get_mut(&account_id).unwrap();
+        account.data.pop();
+        
+        let err = program.process_instruction(program_id, account_id, 0, &[]).unwrap_err();
+        assert!(matches!(err, Error::Serialization(_)));
+        
+        assert!(matches!(program.process_instruction(program_id, [9u8; 32], 0, &[]), Err(Error::AccountNotFound)));
+        assert!(matches!(program.process_instruction([7u8; 32], account_id, 0, &[]), Err(Error::AccountConfusion)));
+    }
+    
+    #[test]
+    fn test_secure_state_round_trips() {
+        let program_id = [1u8; 32];
+        let account_id = [2u8; 32];
+        let mut program = secure::Program::new();
+        program.initialize_account(account_id, program_id, 64).unwrap();
+        
+        program.process_instruction(program_id, account_id, 0, &[]).unwrap();
+        for value in [5u64, u64::MAX] {
+            program.process_instruction(program_id, account_id, 1, &value.to_le_bytes()).unwrap();
+        }
+        
+        let state = program.load_state(account_id).unwrap();
+        assert_eq!(state, secure::State { counter: 1, values: vec![5, u64::MAX] });
+        assert_eq!(program.accounts[&account_id].data.len(), secure::STATE_HEADER_LEN + 16);
+        
+        // Data that outgrew its account is rejected before decoding
+        program.accounts.get_mut(&account_id).unwrap().size = 16;
+        assert!(matches!(program.load_state(account_id), Err(Error::AccountTooSmall { available: 16, .. })));
+    }
+    
+    #[cfg(feature = "borsh")]
+    #[test]
+    fn test_borsh_state_rejects_malformed_data() {
+        use borsh::BorshSerialize;
+        
+        let program_id = [1u8; 32];
+        let account_id = [2u8; 32];
+        let mut program = secure::Program::new();
+        program.initialize_account(account_id, program_id, 64).unwrap();
+        
+        // The stored bytes are plain borsh
+        let state = secure::State { counter: 0, values: Vec::new() };
+        assert_eq!(program.accounts[&account_id].data, state.try_to_vec().unwrap());
+        
+        // Truncated, trailing and wildly over-long data all error instead of panicking
+        for data in [vec![0u8; 11], vec![0u8; 13], [&[0u8; 8][..], &u32::MAX.to_le_bytes()].concat()] {
+            program.accounts.get_mut(&account_id).unwrap().data = data;
+            assert!(matches!(program.load_state(account_id), Err(Error::Serialization(_))));
+        }
+    }
+    
+    #[test]
+    fn test_secure_values_are_capped_at_max_values() {
+        let program_id = [1u8; 32];
+        let account_id = [2u8; 32];
+        let mut program = secure::Program::new();
+        
+        // Plenty of bytes, so only the count limit applies
+        let size = secure::STATE_HEADER_LEN + (secure::MAX_VALUES + 10) * 8;
+        program.initialize_account(account_id, program_id, size).unwrap();
+        for value in 0..secure::MAX_VALUES as u64 {
+            program.process_instruction(program_id, account_id, 1, &value.to_le_bytes()).unwrap();
+        }
+        assert_eq!(program.load_state(account_id).unwrap().values.len(), secure::MAX_VALUES);
+        
+        let err = program.process_instruction(program_id, account_id, 1, &0u64.to_le_bytes()).unwrap_err();
+        assert!(matches!(err, Error::TooManyValues { max } if max == secure::MAX_VALUES));
+        assert_eq!(program.load_state(account_id).unwrap().values.len(), secure::MAX_VALUES);
+        
+        // Other instructions still work at the cap
+        program.process_instruction(program_id, account_id, 0, &[]).unwrap();
+    }
+}
@@ -7,7 +7,10 @@
 //! This is especially important in Rust contracts where deserialization and type
 //! conversion might appear safe but still require validation of logical constraints.
 
-use crate::vulnerabilities::Vulnerability;
+use crate::vulnerabilities::{Category, Exploitability, Severity, Vulnerability};
+
+/// Per-withdrawal cap given to accounts the bank opens itself (e.g. on first deposit)
+pub const DEFAULT_WITHDRAW_LIMIT: u64 = 1_000;
 
 /// Represents an unchecked inputs vulnerability example
 pub struct UncheckedInputsVulnerability;
@@ -27,6 +30,18 @@ impl Vulnerability for UncheckedInputsVulnerability {
         vec!["Solana", "NEAR", "Polkadot", "All Rust-based contracts"]
     }
     
+    fn categories(&self) -> Vec<Category> {
+        vec![Category::InputValidation]
+    }
+    
+    fn exploitability(&self) -> Exploitability {
+        Exploitability::Trivial
+    }
+    
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+    
     fn exploit_example(&self) -> &'static str {
         r#"
         // Vulnerable function that doesn't validate inputs properly
@@ -54,6 +69,36 @@ impl Vulnerability for UncheckedInputsVulnerability {
         "#
     }
     
+    fn secure_example(&self) -> &'static str {
+        r#"
+        // Secure transfer that validates every input before touching balances
+        pub fn transfer(&mut self, sender: [u8; 32], recipient: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            // FIXED: Validate inputs
+            if amount == 0 {
+                return Err("Amount must be greater than zero");
+            }
+            
+            const MAX_TRANSFER: u64 = 1_000_000_000_000;
+            if amount > MAX_TRANSFER {
+                return Err("Amount exceeds maximum transfer limit");
+            }
+            
+            if sender == recipient {
+                return Err("Cannot transfer to self");
+            }
+            
+            let sender_account = self.accounts.get_mut(&sender).ok_or("Sender account not found")?;
+            if sender_account.balance < amount {
+                return Err("Insufficient balance");
+            }
+            sender_account.balance -= amount;
+            
+            // ...credit the recipient's account...
+            Ok(())
+        }
+        "#
+    }
+    
     fn detection_methods(&self) -> Vec<&'static str> {
         vec![
             "Look for functions that receive external inputs without validation",
@@ -82,6 +127,7 @@ pub mod vulnerable {
         pub owner: [u8; 32],
         pub balance: u64,
         pub authorized_delegates: Vec<[u8; 32]>,
+        pub withdraw_limit: u64, // Largest amount a single withdrawal may take
     }
     
     pub struct BankProgram {
@@ -119,6 +165,7 @@ pub mod vulnerable {
                 owner: recipient,
                 balance: 0,
                 authorized_delegates: Vec::new(),
+                withdraw_limit: super::DEFAULT_WITHDRAW_LIMIT,
             });
             recipient_account.balance += amount;
             
@@ -141,27 +188,119 @@ pub mod vulnerable {
             
             Ok(())
         }
+        
+        /// Vulnerable transfer made by a delegate on the owner's behalf
+        pub fn transfer_as_delegate(&mut self, owner: [u8; 32], _delegate: [u8; 32], recipient: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            // VULNERABILITY: `_delegate` is never looked up in `authorized_delegates`,
+            // so the stored list protects nothing and anyone can move the owner's funds
+            self.transfer(owner, recipient, amount)
+        }
+        
+        /// Vulnerable withdrawal that ignores the account's withdraw limit
+        pub fn withdraw(&mut self, account: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            let user_account = match self.accounts.get_mut(&account) {
+                Some(account) => account,
+                None => return Err("Account not found"),
+            };
+            
+            // VULNERABILITY: `withdraw_limit` is stored but never enforced, so
+            // a compromised key can drain the whole balance in one call
+            if user_account.balance < amount {
+                return Err("Insufficient balance");
+            }
+            
+            user_account.balance -= amount;
+            
+            Ok(())
+        }
     }
 }
 
 /// Example of secure code with proper input validation
 pub mod secure {
+    use crate::utils::rbac::{Role, RoleRegistry};
+    
+    /// Delegates an account may authorize unless the deployment configures otherwise
+    pub const DEFAULT_MAX_DELEGATES: usize = 5;
+    
     #[derive(Debug, Clone)]
     pub struct UserAccount {
         pub owner: [u8; 32],
         pub balance: u64,
         pub authorized_delegates: Vec<[u8; 32]>,
+        pub withdraw_limit: u64, // Largest amount a single withdrawal may take
     }
     
     pub struct BankProgram {
         pub accounts: std::collections::HashMap<[u8; 32], UserAccount>,
+        pub max_delegates: usize,
+        pub roles: RoleRegistry, // Accounts with `Role::Admin` may tune parameters
+        pub processed_transfers: std::collections::HashMap<[u8; 32], ([u8; 32], [u8; 32], u64)>, // idempotency key -> (sender, recipient, amount)
+        pub frozen: std::collections::HashSet<[u8; 32]>, // Accounts that may neither send nor receive
+        pub approvals: std::collections::HashMap<([u8; 32], [u8; 32]), u64>, // (owner, spender) -> amount spender may move
     }
     
     impl BankProgram {
-        pub fn new() -> Self {
+        pub fn new(admin: [u8; 32]) -> Self {
+            Self::with_max_delegates(admin, DEFAULT_MAX_DELEGATES)
+        }
+        
+        /// Bank whose accounts may each authorize up to `max_delegates` delegates
+        ///
+        /// `admin` is the only account that can tune parameters or freeze
+        /// accounts until it grants `Role::Admin` to others.
+        pub fn with_max_delegates(admin: [u8; 32], max_delegates: usize) -> Self {
             Self {
                 accounts: std::collections::HashMap::new(),
+                max_delegates,
+                roles: RoleRegistry::with_admin(admin),
+                processed_transfers: std::collections::HashMap::new(),
+                frozen: std::collections::HashSet::new(),
+                approvals: std::collections::HashMap::new(),
+            }
+        }
+        
+        /// Admin-only change of the per-account delegate limit
+        ///
+        /// The limit can't drop below the longest existing delegate list, so
+        /// every account stays within the limit it is checked against.
+        pub fn set_max_delegates(&mut self, caller: [u8; 32], max_delegates: usize) -> Result<(), &'static str> {
+            if !self.roles.has_role(&caller, &Role::Admin) {
+                return Err("Only admin can change max delegates");
+            }
+            
+            let most_delegates = self.accounts.values()
+                .map(|account| account.authorized_delegates.len())
+                .max()
+                .unwrap_or(0);
+            if max_delegates < most_delegates {
+                return Err("Limit is below an existing account's delegate count");
             }
+            
+            self.max_delegates = max_delegates;
+            Ok(())
+        }
+        
+        /// Admin-only freeze of `account` for compliance holds
+        ///
+        /// A frozen account can neither send, receive, nor withdraw. Any key can
+        /// be frozen, including ones with no account yet and the zero address
+        /// (which stops transfers into it). Freezing twice is a no-op.
+        pub fn freeze(&mut self, caller: [u8; 32], account: [u8; 32]) -> Result<(), &'static str> {
+            if !self.roles.has_role(&caller, &Role::Admin) {
+                return Err("Only admin can freeze accounts");
+            }
+            self.frozen.insert(account);
+            Ok(())
+        }
+        
+        /// Admin-only release of a frozen account
+        pub fn unfreeze(&mut self, caller: [u8; 32], account: [u8; 32]) -> Result<(), &'static str> {
+            if !self.roles.has_role(&caller, &Role::Admin) {
+                return Err("Only admin can unfreeze accounts");
+            }
+            self.frozen.remove(&account);
+            Ok(())
         }
         
         /// Secure function with proper input validation
@@ -184,6 +323,10 @@ pub mod secure {
                 return Err("Cannot transfer to self");
             }
             
+            if self.frozen.contains(&sender) || self.frozen.contains(&recipient) {
+                return Err("account frozen");
+            }
+            
             // Get sender account
             let sender_account = match self.accounts.get(&sender) {
                 Some(account) => account,
@@ -202,12 +345,86 @@ pub mod secure {
                 owner: recipient,
                 balance: 0,
                 authorized_delegates: Vec::new(),
+                withdraw_limit: super::DEFAULT_WITHDRAW_LIMIT,
             });
             recipient_account.balance += amount;
             
             Ok(())
         }
         
+        /// Transfer that runs at most once per client-supplied `idempotency_key`
+        ///
+        /// Resubmitting a key with the same parameters is a successful no-op.
+        /// The key's parameters are kept rather than just the key, so a reused
+        /// key describing a different transfer is an error, not a silent skip.
+        /// A transfer that fails does not consume its key.
+        pub fn transfer_idempotent(&mut self, sender: [u8; 32], recipient: [u8; 32], amount: u64, idempotency_key: [u8; 32]) -> Result<(), &'static str> {
+            if let Some(&processed) = self.processed_transfers.get(&idempotency_key) {
+                if processed != (sender, recipient, amount) {
+                    return Err("idempotency key reuse mismatch");
+                }
+                return Ok(());
+            }
+            
+            self.transfer(sender, recipient, amount)?;
+            self.processed_transfers.insert(idempotency_key, (sender, recipient, amount));
+            
+            Ok(())
+        }
+        
+        /// Amount `spender` may still move out of `owner`'s account
+        pub fn allowance(&self, owner: [u8; 32], spender: [u8; 32]) -> u64 {
+            self.approvals.get(&(owner, spender)).copied().unwrap_or(0)
+        }
+        
+        /// Set `spender`'s allowance over `owner`'s funds to exactly `amount`
+        ///
+        /// Overwriting a non-zero allowance is open to front-running: a spender
+        /// who sees the new approval pending can spend the old allowance first
+        /// and then the new one as well. Prefer `increase_allowance` and
+        /// `decrease_allowance`, or reset to zero before approving a new amount.
+        pub fn approve(&mut self, owner: [u8; 32], spender: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            if owner == spender {
+                return Err("Cannot approve self");
+            }
+            if amount == 0 {
+                self.approvals.remove(&(owner, spender));
+            } else {
+                self.approvals.insert((owner, spender), amount);
+            }
+            Ok(())
+        }
+        
+        /// Raise `spender`'s allowance by `added`, returning the new allowance
+        pub fn increase_allowance(&mut self, owner: [u8; 32], spender: [u8; 32], added: u64) -> Result<u64, &'static str> {
+            let allowance = self.allowance(owner, spender).checked_add(added).ok_or("Allowance overflow")?;
+            self.approve(owner, spender, allowance)?;
+            Ok(allowance)
+        }
+        
+        /// Lower `spender`'s allowance by `subtracted`, returning the new allowance
+        pub fn decrease_allowance(&mut self, owner: [u8; 32], spender: [u8; 32], subtracted: u64) -> Result<u64, &'static str> {
+            let allowance = self.allowance(owner, spender).checked_sub(subtracted).ok_or("Allowance would go below zero")?;
+            self.approve(owner, spender, allowance)?;
+            Ok(allowance)
+        }
+        
+        /// Move `amount` of `owner`'s funds to `recipient` on `spender`'s allowance
+        ///
+        /// The allowance is only reduced if the transfer succeeds, so a failed
+        /// transfer leaves both the balance and the allowance untouched. The
+        /// usual transfer checks apply, so `recipient` may not be `owner`.
+        pub fn transfer_from(&mut self, spender: [u8; 32], owner: [u8; 32], recipient: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            // FIXED: Spending beyond the allowance is rejected, never wrapped
+            let allowance = self.allowance(owner, spender);
+            if amount > allowance {
+                return Err("Insufficient allowance");
+            }
+            
+            self.transfer(owner, recipient, amount)?;
+            self.approve(owner, spender, allowance - amount)
+        }
+        
         /// Secure function with proper delegate validation
         pub fn add_delegate(&mut self, account: [u8; 32], delegate: [u8; 32]) -> Result<(), &'static str> {
             // FIXED: Validate inputs
@@ -228,8 +445,7 @@ pub mod secure {
             }
             
             // Check maximum number of delegates
-            const MAX_DELEGATES: usize = 5; // Example limit
-            if user_account.authorized_delegates.len() >= MAX_DELEGATES {
+            if user_account.authorized_delegates.len() >= self.max_delegates {
                 return Err("Maximum number of delegates reached");
             }
             
@@ -237,6 +453,51 @@ pub mod secure {
             
             Ok(())
         }
+        
+        /// Secure transfer made by a delegate on the owner's behalf
+        pub fn transfer_as_delegate(&mut self, owner: [u8; 32], delegate: [u8; 32], recipient: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            let owner_account = match self.accounts.get(&owner) {
+                Some(account) => account,
+                None => return Err("Sender account not found"),
+            };
+            
+            // FIXED: Only delegates the owner has authorized may act for them
+            if !owner_account.authorized_delegates.contains(&delegate) {
+                return Err("Delegate not authorized");
+            }
+            
+            // Same amount, self-transfer and limit checks as an owner's transfer
+            self.transfer(owner, recipient, amount)
+        }
+        
+        /// Secure withdrawal that enforces the account's withdraw limit
+        pub fn withdraw(&mut self, account: [u8; 32], amount: u64) -> Result<(), &'static str> {
+            // FIXED: Validate inputs
+            if amount == 0 {
+                return Err("Amount must be greater than zero");
+            }
+            
+            if self.frozen.contains(&account) {
+                return Err("account frozen");
+            }
+            
+            let user_account = match self.accounts.get_mut(&account) {
+                Some(account) => account,
+                None => return Err("Account not found"),
+            };
+            
+            if amount > user_account.withdraw_limit {
+                return Err("Amount exceeds withdraw limit");
+            }
+            
+            if user_account.balance < amount {
+                return Err("Insufficient balance");
+            }
+            
+            user_account.balance -= amount;
+            
+            Ok(())
+        }
     }
 }
 
@@ -244,6 +505,18 @@ pub mod secure {
 mod tests {
     use super::*;
     
+    const ADMIN: [u8; 32] = [9u8; 32];
+    
+    /// Account for `owner` holding `balance`, with no delegates and the default withdraw limit
+    fn account(owner: [u8; 32], balance: u64) -> secure::UserAccount {
+        secure::UserAccount {
+            owner,
+            balance,
+            authorized_delegates: Vec::new(),
+            withdraw_limit: DEFAULT_WITHDRAW_LIMIT,
+        }
+    }
+    
     #[test]
     fn test_vulnerable_unchecked_inputs() {
         let mut program = vulnerable::BankProgram::new();
@@ -254,6 +527,7 @@ mod tests {
             owner: account_id,
             balance: 100,
             authorized_delegates: Vec::new(),
+            withdraw_limit: DEFAULT_WITHDRAW_LIMIT,
         });
         
         // Zero amount transfer succeeds in vulnerable implementation
@@ -267,15 +541,11 @@ mod tests {
     
     #[test]
     fn test_secure_input_validation() {
-        let mut program = secure::BankProgram::new();
+        let mut program = secure::BankProgram::new(ADMIN);
         let account_id = [1u8; 32];
         
         // Create account with 100 tokens
-        program.accounts.insert(account_id, secure::UserAccount {
-            owner: account_id,
-            balance: 100,
-            authorized_delegates: Vec::new(),
-        });
+        program.accounts.insert(account_id, account(account_id, 100));
         
         // Zero amount transfer fails in secure implementation
         let result = program.transfer(account_id, [2u8; 32], 0);
@@ -287,4 +557,200 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "Cannot add self as delegate");
     }
+    
+    #[test]
+    fn test_withdraw_limit_enforcement() {
+        let account_id = [1u8; 32];
+        
+        // Vulnerable: a single call takes more than the limit allows
+        let mut program = vulnerable::BankProgram::new();
+        program.accounts.insert(account_id, vulnerable::UserAccount {
+            owner: account_id,
+            balance: 500,
+            authorized_delegates: Vec::new(),
+            withdraw_limit: 100,
+        });
+        assert!(program.withdraw(account_id, 500).is_ok());
+        assert_eq!(program.accounts[&account_id].balance, 0);
+        
+        // Secure: the same withdrawal is rejected, but one at the limit succeeds
+        let mut program = secure::BankProgram::new(ADMIN);
+        program.accounts.insert(account_id, secure::UserAccount {
+            withdraw_limit: 100,
+            ..account(account_id, 500)
+        });
+        assert_eq!(program.withdraw(account_id, 500), Err("Amount exceeds withdraw limit"));
+        assert_eq!(program.withdraw(account_id, 0), Err("Amount must be greater than zero"));
+        assert!(program.withdraw(account_id, 100).is_ok());
+        assert_eq!(program.accounts[&account_id].balance, 400);
+        
+        // Within the limit, the balance still has to cover the amount
+        program.accounts.get_mut(&account_id).unwrap().balance = 50;
+        assert_eq!(program.withdraw(account_id, 100), Err("Insufficient balance"));
+    }
+    
+    #[test]
+    fn test_transfer_as_delegate_checks_authorization() {
+        let owner = [1u8; 32];
+        let delegate = [2u8; 32];
+        let stranger = [3u8; 32];
+        let recipient = [4u8; 32];
+        
+        // Vulnerable: a delegate nobody authorized moves the owner's funds
+        let mut program = vulnerable::BankProgram::new();
+        program.accounts.insert(owner, vulnerable::UserAccount {
+            owner,
+            balance: 100,
+            authorized_delegates: vec![delegate],
+            withdraw_limit: DEFAULT_WITHDRAW_LIMIT,
+        });
+        assert!(program.transfer_as_delegate(owner, stranger, stranger, 100).is_ok());
+        assert_eq!(program.accounts[&stranger].balance, 100);
+        
+        // Secure: only the authorized delegate gets through, and only with valid inputs
+        let mut program = secure::BankProgram::new(ADMIN);
+        program.accounts.insert(owner, secure::UserAccount {
+            authorized_delegates: vec![delegate],
+            ..account(owner, 100)
+        });
+        assert_eq!(program.transfer_as_delegate(owner, stranger, stranger, 100), Err("Delegate not authorized"));
+        assert_eq!(program.transfer_as_delegate(owner, delegate, recipient, 0), Err("Amount must be greater than zero"));
+        assert_eq!(program.transfer_as_delegate(owner, delegate, owner, 10), Err("Cannot transfer to self"));
+        
+        program.transfer_as_delegate(owner, delegate, recipient, 40).unwrap();
+        assert_eq!(program.accounts[&owner].balance, 60);
+        assert_eq!(program.accounts[&recipient].balance, 40);
+    }
+    
+    #[test]
+    fn test_secure_max_delegates_is_configurable() {
+        let owner = [1u8; 32];
+        let delegate = |i: u8| [100 + i; 32];
+        let open_account = |program: &mut secure::BankProgram| {
+            program.accounts.insert(owner, account(owner, 0));
+        };
+        
+        // Default deployment: the sixth delegate is refused
+        let mut program = secure::BankProgram::new(ADMIN);
+        assert_eq!(program.max_delegates, secure::DEFAULT_MAX_DELEGATES);
+        open_account(&mut program);
+        for i in 0..5 {
+            program.add_delegate(owner, delegate(i)).unwrap();
+        }
+        assert_eq!(program.add_delegate(owner, delegate(5)), Err("Maximum number of delegates reached"));
+        
+        // Only an admin may raise the limit
+        assert_eq!(program.set_max_delegates(owner, 8), Err("Only admin can change max delegates"));
+        program.set_max_delegates(ADMIN, 8).unwrap();
+        program.add_delegate(owner, delegate(5)).unwrap();
+        
+        // Lowering below the six delegates already held is rejected; down to six is fine
+        assert_eq!(program.set_max_delegates(ADMIN, 5), Err("Limit is below an existing account's delegate count"));
+        program.set_max_delegates(ADMIN, 6).unwrap();
+        assert_eq!(program.add_delegate(owner, delegate(6)), Err("Maximum number of delegates reached"));
+        
+        // A deployment can start with its own limit
+        let mut program = secure::BankProgram::with_max_delegates(ADMIN, 1);
+        open_account(&mut program);
+        program.add_delegate(owner, delegate(0)).unwrap();
+        assert_eq!(program.add_delegate(owner, delegate(1)), Err("Maximum number of delegates reached"));
+    }
+    
+    #[test]
+    fn test_secure_idempotent_transfers() {
+        let sender = [1u8; 32];
+        let recipient = [2u8; 32];
+        let key = [7u8; 32];
+        
+        let mut program = secure::BankProgram::new(ADMIN);
+        program.accounts.insert(sender, account(sender, 1_000));
+        
+        // First use is validated like any transfer and does not consume the key on failure
+        assert_eq!(program.transfer_idempotent(sender, recipient, 0, key), Err("Amount must be greater than zero"));
+        program.transfer_idempotent(sender, recipient, 300, key).unwrap();
+        
+        // A resubmission is a no-op
+        program.transfer_idempotent(sender, recipient, 300, key).unwrap();
+        assert_eq!(program.accounts[&sender].balance, 700);
+        assert_eq!(program.accounts[&recipient].balance, 300);
+        
+        // The same key for a different transfer is rejected, not skipped
+        assert_eq!(program.transfer_idempotent(sender, recipient, 301, key), Err("idempotency key reuse mismatch"));
+        assert_eq!(program.transfer_idempotent(sender, [3u8; 32], 300, key), Err("idempotency key reuse mismatch"));
+        assert_eq!(program.accounts[&sender].balance, 700);
+        
+        // A fresh key transfers again
+        program.transfer_idempotent(sender, recipient, 300, [8u8; 32]).unwrap();
+        assert_eq!(program.accounts[&recipient].balance, 600);
+    }
+    
+    #[test]
+    fn test_secure_frozen_accounts_cannot_send_or_receive() {
+        let (alice, bob, carol) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+        let mut program = secure::BankProgram::new(ADMIN);
+        for owner in [alice, bob] {
+            program.accounts.insert(owner, account(owner, 1_000));
+        }
+        
+        assert_eq!(program.freeze(alice, bob), Err("Only admin can freeze accounts"));
+        program.freeze(ADMIN, bob).unwrap();
+        
+        // Blocked as recipient and as sender, including via delegates and withdrawals
+        assert_eq!(program.transfer(alice, bob, 10), Err("account frozen"));
+        assert_eq!(program.transfer(bob, carol, 10), Err("account frozen"));
+        assert_eq!(program.withdraw(bob, 10), Err("account frozen"));
+        program.add_delegate(bob, carol).unwrap();
+        assert_eq!(program.transfer_as_delegate(bob, carol, alice, 10), Err("account frozen"));
+        
+        // Both sides frozen is still just frozen
+        program.freeze(ADMIN, alice).unwrap();
+        assert_eq!(program.transfer(alice, bob, 10), Err("account frozen"));
+        
+        // Freezing the zero address stops transfers into it
+        program.freeze(ADMIN, [0u8; 32]).unwrap();
+        program.unfreeze(ADMIN, alice).unwrap();
+        assert_eq!(program.transfer(alice, [0u8; 32], 10), Err("account frozen"));
+        
+        // Unfreezing restores transfers
+        assert_eq!(program.unfreeze(bob, bob), Err("Only admin can unfreeze accounts"));
+        program.unfreeze(ADMIN, bob).unwrap();
+        program.transfer(alice, bob, 10).unwrap();
+        program.transfer(bob, carol, 10).unwrap();
+        assert_eq!(program.accounts[&bob].balance, 1_000);
+        assert_eq!(program.accounts[&carol].balance, 10);
+    }
+    
+    #[test]
+    fn test_secure_transfer_from_spends_allowance() {
+        let (owner, spender, shop) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+        let mut program = secure::BankProgram::new(ADMIN);
+        program.accounts.insert(owner, account(owner, 1_000));
+        
+        assert_eq!(program.transfer_from(spender, owner, shop, 1), Err("Insufficient allowance"));
+        assert_eq!(program.approve(owner, owner, 100), Err("Cannot approve self"));
+        program.approve(owner, spender, 100).unwrap();
+        
+        // Each spend draws down the allowance along with the balance
+        program.transfer_from(spender, owner, shop, 60).unwrap();
+        assert_eq!(program.allowance(owner, spender), 40);
+        assert_eq!(program.accounts[&owner].balance, 940);
+        assert_eq!(program.transfer_from(spender, owner, shop, 41), Err("Insufficient allowance"));
+        
+        // The spender may pay itself, but not send the owner's funds back to the owner
+        program.transfer_from(spender, owner, spender, 10).unwrap();
+        assert_eq!(program.transfer_from(spender, owner, owner, 10), Err("Cannot transfer to self"));
+        assert_eq!(program.allowance(owner, spender), 30);
+        
+        // Incremental changes avoid overwriting an allowance that may be mid-spend
+        assert_eq!(program.increase_allowance(owner, spender, 20), Ok(50));
+        assert_eq!(program.decrease_allowance(owner, spender, 51), Err("Allowance would go below zero"));
+        assert_eq!(program.decrease_allowance(owner, spender, 50), Ok(0));
+        assert!(program.approvals.is_empty());
+        
+        // A failed transfer leaves the allowance intact
+        program.approve(owner, spender, 5_000).unwrap();
+        assert_eq!(program.transfer_from(spender, owner, shop, 2_000), Err("Insufficient balance"));
+        assert_eq!(program.allowance(owner, spender), 5_000);
+        assert_eq!(program.accounts[&shop].balance, 60);
+    }
 }
@@ -0,0 +1,29 @@
+//! Shared helpers for integration tests
+
+use proptest::prelude::*;
+
+/// Largest reserve a generated pool starts with
+pub const MAX_RESERVE: u64 = 1_000_000_000_000_000_000;
+
+/// Largest input amount for a single generated swap
+pub const MAX_SWAP_AMOUNT: u64 = 100_000_000_000_000_000;
+
+/// A swap direction (`true` swaps token A in) and its input amount
+pub type Swap = (bool, u64);
+
+/// Random constant-product pool reserves paired with a sequence of swaps.
+///
+/// Reserves and amounts are large enough that `x * y` overflows a u64, but
+/// small enough that the reserves themselves stay within range.
+pub fn pool_and_swaps() -> impl Strategy<Value = (u64, u64, Vec<Swap>)> {
+    (
+        1..=MAX_RESERVE,
+        1..=MAX_RESERVE,
+        prop::collection::vec((any::<bool>(), 1..=MAX_SWAP_AMOUNT), 1..16),
+    )
+}
+
+/// Constant-product invariant `k = x * y` without overflow
+pub fn k(x: u64, y: u64) -> u128 {
+    x as u128 * y as u128
+}
@@ -0,0 +1,5 @@
+const PRIVATE_KEY: &str = "5KQwrPbwdL6PhXujxW37FSSQZ1JiwsST4cqQzDeyXtP79zkvFD3";
+
+pub fn credit(balance: &mut u64, amount: u64) {
+    *balance += amount;
+}
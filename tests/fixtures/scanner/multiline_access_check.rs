@@ -0,0 +1,13 @@
+impl Config {
+    pub fn set_fee(&mut self,
+                   caller: AccountId,
+                   new_fee: u64) -> Result<(), Error> {
+        require!(caller == self.admin, Error::Unauthorized);
+        self.fee = new_fee;
+        Ok(())
+    }
+    
+    pub fn set_fee_unchecked(&mut self, new_fee: u64) {
+        self.fee = new_fee;
+    }
+}
@@ -0,0 +1,35 @@
+impl Program {
+    pub fn update_admin(&mut self, caller: [u8; 32], new_admin: [u8; 32]) -> Result<(), &'static str> {
+        if caller != self.config.admin {
+            return Err("Unauthorized");
+        }
+
+        self.config.admin = new_admin;
+
+        Ok(())
+    }
+
+    pub fn update_admin_logged(&mut self, caller: [u8; 32], new_admin: [u8; 32], timestamp: u64) -> Result<(), &'static str> {
+        if caller != self.config.admin {
+            return Err("Unauthorized");
+        }
+
+        let previous_admin = self.config.admin;
+        self.config.admin = new_admin;
+        self.admin_events.push(AdminChangedEvent { previous_admin, new_admin, timestamp });
+
+        Ok(())
+    }
+
+    pub fn sweep(&mut self, recipient: [u8; 32]) -> Result<(), &'static str> {
+        require!(self.config.admin == recipient, "Unauthorized");
+        self.vault.transfer(recipient, self.treasury_balance)
+    }
+
+    pub fn sweep_logged(&mut self, recipient: [u8; 32], timestamp: u64) -> Result<(), &'static str> {
+        require!(self.config.admin == recipient, "Unauthorized");
+        self.vault.transfer(recipient, self.treasury_balance)?;
+        self.events.emit("Swept", &[("recipient", format_address(&recipient))], timestamp);
+        Ok(())
+    }
+}
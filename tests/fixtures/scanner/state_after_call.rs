@@ -0,0 +1,54 @@
+impl VaultProgram {
+    pub fn withdraw(&mut self, caller: [u8; 32], recipient: [u8; 32], amount: u64, on_transfer: &mut dyn FnMut(&mut VaultProgram)) -> Result<(), &'static str> {
+        let account = match self.accounts.get(&caller) {
+            Some(account) => account,
+            None => return Err("Account not found"),
+        };
+        if account.balance < amount {
+            return Err("Insufficient balance");
+        }
+        
+        self.transfer_tokens(caller, recipient, amount, on_transfer)?;
+        
+        let account = self.accounts.get_mut(&caller).unwrap();
+        account.balance = account.balance.wrapping_sub(amount);
+        Ok(())
+    }
+    
+    pub fn withdraw_secure(&mut self, caller: [u8; 32], recipient: [u8; 32], amount: u64, on_transfer: &mut dyn FnMut(&mut VaultProgram)) -> Result<(), &'static str> {
+        if self.reentrancy_lock {
+            return Err("Reentrancy detected");
+        }
+        self.reentrancy_lock = true;
+        
+        let account = self.accounts.get_mut(&caller).ok_or("Account not found")?;
+        if account.balance < amount {
+            self.reentrancy_lock = false;
+            return Err("Insufficient balance");
+        }
+        account.balance -= amount;
+        
+        let result = self.transfer_tokens(caller, recipient, amount, on_transfer);
+        self.reentrancy_lock = false;
+        result
+    }
+    
+    pub fn withdraw_and_report(&mut self, caller: [u8; 32], amount: u64) -> Result<u64, &'static str> {
+        let account = self.accounts.get_mut(&caller).ok_or("Account not found")?;
+        account.balance -= amount;
+        
+        invoke(&transfer_instruction(caller, amount))?;
+        
+        let remaining = self.accounts[&caller].balance;
+        if remaining <= self.min_balance {
+            return Err("Balance below minimum");
+        }
+        Ok(remaining)
+    }
+    
+    pub fn settle(&mut self, amount: u64) -> Result<(), &'static str> {
+        self.vault.invoke_signed_transfer(amount)?;
+        self.total_deposits -= amount;
+        Ok(())
+    }
+}
@@ -0,0 +1,3 @@
+pub fn credit(total: &mut u64, amount: u64) {
+    *total += amount;
+}
@@ -0,0 +1,4 @@
+pub fn withdraw(vault: &mut Vault, ix: &Instruction, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    invoke(ix, accounts)?; vault.balance -= amount;
+    Ok(())
+}
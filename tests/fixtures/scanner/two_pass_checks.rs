@@ -0,0 +1,19 @@
+pub fn deposit(accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vault = next_account_info(accounts_iter)?;
+    invoke(&deposit_ix(vault.key, amount), accounts);
+    Ok(())
+}
+
+pub fn withdraw(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authority = next_account_info(accounts_iter)?;
+    assert!(authority.is_signer);
+    let vault = next_account_info(accounts_iter)?;
+    if vault.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let result = invoke(&withdraw_ix(vault.key, amount), accounts);
+    result?;
+    Ok(())
+}
@@ -0,0 +1,44 @@
+pub struct Auction {
+    pub highest_bidder: Option<[u8; 32]>,
+    pub bidders: Vec<[u8; 32]>,
+    pub bids: Vec<(u64, [u8; 32])>,
+    pub ended: bool,
+}
+
+impl Auction {
+    pub fn place_bid(&mut self, bidder: [u8; 32], amount: u64) {
+        self.bids.push((amount, bidder));
+        self.bidders.push(bidder);
+    }
+
+    pub fn end_auction(&mut self) -> Result<(), &'static str> {
+        self.ended = true;
+        for bidder in &self.bidders {
+            if Some(*bidder) != self.highest_bidder {
+                self.refund_bidder(*bidder)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn refund_first_page(&self) {
+        for bidder in self.bidders.iter().take(10) {
+            let _ = self.refund_bidder(*bidder);
+        }
+    }
+
+    pub fn refund_until_limit(&self) {
+        let mut processed = 0;
+        for (_, bidder) in self.bids.iter() {
+            if processed >= 10 {
+                break;
+            }
+            let _ = self.refund_bidder(*bidder);
+            processed += 1;
+        }
+    }
+
+    fn refund_bidder(&self, _bidder: [u8; 32]) -> Result<(), &'static str> {
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use std::process::Command;
+
+use rust_smart_contracts_vulns::vulnerabilities::{vulnerability_by_slug, VULNERABILITY_SLUGS};
+
+fn guide() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_rust-smart-contracts-vulns"))
+}
+
+#[test]
+fn test_json_flag_prints_report() {
+    let report = |args: &[&str]| -> serde_json::Value {
+        let output = guide().args(args).output().unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice(&output.stdout).unwrap()
+    };
+    
+    assert_eq!(report(&["show", "reentrancy", "--json"])["name"], "Reentrancy Vulnerability");
+    assert_eq!(report(&["json", "reentrancy"])["name"], "Reentrancy Vulnerability");
+}
+
+#[test]
+fn test_text_output_is_default() {
+    let output = guide().args(["show", "reentrancy"]).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    
+    assert!(stdout.starts_with("Rust Smart Contract Vulnerabilities Guide"));
+    assert!(stdout.contains("\nReentrancy Vulnerability\n"));
+}
+
+#[test]
+fn test_unknown_vulnerability_is_rejected() {
+    let output = guide().args(["show", "nonexistent"]).output().unwrap();
+    assert!(!output.status.success());
+    
+    // clap lists the registered slugs in its error
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("inadequate_events"));
+}
+
+#[test]
+fn test_every_slug_is_shown() {
+    assert_eq!(VULNERABILITY_SLUGS.len(), 15);
+    
+    for slug in VULNERABILITY_SLUGS {
+        let output = guide().args(["show", slug]).output().unwrap();
+        assert!(output.status.success(), "`show {}` failed", slug);
+        
+        let name = vulnerability_by_slug(slug).unwrap().name();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains(&format!("\n{}\n", name)), "`show {}` did not print {}", slug, name);
+    }
+}
+
+#[test]
+fn test_search_lists_matching_vulnerabilities() {
+    let output = guide().args(["search", "twap"]).output().unwrap();
+    assert!(output.status.success());
+    
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("  - Flash Loan Vulnerability\n"));
+    assert!(stdout.contains("  - Oracle Manipulation Vulnerability\n"));
+    assert!(!stdout.contains("Reentrancy"));
+}
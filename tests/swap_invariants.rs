@@ -0,0 +1,57 @@
+//! Property tests for the constant-product pools
+
+mod common;
+
+use std::collections::HashMap;
+
+use common::{k, pool_and_swaps};
+use proptest::prelude::*;
+use rust_smart_contracts_vulns::vulnerabilities::{flash_loan, front_running};
+
+proptest! {
+    #[test]
+    fn flash_loan_swaps_never_decrease_k((reserve_a, reserve_b, swaps) in pool_and_swaps()) {
+        let mut pool = flash_loan::secure::DexPool {
+            token_a: "TOKEN".to_string(),
+            token_b: "USDC".to_string(),
+            token_a_reserves: reserve_a,
+            token_b_reserves: reserve_b,
+        };
+        
+        for (a_in, amount_in) in swaps {
+            let (old_a, old_b) = (pool.token_a_reserves, pool.token_b_reserves);
+            let out_reserves = if a_in { old_b } else { old_a };
+            
+            let amount_out = pool.swap(if a_in { "TOKEN" } else { "USDC" }, amount_in);
+            
+            prop_assert!(amount_out < out_reserves);
+            prop_assert!(k(pool.token_a_reserves, pool.token_b_reserves) >= k(old_a, old_b));
+        }
+    }
+    
+    #[test]
+    fn front_running_swaps_never_decrease_k((reserve_a, reserve_b, swaps) in pool_and_swaps()) {
+        let token_a = [1u8; 32];
+        let token_b = [2u8; 32];
+        
+        let mut dex = front_running::vulnerable::DEX::new();
+        let pool_id = dex.create_pool(token_a, token_b, reserve_a, reserve_b);
+        let mut user = front_running::vulnerable::UserAccount {
+            owner: [3u8; 32],
+            balances: HashMap::from([(token_a, u64::MAX / 2), (token_b, u64::MAX / 2)]),
+        };
+        
+        for (a_in, amount_in) in swaps {
+            let pool = &dex.pools[&pool_id];
+            let (old_a, old_b) = (pool.token_a_reserves, pool.token_b_reserves);
+            let out_reserves = if a_in { old_b } else { old_a };
+            
+            let token_in = if a_in { token_a } else { token_b };
+            let amount_out = dex.swap(pool_id, &mut user, token_in, amount_in, 0).unwrap();
+            
+            let pool = &dex.pools[&pool_id];
+            prop_assert!(amount_out < out_reserves);
+            prop_assert!(k(pool.token_a_reserves, pool.token_b_reserves) >= k(old_a, old_b));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+use std::process::Command;
+
+fn scanner() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_vuln_scanner"))
+}
+
+fn fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/scanner")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn test_fail_on_high_exits_with_findings_code() {
+    let status = scanner()
+        .args(["scan", "--path", &fixture("high_severity.rs"), "--fail-on", "high"])
+        .status()
+        .unwrap();
+    
+    assert_eq!(status.code(), Some(1));
+}
+
+#[test]
+fn test_no_fail_on_exits_clean() {
+    let status = scanner()
+        .args(["scan", "--path", &fixture("high_severity.rs")])
+        .status()
+        .unwrap();
+    
+    assert_eq!(status.code(), Some(0));
+}
+
+#[test]
+fn test_describe_prints_library_writeup() {
+    let output = scanner()
+        .args(["describe", "reentrancy"])
+        .output()
+        .unwrap();
+    
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Reentrancy Vulnerability"));
+    assert!(stdout.contains("  - Follow the checks-effects-interactions pattern"));
+}
+
+#[test]
+fn test_describe_unknown_name_is_a_scan_error() {
+    let status = scanner()
+        .args(["describe", "no-such-vulnerability"])
+        .status()
+        .unwrap();
+    
+    assert_eq!(status.code(), Some(2));
+}
+
+#[test]
+fn test_line_range_requires_a_single_file() {
+    let status = scanner()
+        .args(["scan", "--path", &fixture("two_files"), "--lines", "1:10"])
+        .status()
+        .unwrap();
+    
+    assert_eq!(status.code(), Some(2));
+}
+
+#[test]
+fn test_missing_or_non_rust_path_is_a_scan_error() {
+    let scan = |path: &str| scanner().args(["scan", "--path", path]).status().unwrap().code();
+    
+    assert_eq!(scan("/nonexistent"), Some(2));
+    assert_eq!(scan(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")), Some(2));
+}
+
+#[test]
+fn test_gitignored_directories_are_skipped_unless_no_ignore() {
+    let project = std::env::temp_dir().join(format!("vuln_scanner_ignore_{}", std::process::id()));
+    std::fs::create_dir_all(project.join("vendor")).unwrap();
+    std::fs::write(project.join(".gitignore"), "vendor/\n").unwrap();
+    std::fs::copy(fixture("high_severity.rs"), project.join("vendor/high_severity.rs")).unwrap();
+    
+    let scan = |extra: &[&str]| {
+        let output = scanner()
+            .args(["scan", "--path", project.to_str().unwrap(), "--fail-on", "high"])
+            .args(extra)
+            .output()
+            .unwrap();
+        output.status.code()
+    };
+    
+    assert_eq!(scan(&[]), Some(0));
+    assert_eq!(scan(&["--no-ignore"]), Some(1));
+    
+    std::fs::remove_dir_all(project).unwrap();
+}
+
+#[test]
+fn test_verify_exits_with_findings_code_when_a_mitigation_is_missing() {
+    let reentrancy = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/vulnerabilities/reentrancy.rs");
+    let verify = |require: &str| {
+        scanner()
+            .args(["verify", "--path", reentrancy.to_str().unwrap(), "--require", require])
+            .status()
+            .unwrap()
+            .code()
+    };
+    
+    assert_eq!(verify("reentrancy_lock"), Some(0));
+    assert_eq!(verify("reentrancy_lock,get_twap"), Some(1));
+    assert_eq!(verify("no_such_mitigation"), Some(2));
+}